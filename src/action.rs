@@ -0,0 +1,369 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+// Named commands which can be performed on the application. Keys, mouse
+// gestures and menus are all mapped to actions, so that each command has a
+// single implementation in `App::perform`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    NextLine,
+    PreviousLine,
+    NextBlock,
+    PreviousBlock,
+    NextCommit,
+    PreviousCommit,
+    ShowCommit,
+    Revert,
+    CherryPick,
+    ToggleSelection,
+    ExportMarkdown,
+    ToggleOwnersAudit,
+    OpenIssue,
+    ShowRevertLink,
+    JumpToTrailer,
+    NextTrailerMatch,
+    JumpToCommitType,
+    NextCommitTypeMatch,
+    NextAuthorBlock,
+    Statistics,
+    ContextMenu,
+    Undo,
+    Redo,
+    ToggleParentSplit,
+    FocusNextPane,
+    ToggleScrollLock,
+    ToggleFileTree,
+    TogglePlainView,
+    ToggleWorktreeBlame,
+    Command,
+    NextHotspot,
+    Todos,
+    ExpandLine,
+    ToggleMetrics,
+    NextTab,
+    PreviousTab,
+    ToggleDiff,
+    Legend,
+    Search,
+    NextSearchMatch,
+    PreviousSearchMatch,
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    FirstLine,
+    LastLine,
+    JumpToNewTip,
+    BlameAtCommit,
+    CycleCopyDetection,
+    ToggleIgnoreRevs,
+    IgnoreCommit,
+    ShowSkippedCommits,
+    ToggleRelativeDates,
+    ToggleHeatmap,
+    ScrollRight,
+    ScrollLeft,
+    ToggleWrap,
+    ToggleCommitTypeBadges,
+    ToggleTimeColumn,
+    ToggleAuthorColumn,
+    ToggleCommitColumn,
+    ToggleMessageColumn,
+    ToggleLineColumn,
+    CopySha,
+    CopyShaAndMessage,
+    BlameLfsPointer,
+    OpenCommitOnWeb,
+    OpenFileOnWeb,
+    Blocks,
+    CommitHistory,
+    Help,
+}
+
+impl Action {
+    // All actions, in the order in which they are listed to users.
+    pub const ALL: &'static [Action] = &[
+        Action::Quit,
+        Action::NextLine,
+        Action::PreviousLine,
+        Action::NextBlock,
+        Action::PreviousBlock,
+        Action::NextCommit,
+        Action::PreviousCommit,
+        Action::ShowCommit,
+        Action::Revert,
+        Action::CherryPick,
+        Action::ToggleSelection,
+        Action::ExportMarkdown,
+        Action::ToggleOwnersAudit,
+        Action::OpenIssue,
+        Action::ShowRevertLink,
+        Action::JumpToTrailer,
+        Action::NextTrailerMatch,
+        Action::JumpToCommitType,
+        Action::NextCommitTypeMatch,
+        Action::NextAuthorBlock,
+        Action::Statistics,
+        Action::ContextMenu,
+        Action::Undo,
+        Action::Redo,
+        Action::ToggleParentSplit,
+        Action::FocusNextPane,
+        Action::ToggleScrollLock,
+        Action::ToggleFileTree,
+        Action::TogglePlainView,
+        Action::ToggleWorktreeBlame,
+        Action::Command,
+        Action::NextHotspot,
+        Action::Todos,
+        Action::ExpandLine,
+        Action::ToggleMetrics,
+        Action::NextTab,
+        Action::PreviousTab,
+        Action::ToggleDiff,
+        Action::Legend,
+        Action::Search,
+        Action::NextSearchMatch,
+        Action::PreviousSearchMatch,
+        Action::PageDown,
+        Action::PageUp,
+        Action::HalfPageDown,
+        Action::HalfPageUp,
+        Action::FirstLine,
+        Action::LastLine,
+        Action::JumpToNewTip,
+        Action::BlameAtCommit,
+        Action::CycleCopyDetection,
+        Action::ToggleIgnoreRevs,
+        Action::IgnoreCommit,
+        Action::ShowSkippedCommits,
+        Action::ToggleRelativeDates,
+        Action::ToggleHeatmap,
+        Action::ScrollRight,
+        Action::ScrollLeft,
+        Action::ToggleWrap,
+        Action::ToggleCommitTypeBadges,
+        Action::ToggleTimeColumn,
+        Action::ToggleAuthorColumn,
+        Action::ToggleCommitColumn,
+        Action::ToggleMessageColumn,
+        Action::ToggleLineColumn,
+        Action::CopySha,
+        Action::CopyShaAndMessage,
+        Action::BlameLfsPointer,
+        Action::OpenCommitOnWeb,
+        Action::OpenFileOnWeb,
+        Action::Blocks,
+        Action::CommitHistory,
+        Action::Help,
+    ];
+
+    // Name of the action used for referring to it, e.g. in config files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::NextLine => "next_line",
+            Action::PreviousLine => "previous_line",
+            Action::NextBlock => "next_block",
+            Action::PreviousBlock => "previous_block",
+            Action::NextCommit => "next_commit",
+            Action::PreviousCommit => "previous_commit",
+            Action::ShowCommit => "show_commit",
+            Action::Revert => "revert",
+            Action::CherryPick => "cherry_pick",
+            Action::ToggleSelection => "toggle_selection",
+            Action::ExportMarkdown => "export_markdown",
+            Action::ToggleOwnersAudit => "toggle_owners_audit",
+            Action::OpenIssue => "open_issue",
+            Action::ShowRevertLink => "show_revert_link",
+            Action::JumpToTrailer => "jump_to_trailer",
+            Action::NextTrailerMatch => "next_trailer_match",
+            Action::JumpToCommitType => "jump_to_commit_type",
+            Action::NextCommitTypeMatch => "next_commit_type_match",
+            Action::NextAuthorBlock => "next_author_block",
+            Action::Statistics => "statistics",
+            Action::ContextMenu => "context_menu",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::ToggleParentSplit => "toggle_parent_split",
+            Action::FocusNextPane => "focus_next_pane",
+            Action::ToggleScrollLock => "toggle_scroll_lock",
+            Action::ToggleFileTree => "toggle_file_tree",
+            Action::TogglePlainView => "toggle_plain_view",
+            Action::ToggleWorktreeBlame => "toggle_worktree_blame",
+            Action::Command => "command",
+            Action::NextHotspot => "next_hotspot",
+            Action::Todos => "todos",
+            Action::ExpandLine => "expand_line",
+            Action::ToggleMetrics => "toggle_metrics",
+            Action::NextTab => "next_tab",
+            Action::PreviousTab => "previous_tab",
+            Action::ToggleDiff => "toggle_diff",
+            Action::Legend => "legend",
+            Action::Search => "search",
+            Action::NextSearchMatch => "next_search_match",
+            Action::PreviousSearchMatch => "previous_search_match",
+            Action::PageDown => "page_down",
+            Action::PageUp => "page_up",
+            Action::HalfPageDown => "half_page_down",
+            Action::HalfPageUp => "half_page_up",
+            Action::FirstLine => "first_line",
+            Action::LastLine => "last_line",
+            Action::JumpToNewTip => "jump_to_new_tip",
+            Action::BlameAtCommit => "blame_at_commit",
+            Action::CycleCopyDetection => "cycle_copy_detection",
+            Action::ToggleIgnoreRevs => "toggle_ignore_revs",
+            Action::IgnoreCommit => "ignore_commit",
+            Action::ShowSkippedCommits => "show_skipped_commits",
+            Action::ToggleRelativeDates => "toggle_relative_dates",
+            Action::ToggleHeatmap => "toggle_heatmap",
+            Action::ScrollRight => "scroll_right",
+            Action::ScrollLeft => "scroll_left",
+            Action::ToggleWrap => "toggle_wrap",
+            Action::ToggleCommitTypeBadges => "toggle_commit_type_badges",
+            Action::ToggleTimeColumn => "toggle_time_column",
+            Action::ToggleAuthorColumn => "toggle_author_column",
+            Action::ToggleCommitColumn => "toggle_commit_column",
+            Action::ToggleMessageColumn => "toggle_message_column",
+            Action::ToggleLineColumn => "toggle_line_column",
+            Action::CopySha => "copy_sha",
+            Action::CopyShaAndMessage => "copy_sha_and_message",
+            Action::BlameLfsPointer => "blame_lfs_pointer",
+            Action::OpenCommitOnWeb => "open_commit_on_web",
+            Action::OpenFileOnWeb => "open_file_on_web",
+            Action::Blocks => "blocks",
+            Action::CommitHistory => "commit_history",
+            Action::Help => "help",
+        }
+    }
+
+    // Short description of what the action does, shown to users in menus.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "Exit the program",
+            Action::NextLine => "Move to the line below",
+            Action::PreviousLine => "Move to the line above",
+            Action::NextBlock => "Move to the first line of the block below",
+            Action::PreviousBlock => "Move to the first line of the block above",
+            Action::NextCommit => "Blame at parent commit",
+            Action::PreviousCommit => "Go back to the previous blame",
+            Action::ShowCommit => "Show commit details",
+            Action::Revert => "Revert commit in working tree",
+            Action::CherryPick => "Cherry-pick commit into working tree",
+            Action::ToggleSelection => "Start or stop selecting lines",
+            Action::ExportMarkdown => "Export selection as markdown",
+            Action::ToggleOwnersAudit => "Flag blocks by authors who aren't code owners",
+            Action::OpenIssue => "Open referenced issue",
+            Action::ShowRevertLink => {
+                "Show the commit reverted by the line's commit, or reverting it"
+            }
+            Action::JumpToTrailer => "Jump to block with trailer",
+            Action::NextTrailerMatch => "Jump to next block with trailer",
+            Action::JumpToCommitType => "Jump to block with conventional commit type",
+            Action::NextCommitTypeMatch => "Jump to next block with commit type",
+            Action::NextAuthorBlock => "Jump to next block by the author picked in the statistics",
+            Action::Statistics => "Show file statistics",
+            Action::ContextMenu => "Show actions for the current line",
+            Action::Undo => "Undo the last change to the view",
+            Action::Redo => "Redo the last undone change to the view",
+            Action::ToggleParentSplit => "Show blame at parent commit side by side",
+            Action::FocusNextPane => "Move focus to the other pane",
+            Action::ToggleScrollLock => "Lock or unlock scrolling of the panes",
+            Action::ToggleFileTree => "Show or hide the file tree",
+            Action::TogglePlainView => "Show only the file contents, without the blame",
+            Action::ToggleWorktreeBlame => "Switch between blaming the working tree and HEAD",
+            Action::Command => "Enter a command",
+            Action::NextHotspot => "Jump to the next hottest block",
+            Action::Todos => "List TODO, FIXME and HACK comments",
+            Action::ExpandLine => "Show the full contents of the line",
+            Action::ToggleMetrics => "Show or hide performance metrics",
+            Action::NextTab => "Blame the next file under review",
+            Action::PreviousTab => "Blame the previous file under review",
+            Action::ToggleDiff => "Show the diff of the commit side by side",
+            Action::Legend => "Explain the colors and markers",
+            Action::Search => "Search the file contents",
+            Action::NextSearchMatch => "Jump to the next search match",
+            Action::PreviousSearchMatch => "Jump to the previous search match",
+            Action::PageDown => "Move down by a page",
+            Action::PageUp => "Move up by a page",
+            Action::HalfPageDown => "Move down by half a page",
+            Action::HalfPageUp => "Move up by half a page",
+            Action::FirstLine => "Move to the first line",
+            Action::LastLine => "Move to the last line",
+            Action::JumpToNewTip => "Blame at the new tip of the moved ref",
+            Action::BlameAtCommit => "Blame at the line's commit",
+            Action::CycleCopyDetection => "Detect moved and copied lines, more thoroughly or not",
+            Action::ToggleIgnoreRevs => "Ignore the commits in the ignore revs file, or stop",
+            Action::IgnoreCommit => "Ignore the line's commit and blame again",
+            Action::ShowSkippedCommits => {
+                "Show the ignored commits of the lines marked with *, or stop"
+            }
+            Action::ToggleRelativeDates => "Show how long ago commits were, or their dates",
+            Action::ToggleHeatmap => {
+                "Tint the TIME column from the newest to the oldest line, or stop"
+            }
+            Action::ScrollRight => "Scroll the contents to the right, for long lines",
+            Action::ScrollLeft => "Scroll the contents back to the left",
+            Action::ToggleWrap => "Wrap long lines across rows, or cut them off again",
+            Action::ToggleCommitTypeBadges => {
+                "Show the types of conventional commits as colored badges, or as text again"
+            }
+            Action::ToggleTimeColumn => "Hide or show the TIME column",
+            Action::ToggleAuthorColumn => "Hide or show the AUTHOR column",
+            Action::ToggleCommitColumn => "Hide or show the COMMIT column",
+            Action::ToggleMessageColumn => "Hide or show the MESSAGE column",
+            Action::ToggleLineColumn => "Hide or show the LINE column",
+            Action::CopySha => "Copy the commit hash",
+            Action::CopyShaAndMessage => "Copy the commit hash and message",
+            Action::BlameLfsPointer => "Blame the pointers of files stored with Git LFS",
+            Action::OpenCommitOnWeb => "Open the commit on GitHub, GitLab or Bitbucket",
+            Action::OpenFileOnWeb => "Open the file at the commit on GitHub, GitLab or Bitbucket",
+            Action::Blocks => "List the blocks of lines changed by the same commit",
+            Action::CommitHistory => "List the commits traveled through, to go back to any of them",
+            Action::Help => "Show all keys and what they do",
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|a| a.name() == s)
+            .ok_or_else(|| format!("unknown action '{}'", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn parses_action_names() {
+        for &action in Action::ALL {
+            assert_eq!(action.name().parse(), Ok(action));
+            assert_eq!(action.to_string(), action.name());
+        }
+        assert_eq!(
+            "next_blok".parse::<Action>(),
+            Err("unknown action 'next_blok'".to_string())
+        );
+    }
+
+    #[test]
+    fn names_are_unique() {
+        let names: HashSet<_> = Action::ALL.iter().map(Action::name).collect();
+        assert_eq!(names.len(), Action::ALL.len());
+    }
+}
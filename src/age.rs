@@ -0,0 +1,269 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DAY: i64 = 24 * 60 * 60;
+pub const WEEK: i64 = 7 * DAY;
+pub const MONTH: i64 = 30 * DAY;
+pub const YEAR: i64 = 365 * DAY;
+
+// Current time as seconds since the Unix epoch.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+// Parse a duration like "30d", "2w", "6m" or "5y" into seconds.
+pub fn parse_duration(s: &str) -> Result<i64, String> {
+    let s = s.trim();
+    let unit = match s.chars().last() {
+        Some('d') => DAY,
+        Some('w') => WEEK,
+        Some('m') => MONTH,
+        Some('y') => YEAR,
+        _ => {
+            return Err(format!(
+                "invalid duration '{}', expected e.g. 30d, 2w, 6m or 5y",
+                s
+            ))
+        }
+    };
+    s[..s.len() - 1]
+        .parse::<i64>()
+        .map(|n| n * unit)
+        .map_err(|_| format!("invalid duration '{}', expected e.g. 30d, 2w, 6m or 5y", s))
+}
+
+// Format a duration in seconds in the largest unit in which it's at least
+// one, e.g. "3d" or "2y", like the durations accepted by `parse_duration`.
+pub fn format_duration(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    [(YEAR, 'y'), (MONTH, 'm'), (WEEK, 'w')]
+        .into_iter()
+        .find(|(unit, _)| seconds >= *unit)
+        .map(|(unit, suffix)| format!("{}{}", seconds / unit, suffix))
+        .unwrap_or_else(|| format!("{}d", seconds / DAY))
+}
+
+// Name of the smallest period of time which an age fits in, e.g. "week"
+// for a line changed three days ago. Ages over a year are "older".
+pub fn bucket(age: i64) -> &'static str {
+    [
+        (DAY, "day"),
+        (WEEK, "week"),
+        (MONTH, "month"),
+        (YEAR, "year"),
+    ]
+    .into_iter()
+    .find(|(limit, _)| age < *limit)
+    .map(|(_, name)| name)
+    .unwrap_or("older")
+}
+
+// Blocks of increasing height for drawing ages as bars.
+const GAUGE_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// A single character bar of one to eight eighths, showing how old a change
+// made at `time` is relative to the history which started at
+// `history_start`: the oldest changes get the full block. Unlike colors, the
+// height can be told apart without seeing colors.
+pub fn gauge(time: i64, history_start: i64, now: i64) -> char {
+    let span = (now - history_start).max(1);
+    let age = (now - time).clamp(0, span);
+    let index = (age * GAUGE_BLOCKS.len() as i64 / span) as usize;
+    GAUGE_BLOCKS[index.min(GAUGE_BLOCKS.len() - 1)]
+}
+
+// How the dates of commits are shown in the TIME column.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DateFormat {
+    // The date, e.g. "2023-04-01", in the order of the locale.
+    #[default]
+    Absolute,
+    // How long ago, e.g. "3 weeks ago", like git's --date=relative.
+    Relative,
+}
+
+impl DateFormat {
+    pub fn toggled(self) -> DateFormat {
+        match self {
+            DateFormat::Absolute => DateFormat::Relative,
+            DateFormat::Relative => DateFormat::Absolute,
+        }
+    }
+}
+
+// Parse the name of a date format, "absolute" or "relative".
+pub fn parse_date_format(s: &str) -> Result<DateFormat, String> {
+    match s {
+        "absolute" => Ok(DateFormat::Absolute),
+        "relative" => Ok(DateFormat::Relative),
+        _ => Err(format!(
+            "invalid date format '{}', expected absolute or relative",
+            s
+        )),
+    }
+}
+
+// Format how long ago a time was, e.g. "3 weeks ago" or "2 years, 5 months
+// ago", rounding like git does for --date=relative.
+pub fn format_relative(time: i64, now: i64) -> String {
+    let plural = |n: i64, unit: &str| format!("{} {}{}", n, unit, if n == 1 { "" } else { "s" });
+    let seconds = now - time;
+    if seconds < 0 {
+        return "in the future".to_string();
+    }
+    if seconds < 90 {
+        return format!("{} ago", plural(seconds, "second"));
+    }
+    let minutes = (seconds + 30) / 60;
+    if minutes < 90 {
+        return format!("{} ago", plural(minutes, "minute"));
+    }
+    let hours = (minutes + 30) / 60;
+    if hours < 36 {
+        return format!("{} ago", plural(hours, "hour"));
+    }
+    let days = (hours + 12) / 24;
+    if days < 14 {
+        return format!("{} ago", plural(days, "day"));
+    }
+    if days < 70 {
+        return format!("{} ago", plural((days + 3) / 7, "week"));
+    }
+    if days < 365 {
+        return format!("{} ago", plural((days + 15) / 30, "month"));
+    }
+    if days < 1825 {
+        let total_months = (days * 12 * 2 + 365) / (365 * 2);
+        let (years, months) = (total_months / 12, total_months % 12);
+        if months > 0 {
+            return format!("{}, {} ago", plural(years, "year"), plural(months, "month"));
+        }
+        return format!("{} ago", plural(years, "year"));
+    }
+    format!("{} ago", plural((days + 183) / 365, "year"))
+}
+
+// Format a time in seconds since the Unix epoch in the given time zone
+// offset like "+0200", as "2023-04-01 12:00:00 +0200", which is how git
+// shows dates in ISO format.
+pub fn format_timestamp(time: i64, offset: &str) -> String {
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let digits = offset.trim_start_matches(['+', '-']);
+    let offset_seconds = match (digits.get(..2), digits.get(2..4)) {
+        (Some(hours), Some(minutes)) => {
+            sign * (hours.parse::<i64>().unwrap_or(0) * 3600
+                + minutes.parse::<i64>().unwrap_or(0) * 60)
+        }
+        _ => 0,
+    };
+
+    let local = time + offset_seconds;
+    let (year, month, day) = civil_from_days(local.div_euclid(DAY));
+    let seconds = local.rem_euclid(DAY);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} {}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+        if offset.is_empty() { "+0000" } else { offset }
+    )
+}
+
+// Convert a number of days since 1970-01-01 to a (year, month, day) date in
+// the proleptic Gregorian calendar, using Howard Hinnant's algorithm.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// Rule for warning about lines which were last changed a long time ago, or
+// very recently, e.g. for audits of code nobody has touched in years.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AgeWarning {
+    // Warn about lines older than this many seconds.
+    pub older_than: Option<i64>,
+    // Warn about lines newer than this many seconds.
+    pub newer_than: Option<i64>,
+}
+
+impl AgeWarning {
+    // Check whether a line last changed at the given time should be warned about.
+    pub fn matches(&self, time: i64, now: i64) -> bool {
+        let age = now - time;
+        self.older_than.is_some_and(|limit| age > limit)
+            || self.newer_than.is_some_and(|limit| age < limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("30d"), Ok(30 * DAY));
+        assert_eq!(parse_duration(" 2w "), Ok(2 * WEEK));
+        assert_eq!(parse_duration("6m"), Ok(6 * MONTH));
+        assert_eq!(parse_duration("5y"), Ok(5 * YEAR));
+        assert!(parse_duration("5").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("5h").is_err());
+        assert!(parse_duration("").is_err());
+    }
+
+    #[test]
+    fn warns_about_old_and_new_lines() {
+        let now = 100 * DAY;
+        let warning = AgeWarning {
+            older_than: Some(30 * DAY),
+            newer_than: Some(DAY),
+        };
+        assert!(warning.matches(now - 31 * DAY, now));
+        assert!(!warning.matches(now - 30 * DAY, now));
+        assert!(!warning.matches(now - 2 * DAY, now));
+        assert!(warning.matches(now - 60, now));
+        assert!(!AgeWarning::default().matches(0, now));
+    }
+
+    #[test]
+    fn parses_date_formats() {
+        assert_eq!(parse_date_format("absolute"), Ok(DateFormat::Absolute));
+        assert_eq!(parse_date_format("relative"), Ok(DateFormat::Relative));
+        assert!(parse_date_format("iso").is_err());
+        assert_eq!(DateFormat::Absolute.toggled(), DateFormat::Relative);
+        assert_eq!(DateFormat::Relative.toggled(), DateFormat::Absolute);
+    }
+
+    #[test]
+    fn formats_relative_dates() {
+        let now = 100 * YEAR;
+        let ago = |seconds: i64| format_relative(now - seconds, now);
+        assert_eq!(ago(1), "1 second ago");
+        assert_eq!(ago(89), "89 seconds ago");
+        assert_eq!(ago(90), "2 minutes ago");
+        assert_eq!(ago(3 * 60 * 60), "3 hours ago");
+        assert_eq!(ago(3 * WEEK), "3 weeks ago");
+        assert_eq!(ago((2 * 365 + 150) * DAY), "2 years, 5 months ago");
+        assert_eq!(ago(2 * 365 * DAY), "2 years ago");
+        assert_eq!(ago(3650 * DAY), "10 years ago");
+        assert_eq!(ago(-1), "in the future");
+    }
+}
@@ -0,0 +1,72 @@
+use ratatui::style::{Color, Style};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+use std::str::FromStr;
+
+// A marker shown next to a single line of the blame, e.g. whether the line
+// is covered by tests or has a TODO comment.
+#[derive(PartialEq, Clone, Debug, Deserialize)]
+pub struct Annotation {
+    // 1-based line number in the file.
+    pub line: usize,
+    // Short text shown in the annotation column, ideally a single character.
+    pub marker: String,
+    // Optional color for the marker, either a name like "green" or a hex value.
+    #[serde(default)]
+    pub color: Option<String>,
+    // Optional longer description of the annotation.
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+impl Annotation {
+    pub fn style(&self) -> Style {
+        match self.color.as_deref().map(Color::from_str) {
+            Some(Ok(color)) => Style::default().fg(color),
+            _ => Style::default(),
+        }
+    }
+}
+
+// Source of per-line annotations overlaid next to the blame. Implementations
+// can read annotations from anywhere, e.g. coverage reports or TODO scanners.
+pub trait AnnotationProvider: Debug {
+    // Get the annotation for a 1-based line number, if there is one.
+    fn annotation(&self, line: usize) -> Option<&Annotation>;
+}
+
+// Annotations loaded from a JSON file. The file contains a list of
+// annotations, one for each annotated line:
+//
+// [
+//   { "line": 12, "marker": "●", "color": "green", "note": "covered" },
+//   { "line": 13, "marker": "○", "color": "red", "note": "not covered" }
+// ]
+#[derive(Debug, Default)]
+pub struct JsonAnnotationProvider {
+    annotations: HashMap<usize, Annotation>,
+}
+
+impl JsonAnnotationProvider {
+    pub fn load(path: &str) -> Result<JsonAnnotationProvider, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read annotations file {}: {}", path, e))?;
+        JsonAnnotationProvider::parse(&contents)
+            .map_err(|e| format!("Failed to parse annotations file {}: {}", path, e))
+    }
+
+    pub fn parse(contents: &str) -> Result<JsonAnnotationProvider, serde_json::Error> {
+        let annotations: Vec<Annotation> = serde_json::from_str(contents)?;
+        Ok(JsonAnnotationProvider {
+            annotations: annotations.into_iter().map(|a| (a.line, a)).collect(),
+        })
+    }
+}
+
+impl AnnotationProvider for JsonAnnotationProvider {
+    fn annotation(&self, line: usize) -> Option<&Annotation> {
+        self.annotations.get(&line)
+    }
+}
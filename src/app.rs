@@ -1,35 +1,516 @@
-use crate::file_blame::{Commit, FileBlame, FileBlameError};
-use ratatui::layout::Constraint;
-use ratatui::style::{Color, Style};
+use crate::action::Action;
+use crate::age::{self, AgeWarning, DateFormat};
+use crate::annotations::AnnotationProvider;
+use crate::clipboard;
+use crate::codeowners::CodeOwners;
+use crate::colors::ColorSupport;
+use crate::diff::DiffPane;
+use crate::disk_cache::DiskCache;
+use crate::export;
+use crate::file_blame::{
+    BlameLine, Commit, CommitDetails, CopyDetection, FileBlame, FileBlameError, FileInfo,
+    HighlightCache, ParseOptions, Progress, WORKTREE,
+};
+use crate::handler::KeyMap;
+use crate::hotspots::{self, Hotspot};
+use crate::line_map::LineMap;
+use crate::links::{self, IssueLinker};
+use crate::locale::Locale;
+use crate::memory::{self, CommitRecency, MemorySize};
+use crate::message::{self, MessageCleaner};
+use crate::partial_clone::Promisor;
+use crate::remote::WebRemote;
+use crate::reverts::Reverts;
+use crate::review::Review;
+use crate::search;
+use crate::stats::{AuthorOrder, FileStats, MonthlyCommits};
+use crate::theme::Theme;
+use crate::tree::FileTree;
+use lru::LruCache;
+use ratatui::layout::{Constraint, Rect};
+use ratatui::style::Style;
 use ratatui::widgets::TableState;
+use regex::Regex;
+use std::cmp::Reverse;
 use std::collections::HashMap;
 use std::error;
+use std::fs;
+use std::ops::{Range, RangeInclusive};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Number of lines shown above lines which are jumped to from outside.
+const SCROLL_CONTEXT_LINES: usize = 5;
+
+// Number of columns by which the contents are scrolled sideways at once.
+const CONTENTS_SCROLL_STEP: usize = 8;
+
+// Longest time between two clicks at the same place for a double click.
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+// Number of lines scrolled by a turn of the mouse wheel.
+const WHEEL_LINES: isize = 3;
+
+// How often the followed ref is checked for new commits.
+const REF_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+// How long a status message is shown.
+const STATUS_DURATION: Duration = Duration::from_secs(3);
+
+// Regex matching comments which mark unfinished work.
+const TODO_REGEX: &str = r"\b(TODO|FIXME|HACK)\b";
 
 /// Application result type.
 pub type AppResult<T> = std::result::Result<T, Box<dyn error::Error>>;
 
+// Result of loading a blame in the background, with the commits it refers to.
+type LoadResult = Result<(FileBlame, HashMap<String, Commit>, HighlightCache), FileBlameError>;
+
+// Blames are cached by the path of the file, the commit and the options
+// they were parsed with.
+type BlameKey = (String, String, ParseOptions);
+
+// Whether the first blame is still being loaded in the background, and since
+// when, or ready to be shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AppState {
+    Loading { started: Instant },
+    Ready,
+}
+
 /// Application.
 #[derive(Debug)]
 pub struct App {
     /// Is the application running?
     pub running: bool,
+    pub app_state: AppState,
+    // Receives the blame which is loaded in the background.
+    loader: Option<mpsc::Receiver<LoadResult>>,
+    // How far along loading the blame in the background is, once it has
+    // reported any progress.
+    pub progress: Option<Progress>,
+    // Lines to select once the blame is loaded in the background.
+    pending_selection: Option<(usize, usize)>,
 
     pub state: TableState,
     pub file_path: String,
     pub commit_sha: String,
     pub file_blame: Option<FileBlame>,
+    pub parse_options: ParseOptions,
+    pub file_info: Option<FileInfo>,
     pub commit_cache: HashMap<String, Commit>,
-    pub commit_stack: Vec<String>,
+    // The order in which the cached commits were last used.
+    commit_recency: CommitRecency,
+    // Blames which were shown before, for going back to them without
+    // blaming the file again. Only blames at commit hashes are cached, since
+    // the commits which refs point to can change.
+    blame_cache: LruCache<BlameKey, FileBlame>,
+    // Syntax highlighted contents of blamed files.
+    highlights: HighlightCache,
+    // Commits traveled back from, with the path of the file at each, which
+    // differs from the blamed path if the file was renamed since.
+    pub commit_stack: Vec<(String, String)>,
     pub load_err: Option<FileBlameError>,
     pub columns: Vec<Column>,
+    pub annotations: Option<Box<dyn AnnotationProvider>>,
+    pub selection_anchor: Option<usize>,
+    pub code_owners: Option<CodeOwners>,
+    pub file_owners: Option<Vec<String>>,
+    // Reverts in the history of the blamed file, for marking the lines of
+    // revert commits and of reverted ones.
+    pub reverts: Reverts,
+    pub owners_audit: bool,
+    // Whether only the contents of the file are shown, without the blame.
+    pub plain_view: bool,
+    pub issue_linker: Option<IssueLinker>,
+    pub trailer_query: String,
+    // Last submitted search of the file contents.
+    pub search_query: String,
+    pub age_warning: AgeWarning,
+    pub undo_stack: Vec<ViewState>,
+    pub redo_stack: Vec<ViewState>,
+    last_recorded_action: Option<Action>,
+    pub split: Option<SplitPane>,
+    // Patch of a commit shown side by side with the main blame, instead of
+    // the split pane.
+    pub diff: Option<DiffPane>,
+    pub focus: Pane,
+    pub file_tree: Option<FileTree>,
+    // Oldest commit which can be reached by travelling backwards in time.
+    pub floor_commit: Option<String>,
+    // Number of parent commits which can be traveled back through from the
+    // blamed commit, so that long excursions in huge histories are opt-in.
+    pub max_depth: Option<usize>,
+    // Ref which was blamed first, if it can move, which is checked for new
+    // commits to offer jumping to.
+    pub followed_ref: Option<FollowedRef>,
+    // File listing the commits to ignore, which ignoring can be turned off
+    // and on again for.
+    pub ignore_revs_file: Option<String>,
+    // Whether the lines which were blamed past ignored commits show the
+    // ignored commits instead, until this is turned off again.
+    pub show_skipped_commits: bool,
+    // Short message about the result of an action, e.g. copying a commit
+    // hash, shown in the status bar until it expires.
+    pub status: Option<StatusMessage>,
+    // Whether files stored with Git LFS are blamed anyway, which only blames
+    // their pointers, instead of showing a notice.
+    pub blame_lfs_pointers: bool,
+    // Whether the commit column is tinted with a color derived from each
+    // commit's hash, so that neighboring commits can be told apart.
+    pub commit_accents: bool,
+    // Whether the TIME column is tinted on a gradient from the newest to the
+    // oldest commit in the file.
+    pub heatmap: bool,
+    // Number of columns by which the contents are scrolled to the right, for
+    // reading lines wider than the CONTENTS column.
+    pub contents_offset: usize,
+    // Whether lines wider than the CONTENTS column are wrapped across rows
+    // instead, which makes scrolling sideways unnecessary.
+    pub wrap_contents: bool,
+    // How the summaries of commit messages are cleaned up for the MESSAGE
+    // column.
+    pub message_cleaner: MessageCleaner,
+    // Whether the types of conventional commits are shown as colored badges
+    // in front of the messages, instead of as text.
+    pub commit_type_badges: bool,
+    // Type of conventional commits whose blocks `J` jumps between, e.g. "fix".
+    pub commit_type_query: String,
+    // Which action each key of the blame table triggers.
+    pub key_map: KeyMap,
+    // Order of the rows in the overview of the blocks.
+    pub block_order: BlockOrder,
+    // Order of the authors in the statistics.
+    pub author_order: AuthorOrder,
+    // Author picked in the statistics, whose blocks `A` jumps between while
+    // the lines of other authors are dimmed.
+    pub author_filter: Option<String>,
+    // Colors and characters of the blame table.
+    pub theme: Theme,
+    // Remote which missing objects are fetched from while blaming, if the
+    // repository is a partial clone.
+    pub promisor: Option<Promisor>,
+    // Blocks sorted by hotspot score, computed when first jumping to a
+    // hotspot, and the index of the hotspot which was jumped to last.
+    pub hotspots: Option<Vec<Hotspot>>,
+    pub hotspot_index: Option<usize>,
+    // Colors supported by the terminal, which the rendered colors are converted to.
+    pub color_support: ColorSupport,
+    // Whether the blame is rendered as plain text for screen readers.
+    pub accessible: bool,
+    // Conventions for formatting dates and numbers.
+    pub locale: Locale,
+    // Whether the TIME column shows dates or how long ago they were.
+    pub date_format: DateFormat,
+    // Bytes which the shown blames and the caches of blames, highlighted
+    // contents and commits may use together, after which the least recently
+    // used ones are evicted from the caches.
+    pub memory_budget: usize,
+    // Whether performance metrics are shown over the blame, and how long
+    // rendering the last frame took.
+    pub show_metrics: bool,
+    pub frame_time: Duration,
+    // Number of lines visible in the main table as of the last render, for
+    // moving by pages and keeping the selected line in view.
+    pub viewport_height: usize,
+    pub screen_layout: ScreenLayout,
+    // Where and when the mouse was last clicked, for telling double clicks.
+    last_click: Option<(Instant, u16, u16)>,
+    // Files changed in a range of commits which are reviewed in tabs.
+    pub review: Option<Review>,
+    // When the history of the repository started, for the age gauge column.
+    pub history_start: Option<i64>,
+    pub popup: Option<Popup>,
+    pub external_command: Option<ExternalCommand>,
 }
 
-// Column definition including the column width, style, and header name.
+// A ref which was blamed and can move, e.g. a branch which new commits land
+// on, and the commit it pointed to when it was blamed.
+#[derive(Debug, Clone)]
+pub struct FollowedRef {
+    pub name: String,
+    pub tip: String,
+    // Commit the ref points to since it moved, if it did.
+    pub new_tip: Option<String>,
+    git_root_dir: String,
+    checked: Instant,
+}
+
+// A message shown in the status bar until it expires, e.g. confirming that
+// a commit hash was copied, or explaining why it couldn't be.
+#[derive(Debug, Clone)]
+pub struct StatusMessage {
+    pub text: String,
+    pub is_error: bool,
+    shown: Instant,
+}
+
+// Blame of the same file at another commit, shown side by side with the main
+// blame. The line map maps lines of the pane's blame (old) to lines of the
+// main blame (new), and is used for keeping both blames scrolled in sync.
 #[derive(Debug)]
+pub struct SplitPane {
+    pub commit_sha: String,
+    pub file_blame: FileBlame,
+    pub state: TableState,
+    pub line_map: LineMap,
+    // Whether scrolling one pane also scrolls the other.
+    pub locked: bool,
+}
+
+// The panes which can have focus. Keys for moving the selection apply to the
+// pane with focus.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pane {
+    Tree,
+    Main,
+    Split,
+    Diff,
+}
+
+// Where the panes and the rows of the main table were drawn in the last
+// frame, for finding what the mouse points at.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScreenLayout {
+    pub panes: Vec<(Pane, Rect)>,
+    // The rows of the main table, with the index of the line in each.
+    pub rows: Vec<(Rect, usize)>,
+    // Horizontal position of the COMMIT column of the main table, if shown.
+    pub commit_column: Option<Range<u16>>,
+}
+
+impl ScreenLayout {
+    pub fn pane_at(&self, column: u16, row: u16) -> Option<Pane> {
+        self.panes
+            .iter()
+            .find(|(_, area)| area.contains((column, row).into()))
+            .map(|(pane, _)| *pane)
+    }
+
+    pub fn line_at(&self, column: u16, row: u16) -> Option<usize> {
+        self.rows
+            .iter()
+            .find(|(area, _)| area.contains((column, row).into()))
+            .map(|(_, i)| *i)
+    }
+}
+
+// Snapshot of the state of the view, for undoing and redoing changes to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewState {
+    pub file_path: String,
+    pub commit_sha: String,
+    pub commit_stack: Vec<(String, String)>,
+    pub selected: Option<usize>,
+    pub selection_anchor: Option<usize>,
+    pub owners_audit: bool,
+    pub plain_view: bool,
+    pub trailer_query: String,
+    pub commit_type_query: String,
+    pub author_filter: Option<String>,
+    // Range of lines which is blamed, which moves with the lines between commits.
+    pub line_range: Option<(usize, usize)>,
+    // Commits ignored while blaming, so that ignoring one can be undone.
+    pub ignore_revs: Vec<String>,
+    // File whose commits are ignored, unless ignoring them was turned off.
+    pub ignore_revs_file: Option<String>,
+    pub show_skipped_commits: bool,
+    pub copy_detection: CopyDetection,
+    pub heatmap: bool,
+    pub date_format: DateFormat,
+    // Columns which are hidden.
+    pub hidden_columns: Vec<ColumnKind>,
+    pub wrap_contents: bool,
+    pub commit_type_badges: bool,
+}
+
+// Popups which are rendered on top of the blame table. While a popup is open,
+// key events are handled by the popup instead of the table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Popup {
+    // Details of a commit and the actions which can be run on it.
+    CommitDetail(String, CommitDetails),
+    // Confirmation prompt before running an action on a commit.
+    ConfirmCommitAction(CommitAction, String),
+    // A message for the user, e.g. the result of an action.
+    Message(String),
+    // A single line of text input, e.g. a query.
+    Prompt(PromptKind, String),
+    // Ownership statistics for the blamed file, with the index of the
+    // highlighted author.
+    Statistics(FileStats, usize),
+    // Menu of the actions applicable to the selected line, with the index of
+    // the highlighted item.
+    ContextMenu(usize),
+    // Full contents of a line, given as an index into the blame lines, and
+    // the number of rows scrolled down.
+    ExpandedLine(usize, u16),
+    // List of the TODO, FIXME and HACK comments in the file, with the index
+    // of the highlighted item.
+    Todos(usize),
+    // Explanation of the colors and markers which are currently in use.
+    Legend,
+    // Overview of the file with a row for each block of lines with the same
+    // commit, with the index of the highlighted block.
+    Blocks(usize),
+    // The commits traveled through on the commit stack, followed by the
+    // current one, with the index of the highlighted commit.
+    CommitHistory(usize),
+    // All keys of the blame table and what they do, with the number of rows
+    // scrolled down.
+    Help(u16),
+}
+
+// Order of the rows in the overview of the blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BlockOrder {
+    // From the top of the file to the bottom.
+    #[default]
+    Position,
+    // Most recently changed first.
+    Newest,
+    // By the name of the author, and from the top within each author.
+    Author,
+    // Most lines first.
+    Size,
+}
+
+impl BlockOrder {
+    pub fn next(self) -> BlockOrder {
+        match self {
+            BlockOrder::Position => BlockOrder::Newest,
+            BlockOrder::Newest => BlockOrder::Author,
+            BlockOrder::Author => BlockOrder::Size,
+            BlockOrder::Size => BlockOrder::Position,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BlockOrder::Position => "by position",
+            BlockOrder::Newest => "newest first",
+            BlockOrder::Author => "by author",
+            BlockOrder::Size => "largest first",
+        }
+    }
+}
+
+// What the text entered into a prompt is used for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PromptKind {
+    // Jump to the next block whose commit has a matching trailer.
+    Trailer,
+    // Run a command, e.g. "ref merge-base main".
+    Command,
+    // Search the contents of the file, highlighting matches while typing.
+    Search,
+    // Jump to the next block whose commit has a conventional commit type,
+    // e.g. "fix".
+    CommitType,
+}
+
+impl PromptKind {
+    pub fn title(&self) -> &'static str {
+        match self {
+            PromptKind::Trailer => "Jump to trailer (Key: value)",
+            PromptKind::Command => "Command",
+            PromptKind::Search => "/",
+            PromptKind::CommitType => "Jump to commit type (e.g. fix)",
+        }
+    }
+}
+
+// Git commands which can be run on the selected line's commit from the
+// commit detail popup. The commands are run with `-n` so that the changes
+// are only applied to the working tree and index, and never committed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommitAction {
+    Revert,
+    CherryPick,
+}
+
+impl CommitAction {
+    pub fn git_args(&self, sha: &str) -> Vec<String> {
+        let command = match self {
+            CommitAction::Revert => "revert",
+            CommitAction::CherryPick => "cherry-pick",
+        };
+        vec![command.to_string(), "-n".to_string(), sha.to_string()]
+    }
+}
+
+// An external command which should be run with the terminal user interface
+// suspended. The application only records the command, and the main loop
+// runs it since it owns the terminal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    pub current_dir: String,
+}
+
+// Column definition including the column width, style, and header name.
+#[derive(Debug, Clone)]
 pub struct Column {
+    pub kind: ColumnKind,
     pub width: Constraint,
     pub style: Style,
     pub name: String,
+    // Whether the column is shown, as columns can be hidden to make room for
+    // the contents, e.g. on narrow terminals.
+    pub visible: bool,
+}
+
+// The kind of information shown in a column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnKind {
+    Time,
+    Author,
+    Commit,
+    Message,
+    Annotation,
+    // Bar showing how old the line's change is relative to the repository's history.
+    Age,
+    // File and line which the line was moved or copied from.
+    Origin,
+    Line,
+    Contents,
+}
+
+impl ColumnKind {
+    // The column with the given name, of the columns which can be hidden,
+    // e.g. "author".
+    pub fn from_name(name: &str) -> Option<ColumnKind> {
+        match name {
+            "time" => Some(ColumnKind::Time),
+            "author" => Some(ColumnKind::Author),
+            "commit" => Some(ColumnKind::Commit),
+            "message" => Some(ColumnKind::Message),
+            "line" => Some(ColumnKind::Line),
+            _ => None,
+        }
+    }
+
+    // Parse the name of a column which can be hidden, for --hide-columns.
+    pub fn parse_hideable(name: &str) -> Result<ColumnKind, String> {
+        ColumnKind::from_name(name).ok_or_else(|| {
+            format!(
+                "unknown column '{}', expected time, author, commit, message or line",
+                name
+            )
+        })
+    }
+
+    // Whether the column shows information about the line's commit. These columns
+    // are only filled in for the first line of a block.
+    pub fn is_commit_info(&self) -> bool {
+        matches!(
+            self,
+            ColumnKind::Time | ColumnKind::Author | ColumnKind::Commit | ColumnKind::Message
+        )
+    }
 }
 
 impl Column {
@@ -41,79 +522,677 @@ impl Column {
 impl App {
     /// Constructs a new instance of [`App`].
     pub fn new(file_path: String, commit_sha: String) -> Self {
-        let mut app = App {
+        App::with_parse_options(file_path, commit_sha, ParseOptions::default())
+    }
+
+    // Construct the app with options for parsing blames, which are used from
+    // the first load on, so that large files aren't loaded twice.
+    pub fn with_parse_options(
+        file_path: String,
+        commit_sha: String,
+        options: ParseOptions,
+    ) -> Self {
+        let mut app = App::without_blame(file_path.clone(), commit_sha.clone(), options);
+        app.load_blame(file_path, commit_sha);
+        app.load_code_owners();
+        app.warn_if_unhighlighted();
+        app.follow_ref();
+        app
+    }
+
+    // Construct the app and load the blame on a background thread, so that
+    // its progress can be shown meanwhile instead of a frozen terminal. The
+    // callback is called with the progress of each phase, to be passed to
+    // set_progress, and with None once loading is done, e.g. for waking up
+    // the main loop, which then picks up the blame on the next tick.
+    pub fn loading(
+        file_path: String,
+        commit_sha: String,
+        options: ParseOptions,
+        mut on_progress: impl FnMut(Option<Progress>) + Send + 'static,
+    ) -> Self {
+        let mut app = App::without_blame(file_path.clone(), commit_sha.clone(), options.clone());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut commit_cache = HashMap::new();
+            let mut highlights = HighlightCache::unbounded();
+            let result = FileBlame::parse_with_progress(
+                &file_path,
+                &commit_sha,
+                &mut commit_cache,
+                &mut highlights,
+                &options,
+                |progress| on_progress(Some(progress)),
+            )
+            .map(|file_blame| (file_blame, commit_cache, highlights));
+            // The app may have quit already, in which case nobody is listening.
+            let _ = sender.send(result);
+            on_progress(None);
+        });
+        app.loader = Some(receiver);
+        app.app_state = AppState::Loading {
+            started: Instant::now(),
+        };
+        app
+    }
+
+    // Show how far along loading the blame is, and pick it up once it's done.
+    pub fn set_progress(&mut self, progress: Option<Progress>) {
+        match progress {
+            Some(progress) => self.progress = Some(progress),
+            None => self.tick(),
+        }
+    }
+
+    // Whether the first blame is still being loaded.
+    pub fn is_loading(&self) -> bool {
+        matches!(self.app_state, AppState::Loading { .. })
+    }
+
+    // Take the blame loaded in the background if it's done, and quit if
+    // loading failed.
+    fn finish_loading(&mut self) {
+        let Some(result) = self.loader.as_ref().and_then(|l| l.try_recv().ok()) else {
+            return;
+        };
+        self.loader = None;
+        self.app_state = AppState::Ready;
+
+        let (mut file_blame, commit_cache, highlights) = match result {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                self.load_err = Some(e);
+                self.quit();
+                return;
+            }
+        };
+        if let Some(review) = &self.review {
+            review.filter(&mut file_blame);
+        }
+        // Files left out of a sparse checkout are blamed at HEAD instead of
+        // in the working tree.
+        self.commit_sha = file_blame.commit_sha.clone();
+        self.file_info = FileInfo::load(&self.file_path, &self.commit_sha);
+        self.reverts = Reverts::load(&self.file_path, &self.commit_sha);
+        self.commit_cache.extend(commit_cache);
+        self.highlights = highlights;
+        self.file_blame = Some(file_blame);
+        self.state.select(Some(self.first_introduced_line()));
+        self.trim_caches();
+        self.load_code_owners();
+        self.warn_if_unhighlighted();
+        if let Some((start, end)) = self.pending_selection.take() {
+            self.select_lines(start, end);
+        }
+        self.follow_ref();
+    }
+
+    // Start following the blamed ref if it's one which can move, e.g. a
+    // branch or HEAD, rather than a commit hash.
+    fn follow_ref(&mut self) {
+        if self.commit_sha == WORKTREE {
+            return;
+        }
+        let Some((git_root_dir, _)) = FileBlame::repo_paths(&self.file_path) else {
+            return;
+        };
+        let Some(tip) = DiskCache::resolve(&git_root_dir, &self.commit_sha) else {
+            return;
+        };
+        if tip.starts_with(&self.commit_sha) {
+            return;
+        }
+        self.followed_ref = Some(FollowedRef {
+            name: self.commit_sha.clone(),
+            tip,
+            new_tip: None,
+            git_root_dir,
+            checked: Instant::now(),
+        });
+    }
+
+    // Check every now and then whether the followed ref moved, e.g. because
+    // commits landed on the blamed branch.
+    fn check_followed_ref(&mut self) {
+        let Some(followed) = self.followed_ref.as_mut() else {
+            return;
+        };
+        if followed.checked.elapsed() < REF_CHECK_INTERVAL {
+            return;
+        }
+        followed.checked = Instant::now();
+        if let Some(tip) = DiskCache::resolve(&followed.git_root_dir, &followed.name) {
+            followed.new_tip = (tip != followed.tip).then_some(tip);
+        }
+    }
+
+    // Blame the file at the new tip of the followed ref after it moved. The
+    // blames of the old lineage stay reachable by traveling forwards in
+    // time, since the ref's name in the commit stack is replaced by the
+    // commit it pointed to before.
+    pub fn jump_to_new_tip(&mut self) {
+        let Some(followed) = self.followed_ref.as_mut() else {
+            return;
+        };
+        let Some(new_tip) = followed.new_tip.take() else {
+            self.popup = Some(Popup::Message(format!(
+                "No new commits on {}",
+                followed.name
+            )));
+            return;
+        };
+        let old_tip = std::mem::replace(&mut followed.tip, new_tip);
+        let name = followed.name.clone();
+
+        let mut commit_stack = self.commit_stack.clone();
+        commit_stack.push((self.commit_sha.clone(), self.file_path.clone()));
+        for (sha, _) in commit_stack.iter_mut().filter(|(sha, _)| *sha == name) {
+            *sha = old_tip.clone();
+        }
+        match self.try_load_blame(self.file_path.clone(), name) {
+            Ok(()) => self.commit_stack = commit_stack,
+            Err(e) => self.popup = Some(Popup::Message(e.to_string())),
+        }
+    }
+
+    // The app with everything but the blame, which is loaded separately.
+    fn without_blame(file_path: String, commit_sha: String, options: ParseOptions) -> Self {
+        let theme = Theme::default();
+        App {
+            ignore_revs_file: options.ignore_revs_file.clone(),
+            status: None,
+            blame_lfs_pointers: false,
+            commit_accents: false,
+            heatmap: false,
+            contents_offset: 0,
+            wrap_contents: false,
+            message_cleaner: MessageCleaner::default(),
+            commit_type_badges: false,
+            commit_type_query: String::new(),
+            show_skipped_commits: false,
+            key_map: KeyMap::default(),
+            block_order: BlockOrder::Position,
+            author_order: AuthorOrder::Lines,
+            author_filter: None,
+            theme: theme.clone(),
+            promisor: FileBlame::repo_paths(&file_path)
+                .and_then(|(git_root_dir, _)| Promisor::detect(&git_root_dir)),
+            app_state: AppState::Ready,
+            loader: None,
+            progress: None,
+            pending_selection: None,
             state: TableState::default(),
-            file_path: file_path.clone(),
-            commit_sha: commit_sha.clone(),
+            file_path,
+            commit_sha,
             commit_cache: HashMap::new(),
+            commit_recency: CommitRecency::unbounded(),
+            blame_cache: LruCache::unbounded(),
+            highlights: HighlightCache::unbounded(),
             file_blame: None,
+            parse_options: options,
+            file_info: None,
             commit_stack: Vec::new(),
             load_err: None,
+            annotations: None,
+            selection_anchor: None,
+            code_owners: None,
+            file_owners: None,
+            reverts: Reverts::default(),
+            owners_audit: false,
+            plain_view: false,
+            issue_linker: None,
+            trailer_query: String::new(),
+            search_query: String::new(),
+            age_warning: AgeWarning::default(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            last_recorded_action: None,
+            split: None,
+            diff: None,
+            focus: Pane::Main,
+            file_tree: None,
+            floor_commit: None,
+            max_depth: None,
+            followed_ref: None,
+            hotspots: None,
+            hotspot_index: None,
+            color_support: ColorSupport::detect(),
+            accessible: false,
+            locale: Locale::detect(),
+            date_format: DateFormat::Absolute,
+            memory_budget: memory::DEFAULT_MEMORY_BUDGET,
+            show_metrics: false,
+            frame_time: Duration::ZERO,
+            viewport_height: 0,
+            screen_layout: ScreenLayout::default(),
+            last_click: None,
+            review: None,
+            history_start: None,
+            popup: None,
+            external_command: None,
             running: true,
             columns: vec![
                 // All columns have fixed width except the last one which is for the contents.
                 // The last column will take up the remaining width of the table.
                 Column {
+                    kind: ColumnKind::Time,
                     width: Constraint::Max(10),
-                    style: Style::default().fg(Color::Blue),
+                    style: theme.column_style(ColumnKind::Time),
                     name: "TIME".to_string(),
+                    visible: true,
                 },
                 Column {
+                    kind: ColumnKind::Author,
                     width: Constraint::Max(15),
-                    style: Style::default().fg(Color::Red),
+                    style: theme.column_style(ColumnKind::Author),
                     name: "AUTHOR".to_string(),
+                    visible: true,
                 },
                 Column {
+                    kind: ColumnKind::Commit,
                     width: Constraint::Max(8),
-                    style: Style::default().fg(Color::Green),
+                    style: theme.column_style(ColumnKind::Commit),
                     name: "COMMIT".to_string(),
+                    visible: true,
                 },
                 Column {
+                    kind: ColumnKind::Message,
                     width: Constraint::Max(30),
-                    style: Style::default().fg(Color::Green),
+                    style: theme.column_style(ColumnKind::Message),
                     name: "MESSAGE".to_string(),
+                    visible: true,
                 },
                 Column {
+                    kind: ColumnKind::Line,
                     width: Constraint::Max(5),
-                    style: Style::default().fg(Color::Yellow),
+                    style: theme.column_style(ColumnKind::Line),
                     name: "LINE".to_string(),
+                    visible: true,
                 },
                 Column {
+                    kind: ColumnKind::Contents,
                     width: Constraint::Fill(1000),
-                    style: Style::default(),
+                    style: theme.column_style(ColumnKind::Contents),
                     name: "CONTENTS".to_string(),
+                    visible: true,
                 },
             ],
+        }
+    }
+
+    // Show dates in the TIME column in another format. Relative dates, e.g.
+    // "2 years, 11 months ago", need a wider column.
+    pub fn set_date_format(&mut self, date_format: DateFormat) {
+        self.date_format = date_format;
+        let width = match date_format {
+            DateFormat::Absolute => 10,
+            DateFormat::Relative => 22,
         };
+        for column in self.columns.iter_mut() {
+            if column.kind == ColumnKind::Time {
+                column.width = Constraint::Max(width);
+            }
+        }
+    }
 
-        app.load_blame(file_path, commit_sha);
-        app
+    // Use the colors and characters of a theme, also for the columns which
+    // are already shown.
+    pub fn set_theme(&mut self, theme: Theme) {
+        for column in self.columns.iter_mut() {
+            column.style = theme.column_style(column.kind);
+        }
+        self.theme = theme;
+    }
+
+    // Tell the user that syntax highlighting was skipped because the file is
+    // so large, and how to blame only part of it instead.
+    fn warn_if_unhighlighted(&mut self) {
+        let Some(file_blame) = self.file_blame.as_ref() else {
+            return;
+        };
+        if !file_blame.highlighted {
+            self.popup = Some(Popup::Message(format!(
+                "The file has {} lines, so syntax highlighting is off. Use -L START,END to blame a range of lines, or --highlight-line-limit to raise the limit.",
+                file_blame.blame_lines.len()
+            )));
+        }
+    }
+
+    // Overlay annotations from the given provider next to the blame. The
+    // annotation column is shown right before the line number column.
+    pub fn set_annotation_provider(&mut self, provider: Box<dyn AnnotationProvider>) {
+        if !self
+            .columns
+            .iter()
+            .any(|c| c.kind == ColumnKind::Annotation)
+        {
+            let index = self
+                .columns
+                .iter()
+                .position(|c| c.kind == ColumnKind::Line)
+                .unwrap_or(0);
+            self.columns.insert(
+                index,
+                Column {
+                    kind: ColumnKind::Annotation,
+                    width: Constraint::Max(3),
+                    style: self.theme.column_style(ColumnKind::Annotation),
+                    name: "ANN".to_string(),
+                    visible: true,
+                },
+            );
+        }
+        self.annotations = Some(provider);
+    }
+
+    // Scroll the contents to the right, or to the left for a negative
+    // number of columns, but not past the end of the longest line.
+    pub fn scroll_contents(&mut self, columns: isize) {
+        let longest = self.file_blame.as_ref().map_or(0, |file_blame| {
+            file_blame
+                .blame_lines
+                .iter()
+                .map(|line| line.plain_contents.chars().count())
+                .max()
+                .unwrap_or(0)
+        });
+        self.contents_offset = self
+            .contents_offset
+            .saturating_add_signed(columns)
+            .min(longest.saturating_sub(1));
+    }
+
+    // Hide a column, or show it again. The contents can't be hidden.
+    pub fn toggle_column(&mut self, kind: ColumnKind) {
+        if let Some(column) = self.columns.iter_mut().find(|c| c.kind == kind) {
+            column.visible = !column.visible;
+        }
+    }
+
+    // Show a narrow column with each line's age as a bar, relative to when the
+    // history of the repository started, right before the line numbers.
+    pub fn show_age_gauge(&mut self) {
+        if self.columns.iter().any(|c| c.kind == ColumnKind::Age) {
+            return;
+        }
+        self.history_start = FileBlame::repo_paths(&self.file_path)
+            .and_then(|(git_root_dir, _)| FileBlame::history_start(&git_root_dir));
+        let index = self
+            .columns
+            .iter()
+            .position(|c| c.kind == ColumnKind::Line)
+            .unwrap_or(0);
+        self.columns.insert(
+            index,
+            Column {
+                kind: ColumnKind::Age,
+                width: Constraint::Max(3),
+                style: self.theme.column_style(ColumnKind::Age),
+                name: "AGE".to_string(),
+                visible: true,
+            },
+        );
+    }
+
+    // Set how moved and copied lines are detected, and reload the blame with
+    // it. While they're detected, a column shows where each line came from,
+    // right before the line numbers.
+    pub fn set_copy_detection(&mut self, copy_detection: CopyDetection) {
+        let previous = self.parse_options.copy_detection;
+        self.parse_options.copy_detection = copy_detection;
+        if self.file_blame.is_some() {
+            if let Err(e) = self.try_load_blame(self.file_path.clone(), self.commit_sha.clone()) {
+                self.parse_options.copy_detection = previous;
+                self.popup = Some(Popup::Message(e.to_string()));
+                return;
+            }
+        }
+
+        self.show_origin_column(copy_detection != CopyDetection::Off);
+    }
+
+    // Show the column with where each line came from, right before the line
+    // numbers, or remove it.
+    fn show_origin_column(&mut self, show: bool) {
+        let shown = self.columns.iter().any(|c| c.kind == ColumnKind::Origin);
+        if !show {
+            self.columns.retain(|c| c.kind != ColumnKind::Origin);
+        } else if !shown {
+            let index = self
+                .columns
+                .iter()
+                .position(|c| c.kind == ColumnKind::Line)
+                .unwrap_or(0);
+            self.columns.insert(
+                index,
+                Column {
+                    kind: ColumnKind::Origin,
+                    width: Constraint::Max(24),
+                    style: self.theme.column_style(ColumnKind::Origin),
+                    name: "ORIGIN".to_string(),
+                    visible: true,
+                },
+            );
+        }
+    }
+
+    // Load the CODEOWNERS file of the repository, and look up the owners of
+    // the blamed file.
+    fn load_code_owners(&mut self) {
+        if self.file_blame.is_none() {
+            return;
+        }
+        let Some((git_root_dir, relative_path)) = FileBlame::repo_paths(&self.file_path) else {
+            return;
+        };
+        self.code_owners = CodeOwners::load(&git_root_dir);
+        self.file_owners = self
+            .code_owners
+            .as_ref()
+            .and_then(|c| c.owners_for(&relative_path))
+            .map(|o| o.to_vec());
+    }
+
+    // The blames which are shown, in the main pane and the split pane.
+    fn shown_blames(&self) -> impl Iterator<Item = &FileBlame> {
+        self.file_blame
+            .iter()
+            .chain(self.split.as_ref().map(|s| &s.file_blame))
+    }
+
+    // Estimated number of bytes used by the shown blames and the caches.
+    pub fn memory_usage(&self) -> usize {
+        self.shown_blames().map(|b| b.memory_size()).sum::<usize>()
+            + self
+                .blame_cache
+                .iter()
+                .map(|(_, b)| b.memory_size())
+                .sum::<usize>()
+            + memory::highlight_cache_size(&self.highlights)
+            + memory::commit_cache_size(&self.commit_cache)
+    }
+
+    // Evict the least recently used blames, highlighted contents and commits
+    // from the caches, once they grow over the memory budget together with
+    // the shown blames.
+    fn trim_caches(&mut self) {
+        let shown: Vec<&FileBlame> = self
+            .file_blame
+            .iter()
+            .chain(self.split.as_ref().map(|s| &s.file_blame))
+            .collect();
+        memory::trim_caches(
+            &mut self.blame_cache,
+            &mut self.highlights,
+            &mut self.commit_cache,
+            &mut self.commit_recency,
+            self.memory_budget,
+            &shown,
+        );
+    }
+
+    // Blame a file at a commit, or take the blame from the cache if it was
+    // blamed at the same commit with the same options before.
+    fn parse_blame(
+        &mut self,
+        file_path: &str,
+        commit_sha: &str,
+    ) -> Result<FileBlame, FileBlameError> {
+        let key = (
+            file_path.to_string(),
+            commit_sha.to_string(),
+            self.parse_options.clone(),
+        );
+        if let Some(file_blame) = self.blame_cache.get(&key) {
+            return Ok(file_blame.clone());
+        }
+        let file_blame = FileBlame::parse_with_progress(
+            file_path,
+            commit_sha,
+            &mut self.commit_cache,
+            &mut self.highlights,
+            &self.parse_options,
+            |_| {},
+        )?;
+        if is_commit_hash(commit_sha) {
+            self.blame_cache.put(key, file_blame.clone());
+        }
+        Ok(file_blame)
+    }
+
+    // Review the files changed in a range of commits in tabs, starting with
+    // the blamed file, which should be the first of them.
+    pub fn set_review(&mut self, review: Review) {
+        if let Some(file_blame) = self.file_blame.as_mut() {
+            review.filter(file_blame);
+        }
+        self.review = Some(review);
+        self.state.select(Some(self.first_introduced_line()));
+    }
+
+    // Index of the first line introduced in the range under review, or of
+    // the first line if there is none, which is selected when a file is
+    // first opened.
+    fn first_introduced_line(&self) -> usize {
+        let (Some(review), Some(file_blame)) = (&self.review, &self.file_blame) else {
+            return 0;
+        };
+        file_blame
+            .blame_lines
+            .iter()
+            .position(|l| review.is_introduced(&l.commit_sha))
+            .unwrap_or(0)
+    }
+
+    // Blame the file of the next or previous tab, wrapping around, and
+    // select the line which was selected when the tab was last left.
+    pub fn switch_tab(&mut self, forward: bool) {
+        let Some(review) = self.review.as_mut() else {
+            return;
+        };
+        let len = review.files.len();
+        let index = match review.tab_of(&self.file_path) {
+            Some(i) => {
+                review.selected[i] = self.state.selected();
+                if forward {
+                    (i + 1) % len
+                } else {
+                    (i + len - 1) % len
+                }
+            }
+            None => 0,
+        };
+        let file_path = review.files[index].clone();
+        let commit_sha = review.gitref.clone();
+        let selected = review.selected[index];
+
+        match self.try_load_blame(file_path, commit_sha) {
+            Ok(()) => {
+                self.commit_stack.clear();
+                self.load_code_owners();
+                let len = self.file_blame.as_ref().map_or(0, |b| b.blame_lines.len());
+                let selected = selected.unwrap_or_else(|| self.first_introduced_line());
+                self.state.select(Some(selected.min(len.saturating_sub(1))));
+            }
+            Err(e) => self.popup = Some(Popup::Message(e.to_string())),
+        }
+    }
+
+    // Toggle flagging blocks whose author is not among the file's code owners.
+    pub fn toggle_owners_audit(&mut self) {
+        self.owners_audit = !self.owners_audit && self.file_owners.is_some();
     }
 
     /// Handles the tick event of the terminal.
-    pub fn tick(&self) {}
+    pub fn tick(&mut self) {
+        if self.is_loading() {
+            self.finish_loading();
+        } else {
+            self.check_followed_ref();
+        }
+        if self
+            .status
+            .as_ref()
+            .is_some_and(|status| status.shown.elapsed() >= STATUS_DURATION)
+        {
+            self.status = None;
+        }
+    }
 
     /// Set running to false to quit the application.
     pub fn quit(&mut self) {
         self.running = false;
     }
 
+    // Load the blame information for the given file path and commit sha, and
+    // quit if that fails.
+    fn load_blame(&mut self, file_path: String, commit_sha: String) {
+        if let Err(e) = self.try_load_blame(file_path, commit_sha) {
+            self.load_err = Some(e);
+            self.quit();
+        }
+    }
+
     // Load the blame information for the given file path and commit sha.
     // Keep the line with the same number selected if it's still around after
     // loading the new blame information.
-    fn load_blame(&mut self, file_path: String, commit_sha: String) {
-        let file_blame = match FileBlame::parse(&file_path, &commit_sha, &mut self.commit_cache) {
-            Ok(f) => f,
-            Err(e) => {
-                self.load_err = Some(e);
-                self.quit();
-                return;
+    fn try_load_blame(
+        &mut self,
+        file_path: String,
+        commit_sha: String,
+    ) -> Result<(), FileBlameError> {
+        let mut file_blame = self.parse_blame(&file_path, &commit_sha)?;
+        if let Some(review) = &self.review {
+            review.filter(&mut file_blame);
+        }
+
+        // Keep the file tree in sync with the commit being blamed. For the
+        // working tree, the files at HEAD are listed.
+        if let Some(tree) = self.file_tree.as_mut() {
+            let tree_commit = if commit_sha == WORKTREE {
+                "HEAD"
+            } else {
+                &commit_sha
+            };
+            if tree.commit_sha != tree_commit {
+                tree.reload(tree_commit)?;
             }
-        };
+        }
 
+        // Files left out of a sparse checkout are blamed at HEAD instead of
+        // in the working tree.
+        let commit_sha = file_blame.commit_sha.clone();
         self.file_blame = Some(file_blame);
+        self.file_info = FileInfo::load(&file_path, &commit_sha);
+        self.reverts = Reverts::load(&file_path, &commit_sha);
+        self.hotspots = None;
+        self.hotspot_index = None;
+        self.selection_anchor = None;
+        self.split = None;
+        self.focus = Pane::Main;
         self.file_path = file_path;
         self.commit_sha = commit_sha;
+        self.trim_caches();
 
         let i = match self.state.selected() {
             Some(i) => {
@@ -127,6 +1206,16 @@ impl App {
             None => 0,
         };
         self.state.select(Some(i));
+        Ok(())
+    }
+
+    // Get the table state and blame of the pane which has focus, for moving
+    // the selection in it.
+    fn focused_table(&mut self) -> Option<(&mut TableState, &FileBlame)> {
+        match (self.focus, self.split.as_mut()) {
+            (Pane::Split, Some(split)) => Some((&mut split.state, &split.file_blame)),
+            _ => Some((&mut self.state, self.file_blame.as_ref()?)),
+        }
     }
 
     // Move selection to the first line of the next block. A block is a group of lines
@@ -134,112 +1223,201 @@ impl App {
     // information, moving to the next block basically means moving to the next line
     // with a different commit sha i.e the next line with visible commit information.
     pub fn next_block(&mut self) {
-        let next_index = match self.state.selected() {
-            Some(mut current_index) => {
-                if current_index >= self.file_blame.as_ref().unwrap().blame_lines.len() - 1 {
-                    self.file_blame.as_ref().unwrap().blame_lines.len() - 1
-                } else {
-                    let current_sha = &self
-                        .file_blame
-                        .as_ref()
-                        .unwrap()
-                        .blame_lines
-                        .get(current_index)
-                        .unwrap()
-                        .commit_sha;
-
-                    current_index += 1;
-
-                    while (current_index < self.file_blame.as_ref().unwrap().blame_lines.len())
-                        && (current_sha
-                            == &self
-                                .file_blame
-                                .as_ref()
-                                .unwrap()
-                                .blame_lines
-                                .get(current_index)
-                                .unwrap()
-                                .commit_sha)
-                    {
-                        current_index += 1;
+        if let Some((state, file_blame)) = self.focused_table() {
+            let lines = &file_blame.blame_lines;
+            let next_index = match state.selected() {
+                Some(current_index) => {
+                    if current_index >= lines.len() - 1 {
+                        lines.len() - 1
+                    } else {
+                        let current_sha = &lines[current_index].commit_sha;
+                        (current_index + 1..lines.len())
+                            .find(|&i| &lines[i].commit_sha != current_sha)
+                            .unwrap_or(lines.len() - 1)
                     }
-
-                    current_index
                 }
-            }
-            None => 0,
-        };
-        self.state.select(Some(next_index));
+                None => 0,
+            };
+            state.select(Some(next_index));
+        }
     }
 
     // Move selection to the first line of the previous block.
     pub fn previous_block(&mut self) {
-        let next_index = match self.state.selected() {
-            Some(mut current_index) => {
-                if current_index <= 1 {
-                    0
-                } else {
-                    let current_sha = &self
-                        .file_blame
-                        .as_ref()
-                        .unwrap()
-                        .blame_lines
-                        .get(current_index - 1)
-                        .unwrap()
-                        .commit_sha;
-
-                    current_index -= 1;
-
-                    while (current_index > 0)
-                        && (current_sha
-                            == &self
-                                .file_blame
-                                .as_ref()
-                                .unwrap()
-                                .blame_lines
-                                .get(current_index - 1)
-                                .unwrap()
-                                .commit_sha)
-                    {
-                        current_index -= 1;
+        if let Some((state, file_blame)) = self.focused_table() {
+            let lines = &file_blame.blame_lines;
+            let next_index = match state.selected() {
+                Some(current_index) => {
+                    if current_index <= 1 {
+                        0
+                    } else {
+                        let current_sha = &lines[current_index - 1].commit_sha;
+                        (1..current_index)
+                            .rev()
+                            .find(|&i| &lines[i - 1].commit_sha != current_sha)
+                            .unwrap_or(0)
                     }
-
-                    current_index
                 }
-            }
-            None => 0,
-        };
-        self.state.select(Some(next_index));
+                None => 0,
+            };
+            state.select(Some(next_index));
+        }
     }
 
     // Move selection to the next line.
     pub fn next_line(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i >= self.file_blame.as_ref().unwrap().blame_lines.len() - 1 {
-                    0
-                } else {
-                    i + 1
+        if let Some((state, file_blame)) = self.focused_table() {
+            let i = match state.selected() {
+                Some(i) => {
+                    if i >= file_blame.blame_lines.len() - 1 {
+                        0
+                    } else {
+                        i + 1
+                    }
                 }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+                None => 0,
+            };
+            state.select(Some(i));
+        }
     }
 
     // Move selection to the previous line.
     pub fn previous_line(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.file_blame.as_ref().unwrap().blame_lines.len() - 1
-                } else {
-                    i - 1
+        if let Some((state, file_blame)) = self.focused_table() {
+            let i = match state.selected() {
+                Some(i) => {
+                    if i == 0 {
+                        file_blame.blame_lines.len() - 1
+                    } else {
+                        i - 1
+                    }
                 }
+                None => 0,
+            };
+            state.select(Some(i));
+        }
+    }
+
+    // Number of lines moved by a page, which is the number of lines visible
+    // in the table.
+    fn page_height(&self) -> usize {
+        self.viewport_height.max(1)
+    }
+
+    // Move the selection down by a number of lines, or up if it's negative,
+    // stopping at the first and last lines instead of wrapping around. In
+    // the main blame, the view is scrolled by the same number of lines, so
+    // that paging keeps the selection at the same place on the screen.
+    pub fn move_selection(&mut self, delta: isize) {
+        let height = self.page_height();
+        let main = self.focus != Pane::Split || self.split.is_none();
+        let Some((state, file_blame)) = self.focused_table() else {
+            return;
+        };
+        let last = file_blame.blame_lines.len().saturating_sub(1);
+        let i = state.selected().unwrap_or(0) as isize;
+        state.select(Some(
+            i.saturating_add(delta).clamp(0, last as isize) as usize
+        ));
+        if main {
+            let max_offset = (last + 1).saturating_sub(height) as isize;
+            let offset = state.offset() as isize;
+            *state.offset_mut() = offset.saturating_add(delta).clamp(0, max_offset) as usize;
+            self.scroll_to_selection();
+        }
+    }
+
+    // Focus the pane at a position of the mouse, and select the line at it
+    // in the main table. Double clicking a commit blames the line at the
+    // parent of its commit, like next_commit.
+    pub fn click(&mut self, column: u16, row: u16) {
+        let double_click = self.last_click.is_some_and(|(time, c, r)| {
+            time.elapsed() <= DOUBLE_CLICK_INTERVAL && (c, r) == (column, row)
+        });
+        self.last_click = Some((Instant::now(), column, row));
+        self.select_at(column, row);
+
+        let on_commit = self
+            .screen_layout
+            .commit_column
+            .as_ref()
+            .is_some_and(|columns| columns.contains(&column));
+        if double_click && on_commit && self.screen_layout.line_at(column, row).is_some() {
+            self.last_click = None;
+            if self.parse_options.reverse_until.is_some() {
+                self.perform(Action::PreviousCommit);
+            } else {
+                self.perform(Action::NextCommit);
             }
-            None => 0,
+        }
+    }
+
+    // Focus the pane at a position of the mouse, and select the line at it
+    // in the main table.
+    pub fn select_at(&mut self, column: u16, row: u16) {
+        let Some(pane) = self.screen_layout.pane_at(column, row) else {
+            return;
         };
-        self.state.select(Some(i));
+        self.focus = pane;
+        let Some(line) = self.screen_layout.line_at(column, row) else {
+            return;
+        };
+
+        let before = self.view_state();
+        self.state.select(Some(line));
+        if self.view_state() != before {
+            self.undo_stack.push(before);
+            self.redo_stack.clear();
+        }
+        self.sync_split();
+    }
+
+    // Scroll the pane at a position of the mouse, down for a positive number
+    // of turns of the wheel, and up for a negative one.
+    pub fn scroll_wheel(&mut self, column: u16, row: u16, turns: isize) {
+        match self.screen_layout.pane_at(column, row) {
+            Some(Pane::Tree) => {
+                if let Some(tree) = self.file_tree.as_mut() {
+                    tree.move_selection(turns * WHEEL_LINES);
+                }
+            }
+            Some(Pane::Diff) => {
+                if let Some(diff) = self.diff.as_mut() {
+                    diff.scroll_by(turns * WHEEL_LINES);
+                }
+            }
+            Some(pane) => {
+                self.focus = pane;
+                self.move_selection(turns * WHEEL_LINES);
+                self.sync_split();
+            }
+            None => {}
+        }
+    }
+
+    // Set the number of lines visible in the main table, which is only known
+    // when it's rendered, and scroll so that the selected line is visible.
+    pub fn set_viewport_height(&mut self, height: usize) {
+        self.viewport_height = height;
+        self.scroll_to_selection();
+    }
+
+    // Scroll the main table as little as possible for the selected line to
+    // be visible, e.g. after moving the selection past the edge of the view
+    // or jumping to a line. The view is never scrolled past the last line.
+    fn scroll_to_selection(&mut self) {
+        let Some(len) = self.file_blame.as_ref().map(|b| b.blame_lines.len()) else {
+            return;
+        };
+        let height = self.page_height();
+        let selected = self.state.selected().unwrap_or(0);
+        let mut offset = self.state.offset().min(len.saturating_sub(height));
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset + height {
+            offset = selected + 1 - height;
+        }
+        *self.state.offset_mut() = offset;
     }
 
     // Show the blame information for the same file, but at the parent commit of the
@@ -257,31 +1435,1564 @@ impl App {
             .blame_lines
             .get(i)
             .unwrap();
-        let commit_context = self.commit_cache.get(&blame_line.commit_sha).unwrap();
+
+        // If the file was renamed in the commit, it's blamed under its old
+        // name at the parent commit.
+        let file_path = self
+            .previous_path(blame_line)
+            .unwrap_or_else(|| self.file_path.clone());
 
         // If the commit doesn't have a parent (i.e it's the initial commit), or if the file
         // didn't exist at the parent commit, then we can't show the blame at the parent commit.
-        if commit_context.parent_commit_sha.is_none()
-            || !FileBlame::exists_at_commit(
-                &self.file_path,
-                commit_context.parent_commit_sha.as_ref().unwrap(),
-            )
-        {
+        let Some(parent_sha) = blame_line.previous_sha.clone() else {
+            return;
+        };
+        if !FileBlame::exists_at_commit(&file_path, &parent_sha) {
+            return;
+        }
+
+        // Don't travel past the floor commit, i.e. to a commit which isn't
+        // the floor commit or one of its descendants.
+        if let Some(floor) = &self.floor_commit {
+            if !FileBlame::is_ancestor(&self.file_path, floor, &parent_sha) {
+                self.popup = Some(Popup::Message(format!(
+                    "Reached the floor commit {}",
+                    floor
+                )));
+                return;
+            }
+        }
+
+        if let Some(max_depth) = self.max_depth.filter(|&d| self.commit_stack.len() >= d) {
+            self.popup = Some(Popup::Message(format!(
+                "Reached the maximum depth of {}, use --max-depth to travel further",
+                max_depth
+            )));
+            return;
+        }
+
+        self.commit_stack
+            .push((self.commit_sha.clone(), self.file_path.clone()));
+        self.map_line_range(&parent_sha, &self.commit_sha.clone(), true);
+        self.load_blame(file_path, parent_sha);
+    }
+
+    // The path of the file at the parent of a line's commit, relative to the
+    // current directory, if the file had another name there.
+    fn previous_path(&self, blame_line: &BlameLine) -> Option<String> {
+        let previous_path = blame_line.previous_path.as_ref()?;
+        let (git_root_dir, relative_path) = FileBlame::repo_paths(&self.file_path)?;
+        (*previous_path != relative_path)
+            .then(|| FileBlame::display_path(&git_root_dir, previous_path))
+    }
+
+    // Show the blame for the same file at the commit which last changed the
+    // selected line, i.e. how the file looked like right after the change.
+    // Like traveling to the parent, the current commit is kept on the commit
+    // stack for traveling forwards in time again.
+    pub fn blame_at_selected_commit(&mut self) {
+        let Some(sha) = self.selected_commit().map(|c| c.sha.clone()) else {
+            return;
+        };
+        if sha.bytes().all(|b| b == b'0') {
+            self.popup = Some(Popup::Message("The line isn't committed yet".to_string()));
+            return;
+        }
+        let current = FileBlame::repo_paths(&self.file_path)
+            .and_then(|(git_root_dir, _)| DiskCache::resolve(&git_root_dir, &self.commit_sha));
+        if current.as_ref() == Some(&sha) {
+            self.popup = Some(Popup::Message(format!(
+                "Already blaming at the line's commit {}",
+                &sha[..8]
+            )));
             return;
         }
 
-        self.commit_stack.push(self.commit_sha.clone());
+        self.commit_stack
+            .push((self.commit_sha.clone(), self.file_path.clone()));
+        self.map_line_range(&sha, &self.commit_sha.clone(), true);
+        self.load_blame(self.file_path.clone(), sha);
+    }
 
-        self.load_blame(
-            self.file_path.clone(),
-            commit_context.parent_commit_sha.as_ref().unwrap().clone(),
+    // Ignore the selected line's commit, e.g. a commit which only reformatted
+    // the code, and blame the file again so that its lines are blamed on the
+    // commits before it.
+    pub fn ignore_selected_commit(&mut self) {
+        let Some(sha) = self.selected_commit().map(|c| c.sha.clone()) else {
+            return;
+        };
+        if sha.bytes().all(|b| b == b'0') {
+            self.popup = Some(Popup::Message("The line isn't committed yet".to_string()));
+            return;
+        }
+        // Lines which git can't blame on other commits stay with the commit.
+        if self.parse_options.ignore_revs.contains(&sha) {
+            self.popup = Some(Popup::Message(format!(
+                "Already ignoring {}, the lines left couldn't be blamed on other commits",
+                &sha[..8]
+            )));
+            return;
+        }
+
+        self.parse_options.ignore_revs.push(sha);
+        if let Err(e) = self.try_load_blame(self.file_path.clone(), self.commit_sha.clone()) {
+            self.parse_options.ignore_revs.pop();
+            self.popup = Some(Popup::Message(e.to_string()));
+        }
+    }
+
+    // Show the ignored commits which the lines marked with * were blamed
+    // past, or go back to showing the commits they're blamed on.
+    pub fn toggle_skipped_commits(&mut self) {
+        if self.show_skipped_commits {
+            self.show_skipped_commits = false;
+            return;
+        }
+        let skipped = self.file_blame.as_ref().map_or(0, |blame| {
+            blame
+                .blame_lines
+                .iter()
+                .filter(|line| line.skipped_commit.is_some())
+                .count()
+        });
+        if skipped == 0 {
+            self.set_status(
+                "No lines were blamed past ignored commits".to_string(),
+                true,
+            );
+            return;
+        }
+        self.show_skipped_commits = true;
+        self.set_status(
+            format!(
+                "Showing the ignored commits of {} lines, * to hide",
+                skipped
+            ),
+            false,
         );
     }
 
-    // Go back to the previous commit in the commit stack.
+    // Whether a notice is shown instead of the blame, since the file is
+    // stored with Git LFS and only its pointer would be blamed.
+    pub fn shows_lfs_notice(&self) -> bool {
+        !self.blame_lfs_pointers
+            && self
+                .file_blame
+                .as_ref()
+                .is_some_and(|b| b.lfs_pointer.is_some())
+    }
+
+    // Copy the selected line's full commit hash to the clipboard, optionally
+    // followed by the summary of its commit message.
+    pub fn copy_selected_commit(&mut self, with_message: bool) {
+        let Some(commit) = self.selected_commit() else {
+            return;
+        };
+        if commit.sha.bytes().all(|b| b == b'0') {
+            self.set_status("The line isn't committed yet".to_string(), true);
+            return;
+        }
+        let text = if with_message {
+            format!("{}: {}", commit.sha, commit.commit_message)
+        } else {
+            commit.sha.clone()
+        };
+
+        match clipboard::copy(&text) {
+            Ok(()) => self.set_status(format!("Copied {}", text), false),
+            Err(e) => self.set_status(format!("Failed to copy: {}", e), true),
+        }
+    }
+
+    // Show a message in the status bar until it expires.
+    pub fn set_status(&mut self, text: String, is_error: bool) {
+        self.status = Some(StatusMessage {
+            text,
+            is_error,
+            shown: Instant::now(),
+        });
+    }
+
+    // Go back to the previous commit in the commit stack. In a reverse blame,
+    // it's older than the current one.
     pub fn previous_commit(&mut self) {
-        if let Some(sha) = self.commit_stack.pop() {
-            self.load_blame(self.file_path.clone(), sha)
+        if let Some((sha, file_path)) = self.commit_stack.pop() {
+            if self.parse_options.reverse_until.is_some() {
+                self.map_line_range(&sha, &self.commit_sha.clone(), true);
+            } else {
+                self.map_line_range(&self.commit_sha.clone(), &sha, false);
+            }
+            self.load_blame(file_path, sha)
+        }
+    }
+
+    // In a reverse blame, show the patch of the commit which deleted the
+    // selected line, scrolled to the removed line, next to the blame at that
+    // commit with the place where the line was selected. Like traveling to
+    // the parent, the current commit is kept on the commit stack for going
+    // back. If the commit deleted the whole file, or is the end of the
+    // reverse blame, only its patch is shown.
+    pub fn follow_deletion(&mut self) {
+        let Some(end) = self.parse_options.reverse_until.clone() else {
+            return;
+        };
+        let Some(blame_line) = self
+            .state
+            .selected()
+            .and_then(|i| self.file_blame.as_ref()?.blame_lines.get(i))
+            .cloned()
+        else {
+            return;
+        };
+        let Some(deleting_sha) = blame_line.previous_sha.clone() else {
+            self.popup = Some(Popup::Message(format!("The line still exists at {}", end)));
+            return;
+        };
+        let Some((git_root_dir, relative_path)) = FileBlame::repo_paths(&self.file_path) else {
+            return;
+        };
+        let mut diff = match DiffPane::load(&git_root_dir, &deleting_sha) {
+            Ok(diff) => diff,
+            Err(e) => {
+                self.popup = Some(Popup::Message(e.to_string()));
+                return;
+            }
+        };
+        let old_path = blame_line.original_path.clone().unwrap_or(relative_path);
+        diff.scroll_to_removed_line(&old_path, blame_line.original_line);
+
+        let file_path = self
+            .previous_path(&blame_line)
+            .unwrap_or_else(|| self.file_path.clone());
+        let is_end = FileBlame::rev_parse(&self.file_path, &deleting_sha).ok()
+            == FileBlame::rev_parse(&self.file_path, &end).ok();
+        if FileBlame::exists_at_commit(&file_path, &deleting_sha) && !is_end {
+            let line_range = self.parse_options.line_range;
+            self.commit_stack
+                .push((self.commit_sha.clone(), self.file_path.clone()));
+            self.map_line_range(&self.commit_sha.clone(), &deleting_sha, false);
+            if let Err(e) = self.try_load_blame(file_path, deleting_sha.clone()) {
+                self.commit_stack.pop();
+                self.parse_options.line_range = line_range;
+                self.popup = Some(Popup::Message(e.to_string()));
+                return;
+            }
+            let line = LineMap::between(&self.file_path, &blame_line.commit_sha, &deleting_sha)
+                .old_to_new(blame_line.original_line);
+            self.select_lines(line, line);
+        }
+
+        self.split = None;
+        self.diff = Some(diff);
+    }
+
+    // When only a range of lines is blamed, move the range along with the
+    // lines when traveling between an older and a newer commit, so that the
+    // same section of the file stays blamed even if lines were added or
+    // removed above it.
+    fn map_line_range(&mut self, old_sha: &str, new_sha: &str, to_old: bool) {
+        let Some((start, end)) = self.parse_options.line_range else {
+            return;
+        };
+        let line_map = LineMap::between(&self.file_path, old_sha, new_sha);
+        let map = |line| {
+            if to_old {
+                line_map.new_to_old(line)
+            } else {
+                line_map.old_to_new(line)
+            }
+        };
+        // Lines changed between the commits map to the start of their hunk, so
+        // the end is found from the line after the range, which keeps lines
+        // added at the end of the range inside it. If all lines of the range
+        // were added, the line before them is blamed.
+        let end = map(end + 1).saturating_sub(1).max(1);
+        self.parse_options.line_range = Some((map(start).min(end), end));
+    }
+
+    // Get the commit which last changed the currently selected line.
+    pub fn selected_commit(&self) -> Option<&Commit> {
+        let i = self.state.selected()?;
+        let blame_line = self.file_blame.as_ref()?.blame_lines.get(i)?;
+        self.commit_cache.get(&blame_line.commit_sha)
+    }
+
+    // The absolute path of the blamed file and the number of the selected
+    // line, as "path:line", for tools following the blame cursor.
+    pub fn cursor_location(&self) -> Option<String> {
+        let i = self.state.selected()?;
+        let blame_line = self.file_blame.as_ref()?.blame_lines.get(i)?;
+        let path = FileBlame::absolute_path(&self.file_path)?;
+        Some(format!("{}:{}", path.display(), blame_line.line_number))
+    }
+
+    // Open the commit detail popup for the currently selected line's commit.
+    pub fn open_commit_popup(&mut self) {
+        let Some(sha) = self.selected_commit().map(|c| c.sha.clone()) else {
+            return;
+        };
+        // Uncommitted lines don't have a commit to show details of.
+        let details = match FileBlame::repo_paths(&self.file_path) {
+            Some((git_root_dir, _)) if !sha.chars().all(|c| c == '0') => {
+                CommitDetails::load(&git_root_dir, &sha)
+            }
+            _ => CommitDetails::default(),
+        };
+        self.popup = Some(Popup::CommitDetail(sha, details));
+    }
+
+    pub fn close_popup(&mut self) {
+        self.popup = None;
+    }
+
+    // Ask for confirmation before running an action on the commit shown in
+    // the commit popup, or else on the selected line's commit.
+    pub fn request_commit_action(&mut self, action: CommitAction) {
+        let sha = match &self.popup {
+            Some(Popup::CommitDetail(sha, _)) => Some(sha.clone()),
+            _ => self.selected_commit().map(|c| c.sha.clone()),
+        };
+        if let Some(sha) = sha {
+            self.popup = Some(Popup::ConfirmCommitAction(action, sha));
+        }
+    }
+
+    // Show the details of the commit which a commit reverts, or else of the
+    // commit reverting it, e.g. of the selected line's commit or of the
+    // commit in the commit popup, for following a chain of reverts.
+    pub fn show_revert_link(&mut self, sha: &str) {
+        let Some(linked) = self.reverts.linked(sha).map(str::to_string) else {
+            self.set_status(
+                format!("{:.8} neither reverts a commit nor was reverted", sha),
+                false,
+            );
+            return;
+        };
+        let Some((git_root_dir, _)) = FileBlame::repo_paths(&self.file_path) else {
+            return;
+        };
+        // The commit is only cached if a line of the blame is blamed on it.
+        self.commit_cache
+            .entry(linked.clone())
+            .or_insert_with(|| Commit::load(&git_root_dir, &linked));
+        let details = CommitDetails::load(&git_root_dir, &linked);
+        self.popup = Some(Popup::CommitDetail(linked, details));
+    }
+
+    // Schedule the confirmed action to be run by the main loop. The command is
+    // run in the root of the repository of the blamed file so that git finds it.
+    pub fn confirm_commit_action(&mut self) {
+        if let Some(Popup::ConfirmCommitAction(action, sha)) = &self.popup {
+            let current_dir = FileBlame::repo_paths(&self.file_path)
+                .map(|(git_root_dir, _)| git_root_dir)
+                .unwrap_or_else(|| ".".to_string());
+
+            self.external_command = Some(ExternalCommand {
+                program: "git".to_string(),
+                args: action.git_args(sha),
+                current_dir,
+            });
+        }
+        self.popup = None;
+    }
+
+    // Start selecting a range of lines from the currently selected line, or
+    // stop selecting if a range is already being selected.
+    pub fn toggle_selection(&mut self) {
+        self.selection_anchor = match self.selection_anchor {
+            Some(_) => None,
+            None => self.state.selected(),
+        };
+    }
+
+    // Select the lines with the given numbers, e.g. of a diff hunk, and
+    // scroll so that they start a few lines below the top of the screen.
+    pub fn select_lines(&mut self, start: usize, end: usize) {
+        if self.is_loading() {
+            self.pending_selection = Some((start, end));
+            return;
+        }
+        let Some(file_blame) = self.file_blame.as_ref() else {
+            return;
+        };
+        let index = |number: usize| {
+            file_blame
+                .blame_lines
+                .iter()
+                .position(|l| l.line_number.parse() == Ok(number))
+        };
+        let Some(first) = index(start).or(file_blame.blame_lines.len().checked_sub(1)) else {
+            return;
+        };
+        let last = index(end).unwrap_or(first);
+
+        self.state.select(Some(first));
+        self.selection_anchor = (last != first).then_some(last);
+        *self.state.offset_mut() = first.saturating_sub(SCROLL_CONTEXT_LINES);
+    }
+
+    // The range of lines which is currently selected. If no range is being
+    // selected, this is just the currently selected line.
+    pub fn selected_range(&self) -> Option<RangeInclusive<usize>> {
+        let selected = self.state.selected()?;
+        let anchor = self.selection_anchor.unwrap_or(selected);
+        Some(anchor.min(selected)..=anchor.max(selected))
+    }
+
+    // Export the selected range of lines as markdown to a file in the current
+    // directory, named after the blamed file and the line range.
+    pub fn export_markdown(&mut self) {
+        let (Some(range), Some(file_blame)) = (self.selected_range(), self.file_blame.as_ref())
+        else {
+            return;
+        };
+
+        let markdown = export::markdown(file_blame, &self.commit_cache, range.clone());
+        let file_name = Path::new(&self.file_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let export_path = format!(
+            "{}-L{}-L{}.md",
+            file_name,
+            file_blame.blame_lines[*range.start()].line_number,
+            file_blame.blame_lines[*range.end()].line_number
+        );
+
+        let message = match fs::write(&export_path, markdown) {
+            Ok(_) => format!("Exported selected lines to {}", export_path),
+            Err(e) => format!("Failed to export selected lines: {}", e),
+        };
+        self.selection_anchor = None;
+        self.popup = Some(Popup::Message(message));
+    }
+
+    // Open the issue referenced in the selected line's commit message in the
+    // browser. If the message references multiple issues, the first one is opened.
+    pub fn open_issue(&mut self) {
+        let Some(linker) = &self.issue_linker else {
+            self.popup = Some(Popup::Message(
+                "No issue pattern configured, use --issue-pattern and --issue-url".to_string(),
+            ));
+            return;
+        };
+        let Some(commit) = self.selected_commit() else {
+            return;
+        };
+
+        match linker.urls(&commit.commit_message).first() {
+            Some(url) => {
+                if let Err(e) = links::open_url(url) {
+                    self.popup = Some(Popup::Message(format!("Failed to open {}: {}", url, e)));
+                }
+            }
+            None => {
+                self.popup = Some(Popup::Message(
+                    "No issues referenced in the commit message".to_string(),
+                ))
+            }
+        }
+    }
+
+    // Open the web page of the selected line's commit on the code hosting
+    // site of the origin remote, e.g. GitHub, or of the file at the commit
+    // with the line in it highlighted.
+    pub fn open_selected_commit_on_web(&mut self, file: bool) {
+        let Some(i) = self.state.selected() else {
+            return;
+        };
+        let Some(blame_line) = self.file_blame.as_ref().and_then(|b| b.blame_lines.get(i)) else {
+            return;
+        };
+        if blame_line.commit_sha.bytes().all(|b| b == b'0') {
+            self.set_status("The line isn't committed yet".to_string(), true);
+            return;
+        }
+        let Some((git_root_dir, relative_path)) = FileBlame::repo_paths(&self.file_path) else {
+            return;
+        };
+        let Some(remote) = WebRemote::origin(&git_root_dir) else {
+            self.set_status(
+                "The origin remote isn't on GitHub, GitLab or Bitbucket".to_string(),
+                true,
+            );
+            return;
+        };
+
+        // The line may have had another number, and the file another path,
+        // in the line's commit.
+        let url = if file {
+            let path = blame_line
+                .original_path
+                .as_deref()
+                .unwrap_or(&relative_path);
+            remote.file_url(&blame_line.commit_sha, path, blame_line.original_line)
+        } else {
+            remote.commit_url(&blame_line.commit_sha)
+        };
+        match links::open_url(&url) {
+            Ok(()) => self.set_status(format!("Opened {}", url), false),
+            Err(e) => self.set_status(format!("Failed to open {}: {}", url, e), true),
+        }
+    }
+
+    // Open a prompt for entering text, prefilled with the previous input.
+    pub fn open_prompt(&mut self, kind: PromptKind) {
+        let text = match kind {
+            PromptKind::Trailer => self.trailer_query.clone(),
+            PromptKind::CommitType => self.commit_type_query.clone(),
+            PromptKind::Command | PromptKind::Search => String::new(),
+        };
+        self.popup = Some(Popup::Prompt(kind, text));
+    }
+
+    pub fn prompt_input(&mut self, c: char) {
+        if let Some(Popup::Prompt(_, text)) = &mut self.popup {
+            text.push(c);
+        }
+    }
+
+    pub fn prompt_backspace(&mut self) {
+        if let Some(Popup::Prompt(_, text)) = &mut self.popup {
+            text.pop();
         }
     }
+
+    // Close the prompt and use the entered text.
+    pub fn submit_prompt(&mut self) {
+        let Some(Popup::Prompt(kind, text)) = self.popup.take() else {
+            return;
+        };
+        match kind {
+            PromptKind::Trailer => {
+                self.trailer_query = text;
+                self.next_block_with_trailer();
+            }
+            PromptKind::Command => self.run_command(&text),
+            PromptKind::CommitType => {
+                self.commit_type_query = text.trim().to_lowercase();
+                self.next_block_with_commit_type();
+            }
+            PromptKind::Search => {
+                self.search_query = text;
+                self.next_search_match(true);
+            }
+        }
+    }
+
+    // Move selection to the next or previous line matching the search query,
+    // wrapping around at the end of the file.
+    pub fn next_search_match(&mut self, forward: bool) {
+        let Some(file_blame) = self.file_blame.as_ref() else {
+            return;
+        };
+        if self.search_query.is_empty() {
+            return;
+        }
+        let len = file_blame.blame_lines.len();
+        let selected = self.state.selected().unwrap_or(0);
+        let found = (1..=len)
+            .map(|step| {
+                if forward {
+                    (selected + step) % len
+                } else {
+                    (selected + len - step % len) % len
+                }
+            })
+            .find(|&i| {
+                search::matches(
+                    &file_blame.blame_lines[i].plain_contents,
+                    &self.search_query,
+                )
+            });
+        match found {
+            Some(i) => self.state.select(Some(i)),
+            None => {
+                self.popup = Some(Popup::Message(format!(
+                    "Pattern not found: {}",
+                    self.search_query
+                )))
+            }
+        }
+    }
+
+    // Run a command entered at the command prompt. The commands are:
+    //   - "ref <ref>" - blame at a ref, e.g. a branch or a commit
+    //   - "ref merge-base <branch>" - blame at the merge base of HEAD and a branch
+    //   - "floor <ref>" - don't travel backwards in time past a commit
+    //   - "floor merge-base <branch>" - don't travel past the merge base of HEAD and a branch
+    //   - "floor" - remove the floor commit
+    //   - "<action>" - perform a named action, e.g. "toggle_file_tree"
+    pub fn run_command(&mut self, command: &str) {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let result = match words[..] {
+            [] => Ok(()),
+            ["ref", "merge-base", branch] => FileBlame::merge_base(&self.file_path, "HEAD", branch)
+                .and_then(|sha| self.blame_at(sha))
+                .map_err(|e| e.to_string()),
+            ["ref", commit_sha] => self
+                .blame_at(commit_sha.to_string())
+                .map_err(|e| e.to_string()),
+            ["floor", "merge-base", branch] => {
+                FileBlame::merge_base(&self.file_path, "HEAD", branch)
+                    .and_then(|sha| self.set_floor(Some(&sha)))
+                    .map_err(|e| e.to_string())
+            }
+            ["floor", commit_sha] => self.set_floor(Some(commit_sha)).map_err(|e| e.to_string()),
+            ["floor"] => self.set_floor(None).map_err(|e| e.to_string()),
+            [name] => name.parse().map(|action| self.perform(action)),
+            _ => Err(format!("unknown command '{}'", command)),
+        };
+        if let Err(e) = result {
+            self.popup = Some(Popup::Message(e));
+        }
+    }
+
+    // Move selection to the first line of the next hottest block, starting
+    // with the hottest one.
+    pub fn next_hotspot(&mut self) {
+        let Some(file_blame) = &self.file_blame else {
+            return;
+        };
+        let hotspots = self
+            .hotspots
+            .get_or_insert_with(|| hotspots::compute(file_blame, age::now()));
+        if hotspots.is_empty() {
+            self.popup = Some(Popup::Message("No hotspots found".to_string()));
+            return;
+        }
+
+        let index = self.hotspot_index.map_or(0, |i| (i + 1) % hotspots.len());
+        self.state.select(Some(*hotspots[index].block.start()));
+        self.hotspot_index = Some(index);
+    }
+
+    // Set or remove the floor commit, past which travelling backwards in time
+    // is not possible.
+    pub fn set_floor(&mut self, commit_sha: Option<&str>) -> Result<(), FileBlameError> {
+        self.floor_commit = match commit_sha {
+            Some(commit_sha) => Some(FileBlame::rev_parse(&self.file_path, commit_sha)?),
+            None => None,
+        };
+        Ok(())
+    }
+
+    // Blame the file at another commit. The current commit is pushed onto the
+    // commit stack, so that it can be returned to.
+    pub fn blame_at(&mut self, commit_sha: String) -> Result<(), FileBlameError> {
+        let before = self.view_state();
+        let previous = (self.commit_sha.clone(), self.file_path.clone());
+        self.try_load_blame(self.file_path.clone(), commit_sha)?;
+        self.commit_stack.push(previous);
+        self.undo_stack.push(before);
+        self.redo_stack.clear();
+        Ok(())
+    }
+
+    // Move selection to the next block whose commit has a trailer matching
+    // the last entered trailer query.
+    pub fn next_block_with_trailer(&mut self) {
+        if self.trailer_query.is_empty() {
+            return;
+        }
+        let query = self.trailer_query.clone();
+        if !self.next_block_where(|commit| commit.has_trailer(&query)) {
+            self.popup = Some(Popup::Message(format!(
+                "No blocks with a trailer matching '{}'",
+                query
+            )));
+        }
+    }
+
+    // Move selection to the next block whose commit has the last entered
+    // conventional commit type.
+    pub fn next_block_with_commit_type(&mut self) {
+        if self.commit_type_query.is_empty() {
+            self.popup = Some(Popup::Message(
+                "No commit type to jump to, enter one with F".to_string(),
+            ));
+            return;
+        }
+        let query = self.commit_type_query.clone();
+        if !self
+            .next_block_where(|commit| message::commit_type(&commit.commit_message) == Some(&query))
+        {
+            self.popup = Some(Popup::Message(format!(
+                "No blocks with commits of type '{}'",
+                query
+            )));
+        }
+    }
+
+    // Move selection to the first line of the next block whose commit matches
+    // a predicate, wrapping around at the end of the file. Returns whether a
+    // matching block was found.
+    pub fn next_block_where(&mut self, predicate: impl Fn(&Commit) -> bool) -> bool {
+        let Some(file_blame) = self.file_blame.as_ref() else {
+            return false;
+        };
+        let selected = self.state.selected().unwrap_or(0);
+        let blocks = file_blame.blocks();
+
+        // Start looking from the block after the currently selected one.
+        let current = blocks
+            .iter()
+            .position(|b| b.contains(&selected))
+            .unwrap_or(0);
+        let next = blocks
+            .iter()
+            .cycle()
+            .skip(current + 1)
+            .take(blocks.len())
+            .find(|block| {
+                let sha = &file_blame.blame_lines[*block.start()].commit_sha;
+                self.commit_cache.get(sha).is_some_and(&predicate)
+            });
+
+        match next {
+            Some(block) => {
+                self.state.select(Some(*block.start()));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Open the statistics popup for the blamed file.
+    pub fn open_statistics(&mut self) {
+        if let Some(file_blame) = &self.file_blame {
+            let mut stats = FileStats::compute(file_blame, &self.commit_cache);
+            stats.monthly_commits = MonthlyCommits::load(&self.file_path, &self.commit_sha);
+            stats.sort_authors(self.author_order);
+            // The filtered author is highlighted, for picking another one.
+            let i = stats
+                .authors
+                .iter()
+                .position(|a| Some(&a.author) == self.author_filter.as_ref())
+                .unwrap_or(0);
+            self.popup = Some(Popup::Statistics(stats, i));
+        }
+    }
+
+    // Move the highlight in the statistics by a number of authors, stopping
+    // at the first and last one.
+    pub fn move_statistics(&mut self, delta: isize) {
+        if let Some(Popup::Statistics(stats, i)) = &mut self.popup {
+            let last = stats.authors.len().saturating_sub(1);
+            *i = i.saturating_add_signed(delta).min(last);
+        }
+    }
+
+    // Sort the authors in the statistics in the next order, keeping the
+    // highlighted author.
+    pub fn cycle_author_order(&mut self) {
+        self.author_order = self.author_order.next();
+        if let Some(Popup::Statistics(stats, i)) = &mut self.popup {
+            let author = stats.authors.get(*i).map(|a| a.author.clone());
+            stats.sort_authors(self.author_order);
+            *i = stats
+                .authors
+                .iter()
+                .position(|a| Some(&a.author) == author.as_ref())
+                .unwrap_or(0);
+        }
+    }
+
+    // Close the statistics and filter the blame by the highlighted author:
+    // their first block is selected, and the lines of other authors are
+    // dimmed. Picking the filtered author again removes the filter.
+    pub fn filter_by_author(&mut self) {
+        let Some(Popup::Statistics(stats, i)) = self.popup.take() else {
+            return;
+        };
+        let Some(author) = stats.authors.get(i).map(|a| a.author.clone()) else {
+            return;
+        };
+        let before = self.view_state();
+        if self.author_filter.as_ref() == Some(&author) {
+            self.author_filter = None;
+        } else {
+            let first_line = self.file_blame.as_ref().and_then(|file_blame| {
+                file_blame.blame_lines.iter().position(|line| {
+                    self.commit_cache
+                        .get(&line.commit_sha)
+                        .is_some_and(|c| c.author == author)
+                })
+            });
+            self.state.select(first_line.or(self.state.selected()));
+            self.author_filter = Some(author);
+        }
+        self.undo_stack.push(before);
+        self.redo_stack.clear();
+    }
+
+    // Move selection to the next block by the author picked in the
+    // statistics.
+    pub fn next_block_by_author(&mut self) {
+        let Some(author) = self.author_filter.clone() else {
+            self.popup = Some(Popup::Message(
+                "No author to jump to, pick one in the statistics (s) with Enter".to_string(),
+            ));
+            return;
+        };
+        if !self.next_block_where(|commit| commit.author == author) {
+            self.popup = Some(Popup::Message(format!("No blocks by {}", author)));
+        }
+    }
+
+    // The actions applicable to the selected line's commit, for the context menu.
+    pub fn context_menu_items(&self) -> Vec<Action> {
+        let Some(commit) = self.selected_commit() else {
+            return vec![];
+        };
+
+        let mut items = vec![Action::ShowCommit];
+        if commit.parent_commit_sha.is_some() && self.parse_options.reverse_until.is_none() {
+            items.push(Action::NextCommit);
+        }
+        items.push(Action::BlameAtCommit);
+        items.push(Action::IgnoreCommit);
+        items.push(Action::CopySha);
+        items.push(Action::OpenCommitOnWeb);
+        if self
+            .issue_linker
+            .as_ref()
+            .is_some_and(|l| !l.find(&commit.commit_message).is_empty())
+        {
+            items.push(Action::OpenIssue);
+        }
+        if self.reverts.linked(&commit.sha).is_some() {
+            items.push(Action::ShowRevertLink);
+        }
+        items.extend([
+            Action::Revert,
+            Action::CherryPick,
+            Action::ExportMarkdown,
+            Action::Statistics,
+        ]);
+        items
+    }
+
+    pub fn open_context_menu(&mut self) {
+        if !self.context_menu_items().is_empty() {
+            self.popup = Some(Popup::ContextMenu(0));
+        }
+    }
+
+    // Move the highlighted context menu item up or down, wrapping around.
+    pub fn move_context_menu(&mut self, down: bool) {
+        let len = self.context_menu_items().len();
+        if let Some(Popup::ContextMenu(i)) = &mut self.popup {
+            *i = if down {
+                (*i + 1) % len
+            } else {
+                (*i + len - 1) % len
+            };
+        }
+    }
+
+    // Show the full contents of the selected line, e.g. of a line which is
+    // too long to be shown in the table.
+    pub fn expand_line(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.popup = Some(Popup::ExpandedLine(i, 0));
+        }
+    }
+
+    pub fn scroll_expanded_line(&mut self, down: bool) {
+        if let Some(Popup::ExpandedLine(_, scroll)) = &mut self.popup {
+            *scroll = if down {
+                scroll.saturating_add(1)
+            } else {
+                scroll.saturating_sub(1)
+            };
+        }
+    }
+
+    // Indices of the lines with TODO, FIXME or HACK comments.
+    pub fn todo_lines(&self) -> Vec<usize> {
+        let pattern = Regex::new(TODO_REGEX).unwrap();
+        self.file_blame
+            .iter()
+            .flat_map(|fb| fb.blame_lines.iter().enumerate())
+            .filter(|(_, line)| pattern.is_match(&line.plain_contents))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn open_todos(&mut self) {
+        self.popup = Some(if self.todo_lines().is_empty() {
+            Popup::Message("No TODO, FIXME or HACK comments".to_string())
+        } else {
+            Popup::Todos(0)
+        });
+    }
+
+    pub fn move_todos(&mut self, down: bool) {
+        let len = self.todo_lines().len();
+        if let Some(Popup::Todos(i)) = &mut self.popup {
+            *i = if down {
+                (*i + 1) % len
+            } else {
+                (*i + len - 1) % len
+            };
+        }
+    }
+
+    // Close the list and select the highlighted comment's line.
+    pub fn jump_to_todo(&mut self) {
+        let Some(Popup::Todos(i)) = self.popup.take() else {
+            return;
+        };
+        if let Some(line) = self.todo_lines().get(i).copied() {
+            let before = self.view_state();
+            self.state.select(Some(line));
+            if self.view_state() != before {
+                self.undo_stack.push(before);
+                self.redo_stack.clear();
+            }
+        }
+    }
+
+    // The blocks of lines with the same commit, as ranges of indices into the
+    // blame lines, in the order of the overview of the blocks. Blocks which
+    // are equal in the order stay in the order of the file.
+    pub fn blocks(&self) -> Vec<RangeInclusive<usize>> {
+        let Some(file_blame) = &self.file_blame else {
+            return vec![];
+        };
+        let mut blocks = file_blame.blocks();
+        let commit = |block: &RangeInclusive<usize>| {
+            let sha = &file_blame.blame_lines[*block.start()].commit_sha;
+            self.commit_cache.get(sha)
+        };
+        match self.block_order {
+            BlockOrder::Position => {}
+            BlockOrder::Newest => {
+                blocks.sort_by_key(|b| Reverse(commit(b).map_or(i64::MIN, |c| c.time)))
+            }
+            BlockOrder::Author => blocks.sort_by_cached_key(|b| {
+                commit(b)
+                    .map(|c| c.author.to_lowercase())
+                    .unwrap_or_default()
+            }),
+            BlockOrder::Size => blocks.sort_by_key(|b| Reverse(b.clone().count())),
+        }
+        blocks
+    }
+
+    // Show the blocks in the overview in the next order, from the first one,
+    // e.g. the newest block.
+    pub fn cycle_block_order(&mut self) {
+        if let Some(Popup::Blocks(i)) = &mut self.popup {
+            self.block_order = self.block_order.next();
+            *i = 0;
+        }
+    }
+
+    // Open the overview of the blocks, with the selected line's block
+    // highlighted.
+    pub fn open_blocks(&mut self) {
+        let selected = self.state.selected().unwrap_or(0);
+        let blocks = self.blocks();
+        if blocks.is_empty() {
+            return;
+        }
+        let i = blocks
+            .iter()
+            .position(|block| block.contains(&selected))
+            .unwrap_or(0);
+        self.popup = Some(Popup::Blocks(i));
+    }
+
+    // Move the highlight in the overview of the blocks by a number of rows,
+    // stopping at the first and last block.
+    pub fn move_blocks(&mut self, delta: isize) {
+        let len = self.blocks().len();
+        if let Some(Popup::Blocks(i)) = &mut self.popup {
+            *i = i.saturating_add_signed(delta).min(len.saturating_sub(1));
+        }
+    }
+
+    // Close the overview and select the first line of the highlighted block.
+    pub fn jump_to_block(&mut self) {
+        let Some(Popup::Blocks(i)) = self.popup.take() else {
+            return;
+        };
+        if let Some(block) = self.blocks().get(i) {
+            let before = self.view_state();
+            self.state.select(Some(*block.start()));
+            if self.view_state() != before {
+                self.undo_stack.push(before);
+                self.redo_stack.clear();
+            }
+        }
+    }
+
+    // Open the list of the commits traveled through, with the current commit
+    // highlighted.
+    pub fn open_commit_history(&mut self) {
+        if self.commit_stack.is_empty() {
+            self.set_status("Not traveled to other commits yet".to_string(), false);
+            return;
+        }
+        self.popup = Some(Popup::CommitHistory(self.commit_stack.len()));
+    }
+
+    // Move the highlight in the list of the commits traveled through, where
+    // the last row is the current commit.
+    pub fn move_commit_history(&mut self, delta: isize) {
+        let last = self.commit_stack.len();
+        if let Some(Popup::CommitHistory(i)) = &mut self.popup {
+            *i = i.saturating_add_signed(delta).min(last);
+        }
+    }
+
+    // Close the list and go back to the highlighted commit directly, dropping
+    // it and the commits after it from the commit stack, as if they had been
+    // gone back from one at a time.
+    pub fn jump_to_commit_history(&mut self) {
+        let Some(Popup::CommitHistory(i)) = self.popup.take() else {
+            return;
+        };
+        if i >= self.commit_stack.len() {
+            return;
+        }
+        let before = self.view_state();
+        let (sha, file_path) = self.commit_stack[i].clone();
+        self.commit_stack.truncate(i);
+        self.map_line_range(&self.commit_sha.clone(), &sha, false);
+        self.load_blame(file_path, sha);
+        if self.view_state() != before {
+            self.undo_stack.push(before);
+            self.redo_stack.clear();
+        }
+    }
+
+    pub fn scroll_help(&mut self, delta: i16) {
+        let last = self.key_map.help_entries().len().saturating_sub(1) as u16;
+        if let Some(Popup::Help(scroll)) = &mut self.popup {
+            *scroll = scroll.saturating_add_signed(delta).min(last);
+        }
+    }
+
+    // Perform the highlighted context menu item.
+    pub fn run_context_menu_item(&mut self) {
+        let Some(Popup::ContextMenu(i)) = self.popup.take() else {
+            return;
+        };
+        if let Some(action) = self.context_menu_items().get(i).copied() {
+            self.perform(action);
+        }
+    }
+
+    // Perform a named action. All keybindings, mouse gestures and menus go
+    // through here. If the action changes the view, the previous view is
+    // recorded so that the change can be undone.
+    pub fn perform(&mut self, action: Action) {
+        if let Action::Undo | Action::Redo = action {
+            self.undo_redo(action == Action::Undo);
+            return;
+        }
+
+        let before = self.view_state();
+        self.perform_without_recording(action);
+        self.sync_split();
+        if self.view_state() != before {
+            // Repeated line moves are recorded as a single change, since
+            // undoing them one line at a time isn't useful.
+            let repeated_move = self.last_recorded_action == Some(action)
+                && matches!(
+                    action,
+                    Action::NextLine
+                        | Action::PreviousLine
+                        | Action::PageDown
+                        | Action::PageUp
+                        | Action::HalfPageDown
+                        | Action::HalfPageUp
+                );
+            if !repeated_move {
+                self.undo_stack.push(before);
+            }
+            self.redo_stack.clear();
+            self.last_recorded_action = Some(action);
+        }
+    }
+
+    fn perform_without_recording(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.quit(),
+            Action::NextLine => self.next_line(),
+            Action::PreviousLine => self.previous_line(),
+            Action::NextBlock => self.next_block(),
+            Action::PreviousBlock => self.previous_block(),
+            // In a reverse blame, Left still goes back in time, which is
+            // back along the commit stack, and Right goes forward to where
+            // the selected line was deleted.
+            Action::NextCommit if self.parse_options.reverse_until.is_some() => {
+                self.previous_commit()
+            }
+            Action::PreviousCommit if self.parse_options.reverse_until.is_some() => {
+                self.follow_deletion()
+            }
+            Action::NextCommit => self.next_commit(),
+            Action::PreviousCommit => self.previous_commit(),
+            Action::ShowCommit => self.open_commit_popup(),
+            Action::Revert => self.request_commit_action(CommitAction::Revert),
+            Action::CherryPick => self.request_commit_action(CommitAction::CherryPick),
+            Action::ToggleSelection => self.toggle_selection(),
+            Action::ExportMarkdown => self.export_markdown(),
+            Action::ToggleOwnersAudit => self.toggle_owners_audit(),
+            Action::OpenIssue => self.open_issue(),
+            Action::JumpToTrailer => self.open_prompt(PromptKind::Trailer),
+            Action::NextTrailerMatch => self.next_block_with_trailer(),
+            Action::JumpToCommitType => self.open_prompt(PromptKind::CommitType),
+            Action::NextCommitTypeMatch => self.next_block_with_commit_type(),
+            Action::ToggleCommitTypeBadges => self.commit_type_badges = !self.commit_type_badges,
+            Action::NextAuthorBlock => self.next_block_by_author(),
+            Action::Statistics => self.open_statistics(),
+            Action::ContextMenu => self.open_context_menu(),
+            Action::Undo | Action::Redo => {}
+            Action::ToggleParentSplit => self.toggle_parent_split(),
+            Action::FocusNextPane => self.focus_next_pane(),
+            Action::ToggleScrollLock => self.toggle_scroll_lock(),
+            Action::ToggleFileTree => self.toggle_file_tree(),
+            Action::TogglePlainView => self.plain_view = !self.plain_view,
+            Action::ToggleWorktreeBlame => self.toggle_worktree_blame(),
+            Action::Command => self.open_prompt(PromptKind::Command),
+            Action::NextHotspot => self.next_hotspot(),
+            Action::Todos => self.open_todos(),
+            Action::ExpandLine => self.expand_line(),
+            Action::ToggleMetrics => self.show_metrics = !self.show_metrics,
+            Action::NextTab => self.switch_tab(true),
+            Action::PreviousTab => self.switch_tab(false),
+            Action::ToggleDiff => self.toggle_diff(),
+            Action::Legend => self.toggle_legend(),
+            Action::Search => self.open_prompt(PromptKind::Search),
+            Action::NextSearchMatch => self.next_search_match(true),
+            Action::PreviousSearchMatch => self.next_search_match(false),
+            Action::PageDown => self.move_selection(self.page_height() as isize),
+            Action::PageUp => self.move_selection(-(self.page_height() as isize)),
+            Action::HalfPageDown => self.move_selection(self.page_height() as isize / 2),
+            Action::HalfPageUp => self.move_selection(-(self.page_height() as isize / 2)),
+            Action::FirstLine => self.move_selection(isize::MIN),
+            Action::LastLine => self.move_selection(isize::MAX),
+            Action::JumpToNewTip => self.jump_to_new_tip(),
+            Action::BlameAtCommit => self.blame_at_selected_commit(),
+            Action::ToggleIgnoreRevs => self.toggle_ignore_revs(),
+            Action::IgnoreCommit => self.ignore_selected_commit(),
+            Action::ShowSkippedCommits => self.toggle_skipped_commits(),
+            Action::ToggleRelativeDates => self.set_date_format(self.date_format.toggled()),
+            Action::ToggleHeatmap => self.heatmap = !self.heatmap,
+            Action::ScrollRight => self.scroll_contents(CONTENTS_SCROLL_STEP as isize),
+            Action::ScrollLeft => self.scroll_contents(-(CONTENTS_SCROLL_STEP as isize)),
+            Action::ToggleWrap => self.wrap_contents = !self.wrap_contents,
+            Action::ToggleTimeColumn => self.toggle_column(ColumnKind::Time),
+            Action::ToggleAuthorColumn => self.toggle_column(ColumnKind::Author),
+            Action::ToggleCommitColumn => self.toggle_column(ColumnKind::Commit),
+            Action::ToggleMessageColumn => self.toggle_column(ColumnKind::Message),
+            Action::ToggleLineColumn => self.toggle_column(ColumnKind::Line),
+            Action::BlameLfsPointer => self.blame_lfs_pointers = true,
+            Action::OpenCommitOnWeb => self.open_selected_commit_on_web(false),
+            Action::OpenFileOnWeb => self.open_selected_commit_on_web(true),
+            Action::Blocks => self.open_blocks(),
+            Action::ShowRevertLink => {
+                if let Some(sha) = self.selected_commit().map(|c| c.sha.clone()) {
+                    self.show_revert_link(&sha);
+                }
+            }
+            Action::CommitHistory => self.open_commit_history(),
+            Action::Help => self.popup = Some(Popup::Help(0)),
+            Action::CopySha => self.copy_selected_commit(false),
+            Action::CopyShaAndMessage => self.copy_selected_commit(true),
+            Action::CycleCopyDetection => {
+                self.set_copy_detection(self.parse_options.copy_detection.next())
+            }
+        }
+    }
+
+    // Show the blame at the parent of the selected line's commit side by side
+    // with the main blame, or hide it if it's already shown.
+    pub fn toggle_parent_split(&mut self) {
+        if self.split.take().is_some() {
+            self.focus = Pane::Main;
+            return;
+        }
+
+        // The line is blamed at next at the parent it was changed from,
+        // which for a merge commit needn't be the first parent. In a reverse
+        // blame, the line's previous commit is the one which deleted it.
+        let blame_line = self
+            .state
+            .selected()
+            .and_then(|i| self.file_blame.as_ref()?.blame_lines.get(i))
+            .filter(|_| self.parse_options.reverse_until.is_none());
+        let Some(parent) = blame_line
+            .and_then(|l| l.previous_sha.clone())
+            .or_else(|| self.selected_commit()?.parent_commit_sha.clone())
+        else {
+            self.popup = Some(Popup::Message(
+                "The commit of the selected line has no parent".to_string(),
+            ));
+            return;
+        };
+        let file_path = blame_line
+            .and_then(|l| self.previous_path(l))
+            .unwrap_or_else(|| self.file_path.clone());
+        if !FileBlame::exists_at_commit(&file_path, &parent) {
+            self.popup = Some(Popup::Message(format!(
+                "The file doesn't exist at commit {}",
+                parent
+            )));
+            return;
+        }
+
+        match self.parse_blame(&file_path, &parent) {
+            Ok(file_blame) => {
+                self.diff = None;
+                self.split = Some(SplitPane {
+                    line_map: LineMap::between(&self.file_path, &parent, &self.commit_sha),
+                    commit_sha: parent,
+                    file_blame,
+                    state: TableState::default(),
+                    locked: true,
+                });
+                self.sync_split();
+                self.trim_caches();
+            }
+            Err(e) => self.popup = Some(Popup::Message(e.to_string())),
+        }
+    }
+
+    pub fn toggle_legend(&mut self) {
+        self.popup = match self.popup {
+            Some(Popup::Legend) => None,
+            _ => Some(Popup::Legend),
+        };
+    }
+
+    // Show the patch of the selected line's commit side by side with the
+    // main blame, or hide it if it's already shown.
+    pub fn toggle_diff(&mut self) {
+        if self.diff.take().is_some() {
+            self.focus = Pane::Main;
+            return;
+        }
+
+        let Some(sha) = self.selected_commit().map(|c| c.sha.clone()) else {
+            return;
+        };
+        if sha.chars().all(|c| c == '0') {
+            self.popup = Some(Popup::Message(
+                "The selected line isn't committed yet".to_string(),
+            ));
+            return;
+        }
+        let Some((git_root_dir, _)) = FileBlame::repo_paths(&self.file_path) else {
+            return;
+        };
+        match DiffPane::load(&git_root_dir, &sha) {
+            Ok(diff) => {
+                self.split = None;
+                self.diff = Some(diff);
+                self.focus = Pane::Diff;
+            }
+            Err(e) => self.popup = Some(Popup::Message(e.to_string())),
+        }
+    }
+
+    // Move focus to the next pane which is shown, from left to right.
+    pub fn focus_next_pane(&mut self) {
+        let mut panes = vec![];
+        if self.file_tree.is_some() {
+            panes.push(Pane::Tree);
+        }
+        panes.push(Pane::Main);
+        if self.split.is_some() {
+            panes.push(Pane::Split);
+        }
+        if self.diff.is_some() {
+            panes.push(Pane::Diff);
+        }
+        let current = panes.iter().position(|p| *p == self.focus).unwrap_or(0);
+        self.focus = panes[(current + 1) % panes.len()];
+    }
+
+    // Show the tree of the repository's files at the blamed commit in a
+    // sidebar, or hide it if it's already shown.
+    pub fn toggle_file_tree(&mut self) {
+        if self.file_tree.take().is_some() {
+            self.focus = Pane::Main;
+            return;
+        }
+
+        let Some((git_root_dir, relative_path)) = FileBlame::repo_paths(&self.file_path) else {
+            return;
+        };
+        match FileTree::load(&git_root_dir, &self.commit_sha) {
+            Ok(mut tree) => {
+                tree.reveal(&relative_path);
+                self.file_tree = Some(tree);
+                self.focus = Pane::Tree;
+            }
+            Err(e) => self.popup = Some(Popup::Message(e.to_string())),
+        }
+    }
+
+    // Open the selected file tree entry: expand or collapse a directory, or
+    // blame a file at the same commit.
+    pub fn open_tree_selection(&mut self) {
+        let Some(tree) = self.file_tree.as_mut() else {
+            return;
+        };
+        let Some(item) = tree.selected_item() else {
+            return;
+        };
+
+        if item.is_dir {
+            tree.toggle_expanded(&item.path);
+        } else {
+            // Show paths relative to the current directory where possible,
+            // like the path of the file given on the command line.
+            let path = Path::new(&tree.git_root_dir).join(&item.path);
+            let path = std::env::current_dir()
+                .and_then(|d| d.canonicalize())
+                .ok()
+                .and_then(|d| path.strip_prefix(d).ok().map(|p| p.to_path_buf()))
+                .unwrap_or(path);
+            self.open_file(path.to_string_lossy().to_string());
+        }
+    }
+
+    // Whether HEAD is blamed while the file in the working tree has uncommitted
+    // changes, which are then missing from the blame.
+    pub fn has_uncommitted_changes(&self) -> bool {
+        self.commit_sha == "HEAD"
+            && self
+                .file_info
+                .as_ref()
+                .is_some_and(|i| i.differs_from_worktree)
+    }
+
+    // Switch between blaming the file in the working tree and at HEAD.
+    pub fn toggle_worktree_blame(&mut self) {
+        let commit_sha = if self.commit_sha == WORKTREE {
+            "HEAD"
+        } else {
+            WORKTREE
+        };
+        if self.file_info.as_ref().is_some_and(|i| i.sparse) {
+            self.popup = Some(Popup::Message(
+                "The file isn't checked out by the sparse checkout, so it can only be blamed at commits"
+                    .to_string(),
+            ));
+            return;
+        }
+        if let Err(e) = self.try_load_blame(self.file_path.clone(), commit_sha.to_string()) {
+            self.popup = Some(Popup::Message(e.to_string()));
+        }
+    }
+
+    // Turn ignoring the commits listed in the ignore revs file off or on,
+    // and reload the blame.
+    pub fn toggle_ignore_revs(&mut self) {
+        let Some(file) = &self.ignore_revs_file else {
+            self.popup = Some(Popup::Message(
+                "No commits to ignore, add a .git-blame-ignore-revs file or use --ignore-revs-file"
+                    .to_string(),
+            ));
+            return;
+        };
+        let previous = self.parse_options.ignore_revs_file.take();
+        if previous.is_none() {
+            self.parse_options.ignore_revs_file = Some(file.clone());
+        }
+        if let Err(e) = self.try_load_blame(self.file_path.clone(), self.commit_sha.clone()) {
+            self.parse_options.ignore_revs_file = previous;
+            self.popup = Some(Popup::Message(e.to_string()));
+        }
+    }
+
+    // Blame another file at the same commit.
+    pub fn open_file(&mut self, file_path: String) {
+        let before = self.view_state();
+        match self.try_load_blame(file_path, self.commit_sha.clone()) {
+            Ok(()) => {
+                self.undo_stack.push(before);
+                self.redo_stack.clear();
+                self.load_code_owners();
+            }
+            Err(e) => self.popup = Some(Popup::Message(e.to_string())),
+        }
+    }
+
+    // Lock or unlock scrolling of the split pane to the main blame. When the
+    // lock is re-established, the pane without focus follows the one with focus.
+    pub fn toggle_scroll_lock(&mut self) {
+        if let Some(split) = self.split.as_mut() {
+            split.locked = !split.locked;
+        }
+    }
+
+    // If scrolling is locked, select the line in the pane without focus which
+    // corresponds to the selected line in the pane with focus.
+    pub fn sync_split(&mut self) {
+        let (Some(split), Some(file_blame)) = (self.split.as_mut(), self.file_blame.as_ref())
+        else {
+            return;
+        };
+        if !split.locked {
+            return;
+        }
+
+        match self.focus {
+            Pane::Tree | Pane::Main | Pane::Diff => sync_selection(
+                (&file_blame.blame_lines, &self.state),
+                (&split.file_blame.blame_lines, &mut split.state),
+                |line| split.line_map.new_to_old(line),
+            ),
+            Pane::Split => sync_selection(
+                (&split.file_blame.blame_lines, &split.state),
+                (&file_blame.blame_lines, &mut self.state),
+                |line| split.line_map.old_to_new(line),
+            ),
+        }
+    }
+
+    // Take a snapshot of the current state of the view.
+    pub fn view_state(&self) -> ViewState {
+        ViewState {
+            file_path: self.file_path.clone(),
+            commit_sha: self.commit_sha.clone(),
+            commit_stack: self.commit_stack.clone(),
+            selected: self.state.selected(),
+            selection_anchor: self.selection_anchor,
+            owners_audit: self.owners_audit,
+            plain_view: self.plain_view,
+            trailer_query: self.trailer_query.clone(),
+            commit_type_query: self.commit_type_query.clone(),
+            author_filter: self.author_filter.clone(),
+            line_range: self.parse_options.line_range,
+            ignore_revs: self.parse_options.ignore_revs.clone(),
+            ignore_revs_file: self.parse_options.ignore_revs_file.clone(),
+            show_skipped_commits: self.show_skipped_commits,
+            copy_detection: self.parse_options.copy_detection,
+            heatmap: self.heatmap,
+            date_format: self.date_format,
+            hidden_columns: self
+                .columns
+                .iter()
+                .filter(|c| !c.visible)
+                .map(|c| c.kind)
+                .collect(),
+            wrap_contents: self.wrap_contents,
+            commit_type_badges: self.commit_type_badges,
+        }
+    }
+
+    // Restore the view from a snapshot, reloading the blame if the snapshot
+    // is for a different file, commit or options. If reloading fails, the
+    // view is left as it is.
+    fn restore_view_state(&mut self, view: ViewState) -> Result<(), FileBlameError> {
+        let options = ParseOptions {
+            line_range: view.line_range,
+            ignore_revs: view.ignore_revs,
+            ignore_revs_file: view.ignore_revs_file,
+            copy_detection: view.copy_detection,
+            ..self.parse_options.clone()
+        };
+        if view.file_path != self.file_path
+            || view.commit_sha != self.commit_sha
+            || options != self.parse_options
+        {
+            let previous = std::mem::replace(&mut self.parse_options, options);
+            if let Err(e) = self.try_load_blame(view.file_path, view.commit_sha) {
+                self.parse_options = previous;
+                return Err(e);
+            }
+        }
+        self.show_origin_column(view.copy_detection != CopyDetection::Off);
+        self.commit_stack = view.commit_stack;
+        self.state.select(view.selected);
+        self.selection_anchor = view.selection_anchor;
+        self.owners_audit = view.owners_audit;
+        self.plain_view = view.plain_view;
+        self.trailer_query = view.trailer_query;
+        self.commit_type_query = view.commit_type_query;
+        self.author_filter = view.author_filter;
+        self.show_skipped_commits = view.show_skipped_commits;
+        self.heatmap = view.heatmap;
+        self.set_date_format(view.date_format);
+        for column in self.columns.iter_mut() {
+            column.visible = !view.hidden_columns.contains(&column.kind);
+        }
+        self.wrap_contents = view.wrap_contents;
+        self.commit_type_badges = view.commit_type_badges;
+        Ok(())
+    }
+
+    // Undo the last change to the view, or redo the last undone change. If
+    // the blame of the view can't be loaded, e.g. because the file was
+    // deleted since, the change stays to be undone or redone.
+    fn undo_redo(&mut self, undo: bool) {
+        let current = self.view_state();
+        let from = if undo {
+            &mut self.undo_stack
+        } else {
+            &mut self.redo_stack
+        };
+        let Some(view) = from.pop() else {
+            return;
+        };
+        self.last_recorded_action = None;
+        match self.restore_view_state(view.clone()) {
+            Ok(()) => {
+                let to = if undo {
+                    &mut self.redo_stack
+                } else {
+                    &mut self.undo_stack
+                };
+                to.push(current);
+            }
+            Err(e) => {
+                let from = if undo {
+                    &mut self.undo_stack
+                } else {
+                    &mut self.redo_stack
+                };
+                from.push(view);
+                self.popup = Some(Popup::Message(e.to_string()));
+            }
+        }
+    }
+}
+
+// Select the line in one table which corresponds to the selected line in
+// another table, using a mapping between their line numbers.
+fn sync_selection(
+    from: (&[BlameLine], &TableState),
+    to: (&[BlameLine], &mut TableState),
+    map: impl Fn(usize) -> usize,
+) {
+    let Some(line) = from
+        .1
+        .selected()
+        .and_then(|i| from.0.get(i))
+        .and_then(|l| l.line_number.parse().ok())
+    else {
+        return;
+    };
+
+    let target = map(line).to_string();
+    let index =
+        to.0.iter()
+            .position(|l| l.line_number == target)
+            .unwrap_or(to.0.len().saturating_sub(1));
+    to.1.select(Some(index));
+}
+
+// Whether a commit is given by its full hash, rather than e.g. by a ref.
+fn is_commit_hash(commit_sha: &str) -> bool {
+    (commit_sha.len() == 40 || commit_sha.len() == 64)
+        && commit_sha.bytes().all(|b| b.is_ascii_hexdigit())
 }
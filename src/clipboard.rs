@@ -0,0 +1,20 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::env;
+use std::io::{self, Write};
+
+// Copy text to the system clipboard with the OSC 52 escape sequence, which
+// the terminal turns into a write to the clipboard. Unlike clipboard tools,
+// this also works over SSH, since the sequence travels to the terminal on
+// the local machine. Inside tmux, the sequence is wrapped so that tmux
+// passes it on to the terminal.
+pub fn copy(text: &str) -> io::Result<()> {
+    let mut sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    if env::var_os("TMUX").is_some() {
+        sequence = format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"));
+    }
+    // The user interface is drawn on stderr, so that's where the terminal is.
+    let mut stderr = io::stderr();
+    stderr.write_all(sequence.as_bytes())?;
+    stderr.flush()
+}
@@ -0,0 +1,169 @@
+use crate::file_blame::Commit;
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+
+// Locations where a CODEOWNERS file is looked up, relative to the root of
+// the repository. The first one which exists is used.
+const CODEOWNERS_PATHS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+// A single CODEOWNERS rule: a path pattern and the owners of matching files.
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: Regex,
+    owners: Vec<String>,
+}
+
+// Parsed CODEOWNERS file of a repository.
+#[derive(Debug, Clone, Default)]
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+impl CodeOwners {
+    // Load the CODEOWNERS file of the repository with the given root directory.
+    pub fn load(git_root_dir: &str) -> Option<CodeOwners> {
+        CODEOWNERS_PATHS
+            .iter()
+            .find_map(|p| fs::read_to_string(Path::new(git_root_dir).join(p)).ok())
+            .map(|contents| CodeOwners::parse(&contents))
+    }
+
+    // Parse the contents of a CODEOWNERS file. Each non-empty line which is not
+    // a comment contains a pattern followed by a list of owners.
+    pub fn parse(contents: &str) -> CodeOwners {
+        let rules = contents
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or("").trim())
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let pattern = pattern_to_regex(parts.next()?)?;
+                Some(Rule {
+                    pattern,
+                    owners: parts.map(|o| o.to_string()).collect(),
+                })
+            })
+            .collect();
+        CodeOwners { rules }
+    }
+
+    // Get the owners of a file, given by its path relative to the root of the
+    // repository. Like in git, the last matching rule takes precedence.
+    pub fn owners_for(&self, relative_path: &str) -> Option<&[String]> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.pattern.is_match(relative_path))
+            .map(|rule| rule.owners.as_slice())
+    }
+}
+
+// Check whether the author of a commit is one of the given owners. Owners can
+// be given as emails or as usernames, which are compared to the author's name
+// and the user part of the author's email. Teams (@org/team) can't be resolved
+// to their members, so they never match.
+pub fn is_owner(owners: &[String], commit: &Commit) -> bool {
+    let email_user = commit.author_email.split('@').next().unwrap_or("");
+    owners.iter().any(|owner| match owner.strip_prefix('@') {
+        Some(user) if !user.contains('/') => {
+            user.eq_ignore_ascii_case(email_user) || user.eq_ignore_ascii_case(&commit.author)
+        }
+        Some(_) => false,
+        None => owner.eq_ignore_ascii_case(&commit.author_email),
+    })
+}
+
+// Convert a CODEOWNERS pattern, which uses the gitignore syntax, into a regex
+// matching paths relative to the root of the repository.
+fn pattern_to_regex(pattern: &str) -> Option<Regex> {
+    // Patterns which start with a slash or contain a slash in the middle are
+    // relative to the root, other patterns match at any depth.
+    let trimmed = pattern.trim_end_matches('/');
+    let anchored = trimmed.contains('/');
+    let trimmed = trimmed.trim_start_matches('/');
+
+    let mut regex = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = trimmed.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // "**/" matches zero or more directories
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    // A pattern matching a directory also matches everything inside it.
+    regex.push_str("(?:/.*)?$");
+    Regex::new(&regex).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owners<'a>(code_owners: &'a CodeOwners, path: &str) -> Vec<&'a str> {
+        code_owners
+            .owners_for(path)
+            .unwrap_or_default()
+            .iter()
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let code_owners = CodeOwners::parse(
+            "# Owners\n\
+             *       @everyone\n\
+             *.rs    @rustaceans # Rust\n\
+             /docs/  docs@example.com\n\
+             src/**/gen/ @generators\n",
+        );
+        assert_eq!(owners(&code_owners, "README.md"), ["@everyone"]);
+        assert_eq!(owners(&code_owners, "src/main.rs"), ["@rustaceans"]);
+        assert_eq!(
+            owners(&code_owners, "docs/guide/intro.md"),
+            ["docs@example.com"]
+        );
+        assert_eq!(owners(&code_owners, "other/docs/a.md"), ["@everyone"]);
+        assert_eq!(owners(&code_owners, "src/gen/a.rs"), ["@generators"]);
+        assert_eq!(owners(&code_owners, "src/a/b/gen/c.rs"), ["@generators"]);
+    }
+
+    #[test]
+    fn patterns_follow_gitignore_syntax() {
+        let matches = |pattern: &str, path: &str| pattern_to_regex(pattern).unwrap().is_match(path);
+        assert!(matches("*.rs", "a/b/c.rs"));
+        assert!(!matches("/*.rs", "a/c.rs"));
+        assert!(matches("a?c", "abc"));
+        assert!(!matches("a?c", "a/c"));
+        assert!(matches("build/", "build/out/x"));
+        assert!(matches("a.b", "a.b"));
+        assert!(!matches("a.b", "axb"));
+    }
+
+    #[test]
+    fn matches_owners_to_authors() {
+        let commit = Commit {
+            author: "Jane Doe".to_string(),
+            author_email: "jane@example.com".to_string(),
+            ..Commit::default()
+        };
+        assert!(is_owner(&["@jane".to_string()], &commit));
+        assert!(is_owner(&["JANE@example.com".to_string()], &commit));
+        assert!(is_owner(&["@Jane Doe".to_string()], &commit));
+        assert!(!is_owner(&["@org/jane".to_string()], &commit));
+        assert!(!is_owner(&["@john".to_string()], &commit));
+    }
+}
@@ -0,0 +1,208 @@
+use ratatui::buffer::Buffer;
+use ratatui::style::Color;
+use std::env;
+
+// The colors which a terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorSupport {
+    // 24-bit RGB colors.
+    TrueColor,
+    // The xterm palette of 256 colors.
+    Ansi256,
+    // The 16 basic ANSI colors.
+    Ansi16,
+    // No colors, e.g. when colors were turned off by the user.
+    None,
+}
+
+// RGB values of the 16 basic ANSI colors, as shown by xterm.
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+// Levels of each channel in the 6x6x6 color cube of the 256 color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorSupport {
+    // Detect the colors supported by the terminal from the environment.
+    // Terminals with true color support usually set COLORTERM, while TERM
+    // tells whether the terminal supports 256 colors.
+    pub fn detect() -> ColorSupport {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorSupport::TrueColor
+        } else if term.contains("256color") {
+            ColorSupport::Ansi256
+        } else {
+            ColorSupport::Ansi16
+        }
+    }
+
+    // Convert a color to the nearest color the terminal supports.
+    pub fn convert(&self, color: Color) -> Color {
+        if *self == ColorSupport::None {
+            return Color::Reset;
+        }
+        let Color::Rgb(r, g, b) = color else {
+            return match (self, color) {
+                (ColorSupport::Ansi16, Color::Indexed(i)) if i >= 16 => {
+                    let (r, g, b) = indexed_to_rgb(i);
+                    nearest_ansi16(r, g, b)
+                }
+                _ => color,
+            };
+        };
+        match self {
+            ColorSupport::TrueColor => color,
+            ColorSupport::Ansi256 => Color::Indexed(nearest_indexed(r, g, b)),
+            ColorSupport::Ansi16 => nearest_ansi16(r, g, b),
+            ColorSupport::None => Color::Reset,
+        }
+    }
+
+    // Convert the colors of all cells of a rendered buffer.
+    pub fn convert_buffer(&self, buffer: &mut Buffer) {
+        if *self == ColorSupport::TrueColor {
+            return;
+        }
+        for cell in buffer.content.iter_mut() {
+            cell.fg = self.convert(cell.fg);
+            cell.bg = self.convert(cell.bg);
+        }
+    }
+}
+
+// Decide whether to use colors when the user didn't choose, following the
+// NO_COLOR (https://no-color.org) and CLICOLOR_FORCE conventions. Otherwise,
+// colors are only used when writing to a terminal.
+pub fn colors_by_default(is_terminal: bool) -> bool {
+    let set = |name: &str| env::var(name).is_ok_and(|v| !v.is_empty() && v != "0");
+    if env::var("NO_COLOR").is_ok_and(|v| !v.is_empty()) {
+        false
+    } else if set("CLICOLOR_FORCE") {
+        true
+    } else {
+        is_terminal
+    }
+}
+
+// Whether the terminal has a light background, as far as it tells. Many
+// terminals, e.g. rxvt, Konsole and iTerm2, set COLORFGBG to the indexes of
+// their default foreground and background colors, e.g. "0;15" for black text
+// on white, with the background last.
+pub fn is_light_background() -> bool {
+    let Ok(colorfgbg) = env::var("COLORFGBG") else {
+        return false;
+    };
+    let background = colorfgbg
+        .rsplit(';')
+        .next()
+        .and_then(|i| i.parse::<usize>().ok());
+    match background.and_then(|i| ANSI16.get(i)) {
+        Some((_, (r, g, b))) => {
+            // Perceived brightness, which weighs green the most.
+            299 * *r as u32 + 587 * *g as u32 + 114 * *b as u32 > 128_000
+        }
+        None => false,
+    }
+}
+
+// A subtle dark color derived from a commit hash, for telling commits apart
+// by color. The hue comes from the hash, while the saturation and brightness
+// are fixed, so that the colors are equally subtle and text stays readable
+// on them.
+pub fn commit_accent(sha: &str) -> Color {
+    let hue = sha
+        .get(..4)
+        .and_then(|prefix| u16::from_str_radix(prefix, 16).ok())
+        .unwrap_or_default() as f32
+        * 360.0
+        / 65536.0;
+    let (saturation, value) = (0.5, 0.4);
+
+    // Convert from HSV to RGB.
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let channel = |c: f32| ((c + value - chroma) * 255.0).round() as u8;
+    Color::Rgb(channel(r), channel(g), channel(b))
+}
+
+// Squared distance between two RGB colors.
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> i32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+fn nearest_ansi16(r: u8, g: u8, b: u8) -> Color {
+    ANSI16
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+        .map(|(color, _)| *color)
+        .unwrap()
+}
+
+// Find the nearest color of the 256 color palette, which is either in the
+// color cube (16-231) or in the grayscale ramp (232-255).
+fn nearest_indexed(r: u8, g: u8, b: u8) -> u8 {
+    let level = |v: u8| {
+        (0..6)
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - v as i32).abs())
+            .unwrap()
+    };
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube = 16 + 36 * ri + 6 * gi + bi;
+
+    let average = (r as usize + g as usize + b as usize) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23);
+
+    if distance(indexed_to_rgb(gray as u8), (r, g, b))
+        < distance(indexed_to_rgb(cube as u8), (r, g, b))
+    {
+        gray as u8
+    } else {
+        cube as u8
+    }
+}
+
+// RGB values of a color of the 256 color palette.
+fn indexed_to_rgb(i: u8) -> (u8, u8, u8) {
+    match i {
+        0..=15 => ANSI16[i as usize].1,
+        16..=231 => {
+            let i = i as usize - 16;
+            (
+                CUBE_LEVELS[i / 36],
+                CUBE_LEVELS[(i / 6) % 6],
+                CUBE_LEVELS[i % 6],
+            )
+        }
+        _ => {
+            let v = 8 + 10 * (i - 232);
+            (v, v, v)
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use crate::action::Action;
+use crate::handler::{self, KeyMap};
+use crate::suggest;
+use crate::theme::{self, Theme};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// Settings read from the config file, e.g. ~/.config/blame/config.toml. Only
+// the subset of TOML which the settings need is supported: comments, tables,
+// and keys with strings or arrays of strings on a single line, e.g.
+//
+//   [keys]
+//   next_block = "J"
+//   previous_block = ["K", "ctrl-p"]
+//
+//   [theme]
+//   preset = "solarized"
+//   author = "magenta bold"
+//   syntax = "Solarized (dark)"
+#[derive(Debug, Default)]
+pub struct Config {
+    // Actions and the keys they're bound to instead of their default ones,
+    // in the order in which they're listed in the file.
+    pub key_bindings: Vec<(Action, Vec<String>)>,
+    // Name of the built-in theme to start from.
+    pub theme_preset: Option<String>,
+    // Settings of the theme which are changed from the preset, e.g.
+    // ("header", "blue bold").
+    pub theme_settings: Vec<(String, String)>,
+}
+
+impl Config {
+    // The config file in the user's config directory, following the XDG base
+    // directory specification.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_dir.join("blame").join("config.toml"))
+    }
+
+    // Load the config file at the given path, or the default config file if
+    // there is one. Unlike the default file, a file which was asked for has
+    // to exist.
+    pub fn load(path: Option<&str>) -> Result<Config, String> {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => match Config::default_path().filter(|path| path.exists()) {
+                Some(path) => path,
+                None => return Ok(Config::default()),
+            },
+        };
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+        Config::parse(&contents)
+            .map_err(|e| format!("Failed to parse config file {}: {}", path.display(), e))
+    }
+
+    pub fn parse(contents: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+        // Actions by key, for reporting keys which are bound twice.
+        let mut bound: HashMap<String, Action> = HashMap::new();
+        let mut table = String::new();
+        for (i, line) in contents.lines().enumerate() {
+            let error = |message: String| format!("line {}: {}", i + 1, message);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                table = name.trim().to_string();
+                if table != "keys" && table != "theme" {
+                    return Err(error(format!("unknown table [{}]", table)));
+                }
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected key = value, got '{}'", line)))?;
+            let key = key.trim().trim_matches('"');
+            let values = parse_strings(value.trim()).map_err(error)?;
+            if table == "theme" {
+                let [value] = values.as_slice() else {
+                    return Err(error(format!("expected a single string for '{}'", key)));
+                };
+                // Settings are checked on a theme which is thrown away, and
+                // applied to the preset later, which may come after them.
+                if key == "preset" {
+                    Theme::preset(value).ok_or_else(|| error(unknown_preset(value)))?;
+                    config.theme_preset = Some(value.clone());
+                } else {
+                    Theme::default().set(key, value).map_err(error)?;
+                    config.theme_settings.push((key.to_string(), value.clone()));
+                }
+                continue;
+            }
+            if table != "keys" {
+                return Err(error(format!(
+                    "'{}' is outside of the [keys] and [theme] tables",
+                    key
+                )));
+            }
+            let action = key.parse::<Action>().map_err(|e| {
+                let suggestions = suggest::closest(key, Action::ALL.iter().map(|a| a.name()), 3);
+                error(format!("{}.{}", e, suggest::did_you_mean(&suggestions)))
+            })?;
+            for name in &values {
+                let (code, modifiers) = handler::parse_key(name)
+                    .ok_or_else(|| error(format!("unknown key '{}'", name)))?;
+                let label = handler::key_label(code, modifiers);
+                if let Some(other) = bound.insert(label, action).filter(|a| *a != action) {
+                    return Err(error(format!(
+                        "'{}' is bound to both {} and {}",
+                        name,
+                        other.name(),
+                        action.name()
+                    )));
+                }
+            }
+            config.key_bindings.push((action, values));
+        }
+        Ok(config)
+    }
+
+    // The default keys, with the keys of the actions in the config file
+    // changed.
+    pub fn key_map(&self) -> KeyMap {
+        let mut key_map = KeyMap::default();
+        for (action, names) in &self.key_bindings {
+            let keys: Vec<_> = names.iter().filter_map(|n| handler::parse_key(n)).collect();
+            key_map.bind(*action, &keys);
+        }
+        key_map
+    }
+
+    // The theme from the config file, starting from the given preset instead
+    // of the one in the file, if there is one. Without either, the preset
+    // matches the terminal's background.
+    pub fn theme(&self, preset: Option<&str>) -> Result<Theme, String> {
+        let name = preset
+            .or(self.theme_preset.as_deref())
+            .unwrap_or_else(|| Theme::default_preset());
+        let mut theme = Theme::preset(name).ok_or_else(|| unknown_preset(name))?;
+        for (name, value) in &self.theme_settings {
+            theme.set(name, value)?;
+        }
+        Ok(theme)
+    }
+}
+
+fn unknown_preset(name: &str) -> String {
+    format!(
+        "unknown theme '{}', expected one of: {}",
+        name,
+        theme::PRESETS.join(", ")
+    )
+}
+
+// Remove a comment from the end of a line, unless the "#" is in a string.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// Parse a value which is either a string or an array of strings, e.g. "J"
+// or ["K", "ctrl-p"].
+fn parse_strings(value: &str) -> Result<Vec<String>, String> {
+    let items = match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        Some(items) => items,
+        None => return Ok(vec![parse_string(value)?]),
+    };
+    let mut strings = vec![];
+    let mut rest = items.trim();
+    while !rest.is_empty() {
+        let end = string_end(rest).ok_or_else(|| format!("expected a string, got '{}'", rest))?;
+        strings.push(parse_string(&rest[..end])?);
+        rest = rest[end..].trim_start();
+        if !rest.is_empty() {
+            rest = rest
+                .strip_prefix(',')
+                .ok_or_else(|| format!("expected a comma, got '{}'", rest))?
+                .trim_start();
+        }
+    }
+    Ok(strings)
+}
+
+// Length of the quoted string at the start of some text, including its quotes.
+fn string_end(text: &str) -> Option<usize> {
+    if !text.starts_with('"') {
+        return None;
+    }
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Parse a quoted string, e.g. "ctrl-d" or "\"".
+fn parse_string(value: &str) -> Result<String, String> {
+    let inner = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .filter(|_| value.len() >= 2)
+        .ok_or_else(|| format!("expected a string in double quotes, got '{}'", value))?;
+    let mut string = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        string.push(match c {
+            '\\' => match chars.next() {
+                Some('\\') => '\\',
+                Some('"') => '"',
+                Some('t') => '\t',
+                other => return Err(format!("unsupported escape '\\{}'", other.unwrap_or(' '))),
+            },
+            c => c,
+        });
+    }
+    Ok(string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keys_and_theme() {
+        let config = Config::parse(
+            "# Keys\n\
+             [keys]\n\
+             next_block = \"J\" # comment\n\
+             previous_block = [\"K\", \"ctrl-p\"]\n\
+             \n\
+             [theme]\n\
+             preset = \"solarized\"\n\
+             author = \"magenta bold\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.key_bindings,
+            vec![
+                (Action::NextBlock, vec!["J".to_string()]),
+                (
+                    Action::PreviousBlock,
+                    vec!["K".to_string(), "ctrl-p".to_string()]
+                ),
+            ]
+        );
+        let key_map = config.key_map();
+        assert_eq!(key_map.keys(Action::NextBlock), ["J"]);
+        assert_eq!(key_map.keys(Action::PreviousBlock), ["K", "ctrl-p"]);
+        assert_eq!(config.theme_preset.as_deref(), Some("solarized"));
+        assert_eq!(
+            config.theme_settings,
+            vec![("author".to_string(), "magenta bold".to_string())]
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        let error = |contents: &str| Config::parse(contents).unwrap_err();
+        assert_eq!(error("[colors]"), "line 1: unknown table [colors]");
+        assert_eq!(
+            error("[keys]\nnext_block"),
+            "line 2: expected key = value, got 'next_block'"
+        );
+        assert!(error("next_block = \"J\"").starts_with("line 1: 'next_block' is outside"));
+        assert!(error("[keys]\nnext_block = \"J\"\nprevious_block = \"J\"")
+            .starts_with("line 3: 'J' is bound to both"));
+        assert!(
+            error("[keys]\nnext_blok = \"J\"").starts_with("line 2: unknown action 'next_blok'")
+        );
+        assert_eq!(
+            error("[keys]\nnext_block = \"hyper-j\""),
+            "line 2: unknown key 'hyper-j'"
+        );
+        assert!(error("[theme]\npreset = \"nope\"").starts_with("line 2: unknown theme 'nope'"));
+    }
+
+    #[test]
+    fn strips_comments_outside_of_strings() {
+        assert_eq!(strip_comment("a = \"#\" # comment"), "a = \"#\" ");
+        assert_eq!(strip_comment("a = \"\\\"#\""), "a = \"\\\"#\"");
+        assert_eq!(strip_comment("# comment"), "");
+    }
+
+    #[test]
+    fn parses_strings_and_arrays() {
+        assert_eq!(parse_strings("\"J\""), Ok(vec!["J".to_string()]));
+        assert_eq!(
+            parse_strings("[ \"a\" , \"b,c\" ]"),
+            Ok(vec!["a".to_string(), "b,c".to_string()])
+        );
+        assert_eq!(parse_strings("[]"), Ok(vec![]));
+        assert_eq!(parse_string("\"\\\"\\\\\\t\""), Ok("\"\\\t".to_string()));
+        assert!(parse_strings("[\"a\" \"b\"]").is_err());
+        assert!(parse_string("\"").is_err());
+        assert!(parse_string("\"\\n\"").is_err());
+        assert!(parse_string("J").is_err());
+    }
+}
@@ -0,0 +1,197 @@
+use crate::encoding::Encoding;
+use crate::file_blame::FileBlameError;
+use crate::line_map;
+use crate::metrics;
+use std::process::Command;
+
+// The patch of a commit, as shown by `git show`, in a pane next to the blame
+// which is scrolled independently of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffPane {
+    pub commit_sha: String,
+    pub lines: Vec<String>,
+    // Number of lines scrolled down.
+    pub scroll: usize,
+}
+
+// How a line of a patch is colored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffLineKind {
+    // Commit header and file headers, e.g. "diff --git a/x b/x".
+    Header,
+    // Hunk headers, e.g. "@@ -1,2 +1,3 @@".
+    Hunk,
+    Added,
+    Removed,
+    Context,
+}
+
+impl DiffPane {
+    // Load the patch of a commit in the repository with the given root.
+    pub fn load(git_root_dir: &str, commit_sha: &str) -> Result<DiffPane, FileBlameError> {
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("show")
+                .arg("--no-color")
+                .arg(commit_sha),
+        )
+        .map_err(|e| FileBlameError::Unknown(e.to_string()))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(FileBlameError::Unknown(stderr));
+        }
+
+        let mut lines = vec![];
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            // Git treats files in UTF-16 as binary, so the patches of files
+            // with a working tree encoding are shown decoded instead.
+            let patch = binary_file_path(line).and_then(|path| {
+                let encoding = Encoding::for_path(git_root_dir, path)?;
+                decoded_patch(git_root_dir, commit_sha, path, encoding)
+            });
+            match patch {
+                Some(patch) => lines.extend(patch),
+                None => lines.push(line.to_string()),
+            }
+        }
+
+        Ok(DiffPane {
+            commit_sha: commit_sha.to_string(),
+            lines: lines.iter().map(|l| l.replace('\t', "    ")).collect(),
+            scroll: 0,
+        })
+    }
+
+    // Scroll up or down by a number of lines, stopping at the last line.
+    pub fn scroll_by(&mut self, delta: isize) {
+        let max = self.lines.len().saturating_sub(1) as isize;
+        self.scroll = (self.scroll as isize + delta).clamp(0, max) as usize;
+    }
+
+    // Scroll to where a line of the old version of a file was removed, with
+    // a few lines of context above it, but not above its hunk header. The
+    // path is relative to the root of the repository.
+    pub fn scroll_to_removed_line(&mut self, path: &str, line: usize) {
+        let file_header = format!("--- a/{}", path);
+        let Some(start) = self.lines.iter().position(|l| *l == file_header) else {
+            return;
+        };
+        let mut hunk_start = start;
+        let mut old_line = 0;
+        for (i, l) in self.lines.iter().enumerate().skip(start + 1) {
+            if l.starts_with("diff ") {
+                return;
+            } else if let Some(old_start) = line_map::hunk_old_start(l) {
+                hunk_start = i;
+                old_line = old_start;
+            } else if l.starts_with('-') || l.starts_with(' ') {
+                if l.starts_with('-') && old_line == line {
+                    self.scroll = i.saturating_sub(3).max(hunk_start);
+                    return;
+                }
+                old_line += 1;
+            }
+        }
+    }
+}
+
+// The path of the file in a line like "Binary files a/x and b/x differ", or
+// "/dev/null and b/x" for added files.
+fn binary_file_path(line: &str) -> Option<&str> {
+    let files = line
+        .strip_prefix("Binary files ")?
+        .strip_suffix(" differ")?;
+    let (old, new) = files.split_once(" and ")?;
+    new.strip_prefix("b/").or_else(|| old.strip_prefix("a/"))
+}
+
+// The patch of a file in a commit from the file headers on, with its lines
+// decoded from the file's working tree encoding.
+fn decoded_patch(
+    git_root_dir: &str,
+    commit_sha: &str,
+    path: &str,
+    encoding: Encoding,
+) -> Option<Vec<String>> {
+    let output = metrics::output(Command::new("git").current_dir(git_root_dir).args([
+        "show",
+        "--no-color",
+        "--text",
+        "--format=",
+        commit_sha,
+        "--",
+        path,
+    ]))
+    .ok()?;
+    let mut lines = vec![];
+    let mut in_hunk = false;
+    // The "diff --git" and "index" lines are already shown.
+    for line in output.stdout.split(|&b| b == b'\n').skip(2) {
+        in_hunk |= line.starts_with(b"@@");
+        match line {
+            [prefix @ (b'+' | b'-' | b' '), rest @ ..] if in_hunk => {
+                lines.push(format!("{}{}", *prefix as char, encoding.decode_line(rest)))
+            }
+            _ => lines.push(String::from_utf8_lossy(line).to_string()),
+        }
+    }
+    Some(lines)
+}
+
+// Tell the kind of a line of a patch from how it starts. The lines of the
+// file headers start like removed and added lines, so they're checked first.
+pub fn line_kind(line: &str) -> DiffLineKind {
+    if line.starts_with("+++ ")
+        || line.starts_with("--- ")
+        || line.starts_with("diff ")
+        || line.starts_with("index ")
+        || line.starts_with("commit ")
+    {
+        DiffLineKind::Header
+    } else if line.starts_with("@@") {
+        DiffLineKind::Hunk
+    } else if line.starts_with('+') {
+        DiffLineKind::Added
+    } else if line.starts_with('-') {
+        DiffLineKind::Removed
+    } else {
+        DiffLineKind::Context
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrolls_to_removed_line() {
+        let patch = "commit 91d2a43\n\
+                     diff --git a/f.txt b/f.txt\n\
+                     --- a/f.txt\n\
+                     +++ b/f.txt\n\
+                     @@ -1,6 +1,6 @@\n \
+                     line 1\n\
+                     -line 2\n\
+                     +bob two\n \
+                     line 3\n \
+                     line 4\n\
+                     -line 5\n\
+                     +bob five\n \
+                     line 6";
+        let mut diff = DiffPane {
+            commit_sha: "91d2a43".to_string(),
+            lines: patch.lines().map(str::to_string).collect(),
+            scroll: 0,
+        };
+        diff.scroll_to_removed_line("f.txt", 2);
+        assert_eq!(diff.scroll, 4);
+        diff.scroll_to_removed_line("f.txt", 5);
+        assert_eq!(diff.scroll, 7);
+        // Lines which weren't removed, and other files, don't scroll.
+        diff.scroll_to_removed_line("f.txt", 3);
+        assert_eq!(diff.scroll, 7);
+        diff.scroll_to_removed_line("g.txt", 2);
+        assert_eq!(diff.scroll, 7);
+    }
+}
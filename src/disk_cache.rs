@@ -0,0 +1,141 @@
+use crate::file_blame::{self, Commit, FileBlame, FileBlameError, ParseOptions};
+use crate::metrics;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+
+// Cache of git blame output and commit information on disk, in the
+// repository's git directory. The blame of a file at a commit never changes,
+// so the cache doesn't need to be invalidated. It's filled in ahead of time
+// by `blame prime`, and read when blaming files at commits.
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    // The cache of the repository with the given root, whether or not it
+    // was primed yet.
+    pub fn open(git_root_dir: &str) -> Option<DiskCache> {
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("rev-parse")
+                .arg("--path-format=absolute")
+                .arg("--git-path")
+                .arg("blame-cache"),
+        )
+        .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Some(DiskCache {
+            dir: PathBuf::from(dir),
+        })
+    }
+
+    // Whether the cache was primed, i.e. anything was stored in it.
+    pub fn exists(&self) -> bool {
+        self.dir.is_dir()
+    }
+
+    // Resolve a ref to the full hash of its commit, which the cache is keyed by.
+    pub fn resolve(git_root_dir: &str, rev: &str) -> Option<String> {
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("rev-parse")
+                .arg("--verify")
+                .arg("--quiet")
+                .arg(format!("{}^{{commit}}", rev)),
+        )
+        .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn blame_path(&self, commit_sha: &str, relative_path: &str) -> PathBuf {
+        self.dir
+            .join("line-porcelain")
+            .join(commit_sha)
+            .join(relative_path)
+    }
+
+    // Commits were stored with the parent a blame mentioned for one file
+    // before, so they're stored elsewhere now to not read them.
+    fn commit_path(&self, sha: &str) -> PathBuf {
+        self.dir.join("commits-v2").join(format!("{}.json", sha))
+    }
+
+    // The `git blame --line-porcelain` output of a file at a commit, given by
+    // its full hash.
+    pub fn blame(&self, commit_sha: &str, relative_path: &str) -> Option<String> {
+        fs::read_to_string(self.blame_path(commit_sha, relative_path)).ok()
+    }
+
+    pub fn store_blame(
+        &self,
+        commit_sha: &str,
+        relative_path: &str,
+        output: &str,
+    ) -> io::Result<()> {
+        let path = self.blame_path(commit_sha, relative_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, output)
+    }
+
+    // Information about a commit, keyed by its full hash.
+    pub fn commit(&self, sha: &str) -> Option<Commit> {
+        let json = fs::read_to_string(self.commit_path(sha)).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    pub fn store_commit(&self, sha: &str, commit: &Commit) -> io::Result<()> {
+        let path = self.commit_path(sha);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(commit)?)
+    }
+}
+
+// Store the blames of a file, or of all files in a directory, at a ref in
+// the disk cache, along with the commits they refer to. Returns the number
+// of files whose blames were stored.
+pub fn prime(path: &str, gitref: &str) -> Result<usize, FileBlameError> {
+    let (git_root_dir, relative_path) = FileBlame::try_repo_paths(path)?;
+    let cache = DiskCache::open(&git_root_dir).ok_or(FileBlameError::NotGit)?;
+    let commit_sha = DiskCache::resolve(&git_root_dir, gitref)
+        .ok_or_else(|| FileBlameError::Unknown(format!("unknown ref '{}'", gitref)))?;
+
+    // List the files at the commit, so that directories can be primed too.
+    let files = FileBlame::files_at_commit(&git_root_dir, &commit_sha, &relative_path)?;
+
+    let mut stored_commits = HashSet::new();
+    for file in &files {
+        if cache.blame(&commit_sha, file).is_some() {
+            continue;
+        }
+        let blame =
+            FileBlame::blame_output(&git_root_dir, &commit_sha, file, &ParseOptions::default())?;
+        cache
+            .store_blame(&commit_sha, file, &blame)
+            .map_err(|e| FileBlameError::Unknown(e.to_string()))?;
+
+        for commit in file_blame::porcelain_commits(&git_root_dir, &blame) {
+            if stored_commits.insert(commit.sha.clone()) && cache.commit(&commit.sha).is_none() {
+                cache
+                    .store_commit(&commit.sha, &commit)
+                    .map_err(|e| FileBlameError::Unknown(e.to_string()))?;
+            }
+        }
+    }
+    Ok(files.len())
+}
@@ -0,0 +1,103 @@
+use crate::metrics;
+use std::process::Command;
+
+// Encoding of a file in the working tree, set with the working-tree-encoding
+// attribute in .gitattributes, e.g. for UTF-16 sources of Windows projects.
+// Git converts such files to UTF-8 when they're committed, but versions
+// committed before the attribute was set are stored as they were, and are
+// decoded here for showing them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    // UTF-16 with the byte order given by a byte order mark, or little
+    // endian without one, like on Windows.
+    Utf16,
+    Utf16Le,
+    Utf16Be,
+    // ISO-8859-1, where each byte is the character with the same code point.
+    Latin1,
+}
+
+impl Encoding {
+    // The working tree encoding of a file in the repository with the given
+    // root, if it's set to one which can be decoded.
+    pub fn for_path(git_root_dir: &str, relative_path: &str) -> Option<Encoding> {
+        // The output looks like "<path>: working-tree-encoding: UTF-16LE-BOM".
+        let output = metrics::output(Command::new("git").current_dir(git_root_dir).args([
+            "check-attr",
+            "working-tree-encoding",
+            "--",
+            relative_path,
+        ]))
+        .ok()?;
+        let output = String::from_utf8_lossy(&output.stdout);
+        let (_, value) = output.trim().rsplit_once(": ")?;
+        Encoding::from_name(value)
+    }
+
+    fn from_name(name: &str) -> Option<Encoding> {
+        match name.to_ascii_uppercase().as_str() {
+            "UTF-16" | "UTF16" => Some(Encoding::Utf16),
+            "UTF-16LE" | "UTF-16LE-BOM" | "UTF16LE" => Some(Encoding::Utf16Le),
+            "UTF-16BE" | "UTF-16BE-BOM" | "UTF16BE" => Some(Encoding::Utf16Be),
+            "ISO-8859-1" | "LATIN1" | "LATIN-1" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    // Whether the contents of a file are stored in the encoding rather than
+    // in UTF-8. UTF-16 text has NUL bytes even when it's valid UTF-8, e.g.
+    // for ASCII characters.
+    pub fn is_encoded(bytes: &[u8]) -> bool {
+        bytes.contains(&0) || std::str::from_utf8(bytes).is_err()
+    }
+
+    // Decode the contents of a whole file, without its byte order mark.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf16 => match bytes {
+                [0xfe, 0xff, rest @ ..] => decode_utf16(rest, false),
+                [0xff, 0xfe, rest @ ..] => decode_utf16(rest, true),
+                _ => decode_utf16(bytes, true),
+            },
+            Encoding::Utf16Le => {
+                decode_utf16(bytes.strip_prefix(&[0xff, 0xfe]).unwrap_or(bytes), true)
+            }
+            Encoding::Utf16Be => {
+                decode_utf16(bytes.strip_prefix(&[0xfe, 0xff]).unwrap_or(bytes), false)
+            }
+            Encoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+        }
+    }
+
+    // Decode a single line of a file, as split by git at newline bytes. In
+    // UTF-16, the newline is two bytes, and git splits it in the middle, so
+    // that the other byte is left at the start of the next line in little
+    // endian, or at the end of the line in big endian, and is dropped.
+    pub fn decode_line(self, bytes: &[u8]) -> String {
+        let little_endian = match self {
+            Encoding::Latin1 => return self.decode(bytes),
+            Encoding::Utf16 | Encoding::Utf16Le => true,
+            Encoding::Utf16Be => false,
+        };
+        let bytes = match bytes {
+            [0, rest @ ..] if bytes.len() % 2 == 1 && little_endian => rest,
+            [rest @ .., 0] if bytes.len() % 2 == 1 && !little_endian => rest,
+            _ => bytes,
+        };
+        self.decode(bytes)
+    }
+}
+
+fn decode_utf16(bytes: &[u8], little_endian: bool) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| {
+            if little_endian {
+                u16::from_le_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_be_bytes([pair[0], pair[1]])
+            }
+        })
+        .collect();
+    String::from_utf16_lossy(&units)
+}
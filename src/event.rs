@@ -1,11 +1,17 @@
 use crate::app::AppResult;
+use crate::file_blame::Progress;
 use crossterm::event::{self, Event as CrosstermEvent, KeyEvent, MouseEvent};
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
 use std::time::{Duration, Instant};
 
+/// Longest time the event thread waits for terminal events at a time, so
+/// that pausing takes effect quickly.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
 /// Terminal events.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub enum Event {
     /// Terminal tick.
     Tick,
@@ -15,6 +21,11 @@ pub enum Event {
     Mouse(MouseEvent),
     /// Terminal resize.
     Resize(u16, u16),
+    /// Text pasted into the terminal with bracketed paste.
+    Paste(String),
+    /// Progress of loading the blame in the background, or `None` once it's
+    /// done.
+    Progress(Option<Progress>),
 }
 
 /// Terminal event handler.
@@ -27,6 +38,8 @@ pub struct EventHandler {
     receiver: mpsc::Receiver<Event>,
     /// Event handler thread.
     handler: thread::JoinHandle<()>,
+    /// Whether reading terminal events is paused.
+    paused: Arc<AtomicBool>,
 }
 
 impl EventHandler {
@@ -34,14 +47,24 @@ impl EventHandler {
     pub fn new(tick_rate: u64) -> Self {
         let tick_rate = Duration::from_millis(tick_rate);
         let (sender, receiver) = mpsc::channel();
+        let paused = Arc::new(AtomicBool::new(false));
         let handler = {
             let sender = sender.clone();
+            let paused = paused.clone();
             thread::spawn(move || {
                 let mut last_tick = Instant::now();
                 loop {
+                    // While paused, the terminal's input is left to the
+                    // external process using the terminal.
+                    if paused.load(Ordering::SeqCst) {
+                        thread::sleep(POLL_INTERVAL);
+                        continue;
+                    }
+
                     let timeout = tick_rate
                         .checked_sub(last_tick.elapsed())
-                        .unwrap_or(tick_rate);
+                        .unwrap_or(tick_rate)
+                        .min(POLL_INTERVAL);
 
                     if event::poll(timeout).expect("no events available") {
                         match event::read().expect("unable to read event") {
@@ -50,7 +73,7 @@ impl EventHandler {
                             CrosstermEvent::Resize(w, h) => sender.send(Event::Resize(w, h)),
                             CrosstermEvent::FocusGained => Ok(()),
                             CrosstermEvent::FocusLost => Ok(()),
-                            CrosstermEvent::Paste(_) => unimplemented!(),
+                            CrosstermEvent::Paste(text) => sender.send(Event::Paste(text)),
                         }
                         .expect("failed to send terminal event")
                     }
@@ -66,9 +89,22 @@ impl EventHandler {
             sender,
             receiver,
             handler,
+            paused,
         }
     }
 
+    /// Pauses or resumes reading terminal events, e.g. while an external
+    /// process uses the terminal, so that its input isn't taken away from it.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    /// Returns a sender for sending events from other threads, e.g. a tick
+    /// for waking up the main loop when background work is done.
+    pub fn sender(&self) -> mpsc::Sender<Event> {
+        self.sender.clone()
+    }
+
     /// Receive the next event from the handler thread.
     ///
     /// This function will always block the current thread if
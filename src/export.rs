@@ -0,0 +1,541 @@
+use crate::action::Action;
+use crate::age;
+use crate::file_blame::{BlameLine, Commit, FileBlame};
+use crate::handler::KeyMap;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+// Export a range of blame lines (given as indices into the blame lines) as
+// markdown: a fenced code block with the lines, followed by a table of the
+// commits which last changed them. The result is meant to be pasted into an
+// issue or a postmortem document.
+pub fn markdown(
+    file_blame: &FileBlame,
+    commit_cache: &HashMap<String, Commit>,
+    range: RangeInclusive<usize>,
+) -> String {
+    let lines: Vec<&BlameLine> = file_blame
+        .blame_lines
+        .iter()
+        .skip(*range.start())
+        .take(range.end() + 1 - range.start())
+        .collect();
+
+    let first_line = lines.first().map(|l| l.line_number.as_str()).unwrap_or("");
+    let last_line = lines.last().map(|l| l.line_number.as_str()).unwrap_or("");
+    let language = Path::new(&file_blame.filepath)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("");
+
+    let mut out = format!(
+        "`{}` lines {}-{} at `{}`\n\n```{}\n",
+        file_blame.filepath, first_line, last_line, file_blame.commit_sha, language
+    );
+    for line in &lines {
+        out.push_str(&line.plain_contents);
+        out.push('\n');
+    }
+    out.push_str("```\n\n");
+
+    // Group the line numbers by commit, keeping the commits in the order in
+    // which they first appear in the range.
+    let mut commits: Vec<(&str, Vec<&str>)> = vec![];
+    for line in &lines {
+        match commits.iter_mut().find(|(sha, _)| *sha == line.commit_sha) {
+            Some((_, line_numbers)) => line_numbers.push(&line.line_number),
+            None => commits.push((&line.commit_sha, vec![&line.line_number])),
+        }
+    }
+
+    out.push_str("| Commit | Author | Date | Lines | Message |\n");
+    out.push_str("| --- | --- | --- | --- | --- |\n");
+    for (sha, line_numbers) in commits {
+        let commit = commit_cache.get(sha).cloned().unwrap_or_default();
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} |\n",
+            sha,
+            escape_table_cell(&commit.author),
+            commit.timestamp,
+            line_ranges(&line_numbers),
+            escape_table_cell(&commit.commit_message),
+        ));
+    }
+
+    out
+}
+
+// Separated-values formats for exporting one row per blame line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delimited {
+    Tsv,
+    Csv,
+}
+
+// Export all blame lines as tab- or comma-separated values, with a header row
+// and one row per line. This is meant for analyzing blame data in
+// spreadsheets or data analysis tools.
+pub fn delimited(
+    file_blame: &FileBlame,
+    commit_cache: &HashMap<String, Commit>,
+    format: Delimited,
+    colored: bool,
+) -> String {
+    let separator = match format {
+        Delimited::Tsv => "\t",
+        Delimited::Csv => ",",
+    };
+    let escape = |field: &str| match format {
+        Delimited::Tsv => escape_tsv_field(field),
+        Delimited::Csv => escape_csv_field(field),
+    };
+
+    let mut out = ["sha", "author", "email", "timestamp", "line", "content"].join(separator);
+    out.push('\n');
+    for line in &file_blame.blame_lines {
+        let commit = commit_cache
+            .get(&line.commit_sha)
+            .cloned()
+            .unwrap_or_default();
+        // Syntax highlighted contents are reset at the end, so that the
+        // colors don't leak into the next line.
+        let contents = if colored {
+            format!("{}\x1b[0m", line.contents)
+        } else {
+            line.plain_contents.clone()
+        };
+        let fields = [
+            line.commit_sha.as_str(),
+            commit.author.as_str(),
+            commit.author_email.as_str(),
+            commit.timestamp.as_str(),
+            line.line_number.as_str(),
+            contents.as_str(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|f| escape(f)).collect();
+        out.push_str(&fields.join(separator));
+        out.push('\n');
+    }
+    out
+}
+
+// TSV fields can't contain tabs or newlines, so escape them the same way
+// as e.g. PostgreSQL's text format does.
+fn escape_tsv_field(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+// CSV fields containing separators, quotes or newlines are wrapped in quotes,
+// with quotes doubled, as described in RFC 4180.
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Ownership of a contiguous range of lines which were last changed by the
+// same commit.
+#[derive(Serialize, Debug)]
+pub struct OwnershipRange {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub commit: String,
+    pub author: String,
+    pub email: String,
+    pub timestamp: String,
+    pub summary: String,
+}
+
+// Machine-readable report of who owns which lines of a file, e.g. for CI
+// bots which auto-assign reviewers for changed regions.
+#[derive(Serialize, Debug)]
+pub struct OwnershipReport {
+    pub path: String,
+    #[serde(rename = "ref")]
+    pub gitref: String,
+    pub ranges: Vec<OwnershipRange>,
+}
+
+pub fn ownership_report(
+    file_blame: &FileBlame,
+    commit_cache: &HashMap<String, Commit>,
+) -> OwnershipReport {
+    let line_number = |i: usize| {
+        file_blame.blame_lines[i]
+            .line_number
+            .parse()
+            .unwrap_or_default()
+    };
+
+    let ranges = file_blame
+        .blocks()
+        .into_iter()
+        .map(|block| {
+            let sha = &file_blame.blame_lines[*block.start()].commit_sha;
+            let commit = commit_cache.get(sha).cloned().unwrap_or_default();
+            OwnershipRange {
+                start_line: line_number(*block.start()),
+                end_line: line_number(*block.end()),
+                commit: sha.clone(),
+                author: commit.author,
+                email: commit.author_email,
+                timestamp: commit.timestamp,
+                summary: commit.commit_message,
+            }
+        })
+        .collect();
+
+    OwnershipReport {
+        path: file_blame.filepath.clone(),
+        gitref: file_blame.commit_sha.clone(),
+        ranges,
+    }
+}
+
+// Identifier of the rule which the results of the SARIF ownership report
+// belong to.
+const SARIF_OWNERSHIP_RULE: &str = "ownership";
+
+// The ownership report in the SARIF 2.1.0 format, which code scanning tools
+// understand, with each range as a result. The path is given relative to the
+// root of the repository. The author, commit and date are in the properties
+// of each result.
+pub fn ownership_sarif(report: &OwnershipReport) -> Value {
+    let uri = FileBlame::repo_paths(&report.path)
+        .map_or_else(|| report.path.clone(), |(_, relative_path)| relative_path);
+    let results: Vec<Value> = report
+        .ranges
+        .iter()
+        .map(|range| {
+            json!({
+                "ruleId": SARIF_OWNERSHIP_RULE,
+                "level": "note",
+                "message": {
+                    "text": format!(
+                        "Last changed by {} <{}> in {}: {}",
+                        range.author,
+                        range.email,
+                        &range.commit[..range.commit.len().min(8)],
+                        range.summary
+                    ),
+                },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": {
+                            "uri": encode_uri_path(&uri),
+                            "uriBaseId": "%SRCROOT%",
+                        },
+                        "region": {
+                            "startLine": range.start_line,
+                            "endLine": range.end_line,
+                        },
+                    },
+                }],
+                "properties": {
+                    "author": range.author,
+                    "email": range.email,
+                    "commit": range.commit,
+                    "date": range.timestamp,
+                    "summary": range.summary,
+                },
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "blame",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [{
+                        "id": SARIF_OWNERSHIP_RULE,
+                        "shortDescription": {
+                            "text": "Commit and author which last changed a range of lines",
+                        },
+                    }],
+                },
+            },
+            "properties": {
+                "ref": report.gitref,
+            },
+            "results": results,
+        }],
+    })
+}
+
+// Percent-encode the characters of a path which can't be in a URI, keeping
+// the slashes between its components.
+fn encode_uri_path(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+// Compact map of each line of a file to the commit which last changed it,
+// meant for editor plugins which show the blame in the gutter.
+#[derive(Serialize, Debug)]
+pub struct AnnotationSidecar {
+    pub path: String,
+    #[serde(rename = "ref")]
+    pub gitref: String,
+    // Keyed by line number.
+    pub lines: BTreeMap<usize, LineAnnotation>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LineAnnotation {
+    pub sha: String,
+    pub author: String,
+    // How recently the line was changed: "day", "week", "month", "year" or
+    // "older".
+    pub age: &'static str,
+}
+
+pub fn annotation_sidecar(
+    file_blame: &FileBlame,
+    commit_cache: &HashMap<String, Commit>,
+    now: i64,
+) -> AnnotationSidecar {
+    let lines = file_blame
+        .blame_lines
+        .iter()
+        .filter_map(|line| {
+            let commit = commit_cache.get(&line.commit_sha)?;
+            let annotation = LineAnnotation {
+                sha: line.commit_sha.clone(),
+                author: commit.author.clone(),
+                age: age::bucket(now - commit.time),
+            };
+            Some((line.line_number.parse().ok()?, annotation))
+        })
+        .collect();
+
+    AnnotationSidecar {
+        path: file_blame.filepath.clone(),
+        gitref: file_blame.commit_sha.clone(),
+        lines,
+    }
+}
+
+// Collapse consecutive line numbers into ranges, e.g. "10-12, 15".
+fn line_ranges(line_numbers: &[&str]) -> String {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for n in line_numbers.iter().filter_map(|n| n.parse::<usize>().ok()) {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == n => *end = n,
+            _ => ranges.push((n, n)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// The keys of an action, e.g. for cheatsheets or which-key style helpers.
+#[derive(Serialize, Debug)]
+pub struct KeyBinding {
+    pub action: &'static str,
+    // Names of the keys as the config file accepts them, empty if the action
+    // isn't bound to any.
+    pub keys: Vec<String>,
+    pub description: &'static str,
+}
+
+// The keys of all actions, in the order in which they're listed to users.
+pub fn key_bindings(key_map: &KeyMap) -> Vec<KeyBinding> {
+    Action::ALL
+        .iter()
+        .map(|action| KeyBinding {
+            action: action.name(),
+            keys: key_map.keys(*action),
+            description: action.description(),
+        })
+        .collect()
+}
+
+// The keys of all actions as a markdown table.
+pub fn key_bindings_markdown(key_map: &KeyMap) -> String {
+    let mut out = String::from("| Keys | Action | Description |\n| --- | --- | --- |\n");
+    for binding in key_bindings(key_map) {
+        let keys: Vec<String> = binding
+            .keys
+            .iter()
+            .map(|key| format!("`{}`", escape_table_cell(key)))
+            .collect();
+        out.push_str(&format!(
+            "| {} | {} | {} |\n",
+            keys.join(", "),
+            binding.action,
+            escape_table_cell(binding.description)
+        ));
+    }
+    out
+}
+
+// Escape characters which would break a markdown table cell.
+fn escape_table_cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collapses_line_ranges() {
+        assert_eq!(
+            line_ranges(&["10", "11", "12", "15", "17", "18"]),
+            "10-12, 15, 17-18"
+        );
+        assert_eq!(line_ranges(&[]), "");
+    }
+
+    #[test]
+    fn escapes_table_cells() {
+        assert_eq!(escape_table_cell("a | b"), "a \\| b");
+    }
+
+    #[test]
+    fn escapes_tsv_fields() {
+        assert_eq!(escape_tsv_field("plain"), "plain");
+        assert_eq!(escape_tsv_field("a\tb\nc\rd\\e"), "a\\tb\\nc\\rd\\\\e");
+    }
+
+    #[test]
+    fn escapes_csv_fields() {
+        assert_eq!(escape_csv_field("plain"), "plain");
+        assert_eq!(escape_csv_field("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_csv_field("a\nb"), "\"a\nb\"");
+    }
+
+    // A blame of lines numbered from 1, each last changed by the given commit.
+    fn blame(shas: &[&str]) -> FileBlame {
+        FileBlame {
+            blame_lines: shas
+                .iter()
+                .enumerate()
+                .map(|(i, sha)| BlameLine {
+                    commit_sha: sha.to_string(),
+                    contents: String::new(),
+                    plain_contents: String::new(),
+                    line_number: (i + 1).to_string(),
+                    truncated: false,
+                    previous_sha: None,
+                    previous_path: None,
+                    original_path: None,
+                    original_line: i + 1,
+                    skipped_commit: None,
+                })
+                .collect(),
+            filepath: "src/main.rs".to_string(),
+            commit_sha: "HEAD".to_string(),
+            highlighted: false,
+            lfs_pointer: None,
+        }
+    }
+
+    #[test]
+    fn reports_ranges_of_lines_by_commit() {
+        let commit = Commit {
+            sha: "a".to_string(),
+            author: "Jane Doe".to_string(),
+            author_email: "jane@example.com".to_string(),
+            commit_message: "Add main".to_string(),
+            ..Commit::default()
+        };
+        let commit_cache = HashMap::from([("a".to_string(), commit)]);
+        let report = ownership_report(&blame(&["a", "a", "b", "a"]), &commit_cache);
+        assert_eq!(report.path, "src/main.rs");
+        assert_eq!(report.gitref, "HEAD");
+        let ranges: Vec<_> = report
+            .ranges
+            .iter()
+            .map(|r| {
+                (
+                    r.start_line,
+                    r.end_line,
+                    r.commit.as_str(),
+                    r.email.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                (1, 2, "a", "jane@example.com"),
+                (3, 3, "b", ""),
+                (4, 4, "a", "jane@example.com")
+            ]
+        );
+    }
+
+    #[test]
+    fn encodes_uri_paths() {
+        assert_eq!(encode_uri_path("src/main.rs"), "src/main.rs");
+        assert_eq!(encode_uri_path("docs/a b#c.md"), "docs/a%20b%23c.md");
+        assert_eq!(encode_uri_path("ü"), "%C3%BC");
+    }
+
+    #[test]
+    fn reports_ownership_as_sarif() {
+        let report = OwnershipReport {
+            path: "/nonexistent/a b.rs".to_string(),
+            gitref: "HEAD".to_string(),
+            ranges: vec![OwnershipRange {
+                start_line: 3,
+                end_line: 5,
+                commit: "1e1d1c3c8b7d6f5e4a3b2c1d0e9f8a7b6c5d4e3f".to_string(),
+                author: "Jane Doe".to_string(),
+                email: "jane@example.com".to_string(),
+                timestamp: "2024-01-02 03:04:05 +0000".to_string(),
+                summary: "Fix the thing".to_string(),
+            }],
+        };
+        let sarif = ownership_sarif(&report);
+        assert_eq!(sarif["version"], "2.1.0");
+        assert_eq!(sarif["runs"][0]["tool"]["driver"]["name"], "blame");
+
+        let result = &sarif["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "ownership");
+        assert_eq!(result["level"], "note");
+        assert_eq!(
+            result["message"]["text"],
+            "Last changed by Jane Doe <jane@example.com> in 1e1d1c3c: Fix the thing"
+        );
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "/nonexistent/a%20b.rs");
+        assert_eq!(location["region"]["startLine"], 3);
+        assert_eq!(location["region"]["endLine"], 5);
+        assert_eq!(result["properties"]["author"], "Jane Doe");
+        assert_eq!(result["properties"]["commit"], report.ranges[0].commit);
+        assert_eq!(result["properties"]["date"], "2024-01-02 03:04:05 +0000");
+    }
+}
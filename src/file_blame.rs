@@ -1,58 +1,596 @@
-use regex::Regex;
-use std::collections::HashMap;
+use crate::age;
+use crate::disk_cache::DiskCache;
+use crate::encoding::Encoding;
+use crate::lfs::LfsPointer;
+use crate::metrics;
+use crate::partial_clone::{self, Promisor};
+use crate::suggest;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
 use std::error::Error;
 use std::fmt::Display;
-use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::mem;
+use std::ops::RangeInclusive;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
 
 use syntect::easy::HighlightLines;
 use syntect::highlighting::ThemeSet;
-use syntect::parsing::SyntaxSet;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
 use syntect::util::as_24_bit_terminal_escaped;
 
-// Regex for parsing a line of git blame output.
-// A line of git blame output looks like this:
+// Pseudo commit for blaming the file as it is in the working tree, including
+// uncommitted changes.
+pub const WORKTREE: &str = "WORKTREE";
+
+// A line of `git blame --line-porcelain` output, i.e. a line of the file
+// with the information about the commit which last changed it. The output
+// has a header for each line, which looks like this:
 //
-// 1e1d1c3c (John Doe 2019-01-01 12:00:00 -0400 142) This is the code
+// 1e1d1c3c8b7d6f5e4a3b2c1d0e9f8a7b6c5d4e3f 140 142 1
+// author John Doe
+// author-mail <john@example.com>
+// author-time 1546358400
+// author-tz -0400
+// summary Fix the thing
+// previous 9f8e7d6c5b4a3f2e1d0c9b8a7f6e5d4c3b2a1f0e src/main.rs
+// filename src/main.rs
+// 	This is the code
 //
-// ^^^^^^^^  ^^^^^^^^ ^^^^^^^^^^^^^^^^^^^^^^^^^ ^^^  ^^^^^^^^^^^^^^^^
-//    |          |         |                     |     |
-//    |          |         |                     |     +-- file content
-//    |          |         |                     |
-//    |          |         +-- timestamp         +-- line number
-//    |          |
-//    |          +-- author name
-//    |
-//    +-- commit hash
-const BLAME_LINE_REGEX: &str = r"(?x)
-^
-  (\^?[0-9a-f]{4,40})     # commit sha
-  (?: [^(]+)?             # optional file name
-  [\ ]
-  \(                      # open (
-  ([^\ ].*[^\ ])          # author name
-  [\ ]+
-  (
-    \d{4}-\d{2}-\d{2}\    # timestamp date
-    \d{2}:\d{2}:\d{2}\    # timestamp time
-    [-+]\d{4}             # timestamp offset
-  )
-  [\ ]+
-  (\d+)                   # line number
-  \)                      # close )
-  [\ ]
-  (.*)                    # file content
-$";
+// The first line has the commit hash, the line number in the commit and the
+// line number in the blamed file, and the line of the file follows the
+// header after a tab. The previous commit is missing for the initial commit.
+struct PorcelainLine {
+    commit: Commit,
+    line_number: usize,
+    contents: String,
+    // The commit which the line's commit changed the line from, i.e. the
+    // parent it was blamed at next, and the path of the file there, which
+    // differs from the blamed path if the file was renamed. These are about
+    // the file, so they don't belong in the commit, which is shared by all
+    // files.
+    previous_sha: Option<String>,
+    previous_path: Option<String>,
+    // Path of the file and line number in it in the line's commit, which
+    // differ from the blamed ones if the line was moved or copied.
+    original_path: String,
+    original_line: usize,
+}
+
+// The distinct commits in the output of `git blame --line-porcelain`, with
+// their parents and trailers. Uncommitted lines are left out.
+pub fn porcelain_commits(git_root_dir: &str, output: &str) -> Vec<Commit> {
+    let mut commits: Vec<Commit> = vec![];
+    let mut seen = HashSet::new();
+    for line in parse_porcelain(output) {
+        if !line.commit.sha.chars().all(|c| c == '0') && seen.insert(line.commit.sha.clone()) {
+            commits.push(line.commit);
+        }
+    }
+    Commit::complete(git_root_dir, &mut commits);
+    commits
+}
+
+// Parse the output of `git blame --line-porcelain`.
+fn parse_porcelain(output: &str) -> Vec<PorcelainLine> {
+    let mut lines = vec![];
+    let mut commit = Commit::default();
+    let mut line_number = 0;
+    let mut offset = String::new();
+    let mut previous_sha = None;
+    let mut previous_path = None;
+    let mut original_path = String::new();
+    let mut original_line = 0;
+    for line in output.lines() {
+        if let Some(contents) = line.strip_prefix('\t') {
+            commit.timestamp = age::format_timestamp(commit.time, &offset);
+            lines.push(PorcelainLine {
+                commit: mem::take(&mut commit),
+                line_number,
+                contents: contents.to_string(),
+                previous_sha: previous_sha.take(),
+                previous_path: previous_path.take(),
+                original_path: mem::take(&mut original_path),
+                original_line,
+            });
+            continue;
+        }
+
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "author" => commit.author = value.to_string(),
+            "author-mail" => {
+                commit.author_email = value
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            }
+            "author-time" => commit.time = value.parse().unwrap_or_default(),
+            "author-tz" => offset = value.to_string(),
+            "summary" => commit.commit_message = value.to_string(),
+            "previous" => {
+                let (sha, path) = value.split_once(' ').unwrap_or((value, ""));
+                previous_sha = Some(sha.to_string());
+                previous_path = Some(path.to_string()).filter(|p| !p.is_empty());
+            }
+            "filename" => original_path = value.to_string(),
+            // SHA-1 hashes have 40 digits, SHA-256 hashes have 64.
+            _ if (key.len() == 40 || key.len() == 64)
+                && key.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                commit.sha = key.to_string();
+                let mut numbers = value.split(' ').map(|n| n.parse().unwrap_or_default());
+                original_line = numbers.next().unwrap_or_default();
+                line_number = numbers.next().unwrap_or_default();
+            }
+            _ => {}
+        }
+    }
+    lines
+}
+
+// The way blames, commit information, files and refs are obtained from a
+// repository. Everything else only goes through this, so that the
+// in-process implementation can replace running git.
+pub trait GitBackend: Sync {
+    // The `git blame --line-porcelain` output of a file at a commit, or of
+    // the file in the working tree for WORKTREE, with the options for which
+    // lines are blamed and how.
+    fn blame(
+        &self,
+        git_root_dir: &str,
+        commit_sha: &str,
+        relative_path: &str,
+        options: &ParseOptions,
+    ) -> Result<String, FileBlameError>;
+
+    // Information about a commit.
+    fn commit(&self, git_root_dir: &str, sha: &str) -> Commit;
+
+    // The message body and changed files of a commit.
+    fn commit_details(&self, git_root_dir: &str, sha: &str) -> CommitDetails;
+
+    // First parents and trailers of many commits, keyed by their full hashes.
+    fn parents_and_trailers(
+        &self,
+        git_root_dir: &str,
+        shas: &[&str],
+    ) -> HashMap<String, ParentAndTrailers>;
+
+    // Whether a file exists at a commit.
+    fn exists_at_commit(&self, git_root_dir: &str, commit_sha: &str, relative_path: &str) -> bool;
+
+    // The abbreviated hash of the commit a ref points to.
+    fn rev_parse(&self, git_root_dir: &str, rev: &str) -> Result<String, FileBlameError>;
+
+    // The contents of a file at a commit, as committed.
+    fn file_contents(
+        &self,
+        git_root_dir: &str,
+        commit_sha: &str,
+        relative_path: &str,
+    ) -> Option<Vec<u8>>;
+
+    // Whether a commit is an ancestor of (or the same as) another commit.
+    fn is_ancestor(&self, git_root_dir: &str, ancestor: &str, descendant: &str) -> bool;
+
+    // The full hash of the best common ancestor of two commits.
+    fn merge_base(&self, git_root_dir: &str, a: &str, b: &str) -> Result<String, FileBlameError>;
+
+    // Paths of the files in a directory at a commit, relative to the root of
+    // the repository, or the path itself if it's a file. An empty path is
+    // the whole repository.
+    fn files_at_commit(
+        &self,
+        git_root_dir: &str,
+        commit_sha: &str,
+        relative_path: &str,
+    ) -> Result<Vec<String>, FileBlameError>;
+}
+
+// Backend which runs the git command line, with one process per call.
+pub struct CliBackend;
+
+// The backend used for all git access: libgit2 when built with the git2
+// feature, and the git command line otherwise.
+#[cfg(not(feature = "git2"))]
+pub fn backend() -> &'static dyn GitBackend {
+    &CliBackend
+}
+
+#[cfg(feature = "git2")]
+pub fn backend() -> &'static dyn GitBackend {
+    &crate::git2_backend::Git2Backend
+}
+
+// Run a git command in the repository and return the lines of its output.
+pub fn git_lines(git_root_dir: &str, args: &[&str]) -> Result<Vec<String>, FileBlameError> {
+    let output = metrics::output(Command::new("git").current_dir(git_root_dir).args(args))
+        .map_err(|e| FileBlameError::Unknown(e.to_string()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(FileBlameError::Unknown(stderr));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|l| l.to_string())
+        .collect())
+}
+
+impl GitBackend for CliBackend {
+    fn blame(
+        &self,
+        git_root_dir: &str,
+        commit_sha: &str,
+        relative_path: &str,
+        options: &ParseOptions,
+    ) -> Result<String, FileBlameError> {
+        // Without fetching, the blame stops at the oldest commit whose
+        // version of the file is available in a partial clone. Git 2.44 and
+        // later also refuse to fetch anything which is missing anyway.
+        let boundary = if options.no_fetch && Promisor::detect(git_root_dir).is_some() {
+            partial_clone::local_boundary(git_root_dir, commit_sha, relative_path)?
+        } else {
+            None
+        };
+        let output = metrics::output(
+            Command::new("git")
+                .arg("blame")
+                .arg("--line-porcelain")
+                .current_dir(git_root_dir)
+                .envs(options.no_fetch.then_some(("GIT_NO_LAZY_FETCH", "1")))
+                .args(options.copy_detection.git_args())
+                // An empty file clears the one set with blame.ignoreRevsFile,
+                // so that ignoring can be turned off.
+                .arg("--ignore-revs-file=")
+                .args(
+                    options
+                        .ignore_revs_file
+                        .as_ref()
+                        .map(|file| format!("--ignore-revs-file={}", file)),
+                )
+                .args(
+                    options
+                        .ignore_revs
+                        .iter()
+                        .map(|sha| format!("--ignore-rev={}", sha)),
+                )
+                .args(boundary.map(|sha| format!("^{}", sha)))
+                .args(match &options.reverse_until {
+                    Some(end) => vec!["--reverse".to_string(), format!("{}..{}", commit_sha, end)],
+                    None => Some(commit_sha)
+                        .filter(|&c| c != WORKTREE)
+                        .map(str::to_string)
+                        .into_iter()
+                        .collect(),
+                })
+                .args(
+                    options
+                        .line_range
+                        .map(|(start, end)| format!("-L{},{}", start, end)),
+                )
+                .arg("--")
+                .arg(relative_path),
+        )
+        .map_err(|e| FileBlameError::Unknown(e.to_string()))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            return Err(FileBlameError::Unknown(stderr));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn commit(&self, git_root_dir: &str, sha: &str) -> Commit {
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("show")
+                .arg(sha)
+                .arg("--pretty=format:%P%x00%aN%x00%ae%x00%ai%x00%at%x00%(trailers:only,unfold)%x00%s")
+                .arg("--no-patch"),
+        )
+        .expect("failed to execute process");
+        let output = String::from_utf8_lossy(&output.stdout);
+
+        // The output fields are separated by NUL characters since
+        // commit messages and emails can contain any other character.
+        let mut fields = output.splitn(7, '\0');
+        let parent_commit = fields.next().unwrap_or("");
+        let author = fields.next().unwrap_or("");
+        let author_email = fields.next().unwrap_or("");
+        let timestamp = fields.next().unwrap_or("");
+        let time = fields.next().unwrap_or("").parse().unwrap_or_default();
+        let trailers = parse_trailers(fields.next().unwrap_or(""));
+        let commit_message = fields.next().unwrap_or("");
+
+        Commit {
+            author: author.to_owned(),
+            author_email: author_email.to_owned(),
+            commit_message: commit_message.to_owned(),
+            timestamp: timestamp.to_owned(),
+            time,
+            trailers,
+            sha: sha.to_owned(),
+            parent_commit_sha: first_parent(parent_commit),
+        }
+    }
+
+    fn commit_details(&self, git_root_dir: &str, sha: &str) -> CommitDetails {
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("show")
+                .arg("--format=%b%x00")
+                .arg("--name-status")
+                .arg(sha),
+        );
+        let Ok(output) = output else {
+            return CommitDetails::default();
+        };
+
+        // The body is followed by a NUL character and the changed files, one
+        // per line as the status and the path separated by a tab.
+        let output = String::from_utf8_lossy(&output.stdout);
+        let (body, files) = output.split_once('\0').unwrap_or((&output, ""));
+        CommitDetails {
+            body: body.trim().to_string(),
+            changed_files: files
+                .lines()
+                .filter_map(|line| line.split_once('\t'))
+                .map(|(status, path)| (status.to_string(), path.replace('\t', " -> ")))
+                .collect(),
+        }
+    }
+
+    fn parents_and_trailers(
+        &self,
+        git_root_dir: &str,
+        shas: &[&str],
+    ) -> HashMap<String, ParentAndTrailers> {
+        if shas.is_empty() {
+            return HashMap::new();
+        }
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("log")
+                .arg("--no-walk=unsorted")
+                .arg("--format=%H%x00%P%x00%(trailers:only,unfold)%x1e")
+                .args(shas),
+        );
+        let Ok(output) = output else {
+            return HashMap::new();
+        };
+
+        // Each commit's trailers are followed by a record separator, since
+        // they span multiple lines.
+        String::from_utf8_lossy(&output.stdout)
+            .split('\x1e')
+            .filter_map(|record| {
+                let mut fields = record.trim_start().splitn(3, '\0');
+                let sha = fields.next().filter(|sha| !sha.is_empty())?;
+                let parent = first_parent(fields.next()?);
+                let trailers = parse_trailers(fields.next()?);
+                Some((sha.to_string(), (parent, trailers)))
+            })
+            .collect()
+    }
+
+    fn exists_at_commit(&self, git_root_dir: &str, commit_sha: &str, relative_path: &str) -> bool {
+        // Run the Git command for the check. If the file exists, there will be no
+        // output and the status will be success (0). Otherwise, the output will
+        // be an error message "fatal: invalid object name '32c2e2df'" if the commit
+        // doesn't exist, or "fatal: path 'foobar.rs' does not exist in '32c2e2df'"
+        // if the file doesn't exist at that commit. In both cases, an unsuccessful
+        // status is returned (>0).
+        metrics::output(
+            Command::new("git")
+                .arg("cat-file")
+                .current_dir(git_root_dir)
+                .arg("-e")
+                .arg(format!("{}:{}", commit_sha, relative_path)),
+        )
+        .unwrap()
+        .status
+        .success()
+    }
+
+    fn rev_parse(&self, git_root_dir: &str, rev: &str) -> Result<String, FileBlameError> {
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("rev-parse")
+                .arg("--verify")
+                .arg("--short")
+                .arg(format!("{}^{{commit}}", rev)),
+        )
+        .map_err(|e| FileBlameError::Unknown(e.to_string()))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(FileBlameError::Unknown(stderr));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn file_contents(
+        &self,
+        git_root_dir: &str,
+        commit_sha: &str,
+        relative_path: &str,
+    ) -> Option<Vec<u8>> {
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("cat-file")
+                .arg("blob")
+                .arg(format!("{}:{}", commit_sha, relative_path)),
+        )
+        .ok()?;
+        output.status.success().then_some(output.stdout)
+    }
+
+    fn is_ancestor(&self, git_root_dir: &str, ancestor: &str, descendant: &str) -> bool {
+        metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("merge-base")
+                .arg("--is-ancestor")
+                .arg(ancestor)
+                .arg(descendant),
+        )
+        .is_ok_and(|output| output.status.success())
+    }
+
+    fn merge_base(&self, git_root_dir: &str, a: &str, b: &str) -> Result<String, FileBlameError> {
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("merge-base")
+                .arg(a)
+                .arg(b),
+        )
+        .map_err(|e| FileBlameError::Unknown(e.to_string()))?;
+
+        // Without a common ancestor, git exits with an error but doesn't
+        // print anything.
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(FileBlameError::Unknown(if stderr.is_empty() {
+                format!("no merge base of {} and {}", a, b)
+            } else {
+                stderr
+            }));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn files_at_commit(
+        &self,
+        git_root_dir: &str,
+        commit_sha: &str,
+        relative_path: &str,
+    ) -> Result<Vec<String>, FileBlameError> {
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("ls-tree")
+                .arg("-r")
+                .arg("--name-only")
+                .arg(commit_sha)
+                .arg("--")
+                .args(Some(relative_path).filter(|p| !p.is_empty())),
+        )
+        .map_err(|e| FileBlameError::Unknown(e.to_string()))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            return Err(FileBlameError::Unknown(stderr));
+        }
+        let files: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.to_string())
+            .collect();
+        if files.is_empty() {
+            return Err(FileBlameError::MissingAtCommit);
+        }
+        Ok(files)
+    }
+}
+
+// The first parent of a commit, and its trailers.
+pub type ParentAndTrailers = (Option<String>, BTreeMap<String, Vec<String>>);
+
+// The first of the space separated parents of a commit, as output by git
+// for %P, if it has any.
+fn first_parent(parents: &str) -> Option<String> {
+    parents.split_whitespace().next().map(str::to_string)
+}
+
+// Details of a commit which are only shown when looking at the commit, so
+// they are loaded on demand instead of for every commit in a blame.
+#[derive(PartialEq, Default, Clone, Debug)]
+pub struct CommitDetails {
+    // The commit message without the summary line.
+    pub body: String,
+    // Status (e.g. M, A or R100) and path of each changed file. Renamed
+    // files have both paths, as "old -> new".
+    pub changed_files: Vec<(String, String)>,
+}
+
+impl CommitDetails {
+    pub fn load(git_root_dir: &str, sha: &str) -> CommitDetails {
+        backend().commit_details(git_root_dir, sha)
+    }
+}
 
 // Metadata for a single Git commit. All commits have a parent,
 // except the initial commit.
-#[derive(PartialEq, Default, Clone, Debug)]
+#[derive(PartialEq, Default, Clone, Debug, Serialize, Deserialize)]
 pub struct Commit {
     pub sha: String,
     pub author: String,
+    pub author_email: String,
     pub commit_message: String,
     pub parent_commit_sha: Option<String>,
     pub timestamp: String,
+    // Author time as seconds since the Unix epoch.
+    pub time: i64,
+    // Trailers at the end of the commit message (e.g. Signed-off-by), keyed
+    // by the trailer name. A trailer can appear multiple times in a message.
+    pub trailers: BTreeMap<String, Vec<String>>,
+}
+
+impl Commit {
+    // Whether this is the placeholder commit of lines changed in the working
+    // tree, which git blames on an all zero hash.
+    pub fn is_uncommitted(&self) -> bool {
+        self.sha.bytes().all(|b| b == b'0')
+    }
+
+    // Fill in the parents and trailers of many commits parsed from a blame
+    // at once, since git blame doesn't output them. The parent a blame
+    // mentions for a line is the one the line was blamed at next, which for
+    // a merge commit needn't be the first parent.
+    pub fn complete(git_root_dir: &str, commits: &mut [Commit]) {
+        let shas: Vec<&str> = commits.iter().map(|c| c.sha.as_str()).collect();
+        let mut loaded = backend().parents_and_trailers(git_root_dir, &shas);
+        for commit in commits {
+            if let Some((parent, trailers)) = loaded.remove(&commit.sha) {
+                commit.parent_commit_sha = parent;
+                commit.trailers = trailers;
+            }
+        }
+    }
+
+    // Fetch the information about a commit in a repository.
+    pub fn load(git_root_dir: &str, sha: &str) -> Commit {
+        backend().commit(git_root_dir, sha)
+    }
+
+    // Check whether the commit has a trailer matching a query. The query is
+    // either "Key: value", which matches trailers with that key whose value
+    // contains the given value, or just a value, which matches any trailer
+    // whose value contains it. Matching is case-insensitive.
+    pub fn has_trailer(&self, query: &str) -> bool {
+        let (key, value) = match query.split_once(':') {
+            Some((key, value)) => (Some(key.trim()), value.trim()),
+            None => (None, query.trim()),
+        };
+        let value = value.to_lowercase();
+
+        self.trailers.iter().any(|(k, values)| {
+            key.is_none_or(|key| key.eq_ignore_ascii_case(k))
+                && values.iter().any(|v| v.to_lowercase().contains(&value))
+        })
+    }
 }
 
 // A single line for a Git blame of a specific file at a specific commit.
@@ -64,8 +602,284 @@ pub struct Commit {
 #[derive(PartialEq, Clone, Debug)]
 pub struct BlameLine {
     pub commit_sha: String,
+    // Line contents with syntax highlighting applied as ANSI escape codes.
+    // Very long lines are truncated.
     pub contents: String,
+    // Line contents as they are in the file.
+    pub plain_contents: String,
     pub line_number: String,
+    // Whether the highlighted contents were truncated.
+    pub truncated: bool,
+    // The parent of the line's commit which the line is blamed at next,
+    // unless the line's commit added the file, and the path of the file
+    // there, relative to the root of the repository. The path differs from
+    // the blamed file's path if the file was renamed in the commit. In a
+    // reverse blame, it's the child of the line's commit which deleted the
+    // line instead, unless the line still exists at the end.
+    pub previous_sha: Option<String>,
+    pub previous_path: Option<String>,
+    // Path of the file in the line's commit, relative to the root of the
+    // repository, if it's another file, e.g. because the file was renamed
+    // since, or the line was copied from another file.
+    pub original_path: Option<String>,
+    // Number of the line in the file in the line's commit.
+    pub original_line: usize,
+    // With commits ignored, the commit which the line is blamed on without
+    // ignoring any, if git skipped over it, i.e. it's one of the ignored
+    // commits.
+    pub skipped_commit: Option<String>,
+}
+
+// Syntax highlighted contents of blamed files, keyed by a hash of the path,
+// the theme and the lines. Blaming the same contents again, e.g. with other
+// options or at a commit which didn't change the file, reuses them instead
+// of highlighting the file again, which is what's slow for big files.
+pub type HighlightCache = LruCache<u64, Vec<String>>;
+
+// Key of the highlighted contents of the lines of a file in the highlight
+// cache. The syntax is found by the path, and the lines are highlighted with
+// the theme and truncated to the maximum length.
+fn highlight_key(filename: &str, options: &ParseOptions, lines: &[PorcelainLine]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    filename.hash(&mut hasher);
+    options.syntax_theme.hash(&mut hasher);
+    options.max_line_length.hash(&mut hasher);
+    for line in lines {
+        line.line_number.hash(&mut hasher);
+        line.contents.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+// Default number of characters of a line which are highlighted and shown.
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 1000;
+
+// Default number of lines of a blame above which syntax highlighting is
+// skipped, as highlighting is what makes huge files slow to open.
+pub const DEFAULT_HIGHLIGHT_LINE_LIMIT: usize = 50_000;
+
+// Syntax highlighting theme for terminals with a dark background, which is
+// also used when the chosen theme doesn't exist.
+pub const DEFAULT_SYNTAX_THEME: &str = "base16-ocean.dark";
+
+// Names of the syntax highlighting themes which are built in, in
+// alphabetical order.
+pub fn syntax_themes() -> Vec<String> {
+    ThemeSet::load_defaults().themes.into_keys().collect()
+}
+
+// Names of files without an extension, and the extension of the syntax
+// they're highlighted with. They're also recognized with another extension
+// added, e.g. Makefile.custom or Dockerfile.dev.
+const FILE_NAME_SYNTAXES: &[(&str, &str)] = &[
+    ("BUILD", "py"),
+    ("Containerfile", "sh"),
+    ("Dockerfile", "sh"),
+    ("GNUmakefile", "make"),
+    ("Gemfile", "rb"),
+    ("Jenkinsfile", "groovy"),
+    ("Makefile", "make"),
+    ("Rakefile", "rb"),
+    ("Vagrantfile", "rb"),
+    ("WORKSPACE", "py"),
+    ("makefile", "make"),
+];
+
+// The syntax of a file, found from its name, e.g. main.rs, Makefile or
+// Makefile.custom, then from its first line, e.g. a shebang. Files with
+// unknown syntaxes, e.g. binary files stored with Git LFS, are shown as plain
+// text.
+fn find_syntax<'a>(
+    syntaxes: &'a SyntaxSet,
+    path: &Path,
+    first_line: Option<&str>,
+) -> &'a SyntaxReference {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let extension = path.extension().and_then(|e| e.to_str());
+    let base_name = file_name.split('.').next().unwrap_or(file_name);
+    let by_file_name = || {
+        let (_, extension) = FILE_NAME_SYNTAXES
+            .iter()
+            .find(|(name, _)| *name == base_name)?;
+        syntaxes.find_syntax_by_extension(extension)
+    };
+    // Syntaxes also list some file names among their extensions, e.g.
+    // .bashrc.
+    syntaxes
+        .find_syntax_by_extension(file_name)
+        .or_else(|| extension.and_then(|e| syntaxes.find_syntax_by_extension(e)))
+        .or_else(by_file_name)
+        .or_else(|| first_line.and_then(|line| syntaxes.find_syntax_by_first_line(line)))
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text())
+}
+
+// The phases of parsing a blame, in order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadPhase {
+    // Running git blame, or reading its output from the disk cache.
+    Blaming,
+    // Looking up the commits of the lines, and loading their trailers.
+    LoadingCommits,
+    Highlighting,
+}
+
+impl LoadPhase {
+    pub fn label(self) -> &'static str {
+        match self {
+            LoadPhase::Blaming => "running git blame",
+            LoadPhase::LoadingCommits => "loading commits",
+            LoadPhase::Highlighting => "highlighting",
+        }
+    }
+}
+
+// How far along parsing a blame is: the phase it's in, and the percentage
+// of the phase which is done. Git blame doesn't tell how far along it is, so
+// phases other than highlighting stay at 0% until the next one starts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub phase: LoadPhase,
+    pub percent: u8,
+}
+
+impl Progress {
+    fn start(phase: LoadPhase) -> Progress {
+        Progress { phase, percent: 0 }
+    }
+}
+
+// Options for parsing a blame.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ParseOptions {
+    // Lines longer than this many characters are truncated in the highlighted
+    // contents, while the plain contents are kept whole.
+    pub max_line_length: usize,
+    // Blames with more lines than this are not syntax highlighted.
+    pub highlight_line_limit: usize,
+    // Only blame the lines in this range, 1-based and inclusive, like git
+    // blame's -L option.
+    pub line_range: Option<(usize, usize)>,
+    // How lines which were moved or copied are detected.
+    pub copy_detection: CopyDetection,
+    // Don't fetch objects missing in a partial clone, and blame the lines
+    // from before them on the oldest commit which can be blamed instead.
+    pub no_fetch: bool,
+    // Absolute path of a file listing commits to ignore, e.g. bulk
+    // reformatting commits, whose lines are blamed on the commits before.
+    pub ignore_revs_file: Option<String>,
+    // Commits to ignore in addition to the ones in the file, e.g. picked
+    // one at a time while blaming.
+    pub ignore_revs: Vec<String>,
+    // Name of the syntax highlighting theme, e.g. "InspiredGitHub" for
+    // terminals with a light background.
+    pub syntax_theme: String,
+    // Blame in reverse from the blamed commit up to this one, like git
+    // blame's --reverse option. Each line is blamed on the last commit in
+    // which it still existed, and the commit which deleted it is the
+    // previous commit of the line.
+    pub reverse_until: Option<String>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            highlight_line_limit: DEFAULT_HIGHLIGHT_LINE_LIMIT,
+            line_range: None,
+            copy_detection: CopyDetection::Off,
+            no_fetch: false,
+            ignore_revs_file: None,
+            ignore_revs: Vec::new(),
+            syntax_theme: DEFAULT_SYNTAX_THEME.to_string(),
+            reverse_until: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    // Whether the blame is a plain `git blame` of the whole file, which is
+    // what the disk cache stores.
+    pub fn is_plain_blame(&self) -> bool {
+        self.line_range.is_none()
+            && self.copy_detection == CopyDetection::Off
+            && !self.no_fetch
+            && self.ignore_revs_file.is_none()
+            && self.ignore_revs.is_empty()
+            && self.reverse_until.is_none()
+    }
+
+    // Whether any commits are ignored, from the file or picked one at a time.
+    pub fn ignores_revs(&self) -> bool {
+        self.ignore_revs_file.is_some() || !self.ignore_revs.is_empty()
+    }
+}
+
+// How hard git blame looks for lines which were moved or copied, so that
+// they're blamed on the commit which originally added them instead of the
+// commit which moved them, like git blame's -M and -C options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CopyDetection {
+    #[default]
+    Off,
+    // Lines moved or copied within the file (-M).
+    Moves,
+    // Also lines moved or copied from other files changed in the same
+    // commit (-C).
+    Copies,
+    // Also lines copied from any file in the commit which created the
+    // file (-C -C).
+    MoreCopies,
+}
+
+impl CopyDetection {
+    pub fn git_args(self) -> &'static [&'static str] {
+        match self {
+            CopyDetection::Off => &[],
+            CopyDetection::Moves => &["-M"],
+            CopyDetection::Copies => &["-M", "-C"],
+            CopyDetection::MoreCopies => &["-M", "-C", "-C"],
+        }
+    }
+
+    // The next way of detecting moves and copies, from the cheapest to the
+    // most thorough and back to none.
+    pub fn next(self) -> CopyDetection {
+        match self {
+            CopyDetection::Off => CopyDetection::Moves,
+            CopyDetection::Moves => CopyDetection::Copies,
+            CopyDetection::Copies => CopyDetection::MoreCopies,
+            CopyDetection::MoreCopies => CopyDetection::Off,
+        }
+    }
+
+    // Short name like the git blame options, e.g. for the title.
+    pub fn label(self) -> &'static str {
+        match self {
+            CopyDetection::Off => "off",
+            CopyDetection::Moves => "-M",
+            CopyDetection::Copies => "-C",
+            CopyDetection::MoreCopies => "-CC",
+        }
+    }
+}
+
+// Parse a line range like "100,200" as given to the -L option.
+pub fn parse_line_range(range: &str) -> Result<(usize, usize), String> {
+    let (start, end) = range
+        .split_once(',')
+        .ok_or_else(|| format!("expected START,END but got '{}'", range))?;
+    let start: usize = start
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid start line '{}'", start))?;
+    let end: usize = end
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid end line '{}'", end))?;
+    if start == 0 || end < start {
+        return Err(format!("invalid line range '{}'", range));
+    }
+    Ok((start, end))
 }
 
 // All lines for a Git blame of a specific file at a specific commit.
@@ -76,82 +890,608 @@ pub struct FileBlame {
     pub blame_lines: Vec<BlameLine>,
     pub filepath: String,
     pub commit_sha: String,
+    // Whether syntax highlighting was applied, which is skipped for blames
+    // with more lines than the limit in the parse options.
+    pub highlighted: bool,
+    // The Git LFS pointer which the file is at the commit, if it's stored
+    // with LFS.
+    pub lfs_pointer: Option<LfsPointer>,
 }
 
 // Possible errors that can be returned when building a blame for a file.
 #[derive(Debug, Clone)]
 pub enum FileBlameError {
+    GitNotFound,
     NotExist,
     NotFile,
     NotGit,
     MissingAtCommit,
+    // A file which doesn't exist, with the paths of similar tracked files.
+    UnknownPath(String, Vec<String>),
+    // A ref which doesn't exist, with the names of similar refs.
+    UnknownRef(String, Vec<String>),
     Unknown(String),
 }
 
 impl Display for FileBlameError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            FileBlameError::GitNotFound => write!(
+                f,
+                "git was not found. Install it and make sure it's on your PATH"
+            ),
             FileBlameError::NotExist => write!(f, "File doesn't exist"),
             FileBlameError::NotFile => write!(f, "Provided path is not a regular file"),
             FileBlameError::NotGit => write!(f, "File is not in a git repository"),
             FileBlameError::MissingAtCommit => write!(f, "File does not exist at commit"),
+            FileBlameError::UnknownPath(path, suggestions) => write!(
+                f,
+                "File '{}' doesn't exist.{}",
+                path,
+                suggest::did_you_mean(suggestions)
+            ),
+            FileBlameError::UnknownRef(name, suggestions) => write!(
+                f,
+                "Ref '{}' not found.{}",
+                name,
+                suggest::did_you_mean(suggestions)
+            ),
             FileBlameError::Unknown(s) => write!(f, "Unknown error: {}", s),
         }
     }
 }
 
+impl FileBlameError {
+    // Exit code of the program when it fails with the error. Exit code 2 is
+    // used for invalid arguments.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FileBlameError::Unknown(_) => 1,
+            FileBlameError::NotGit => 3,
+            FileBlameError::NotExist | FileBlameError::UnknownPath(_, _) => 4,
+            FileBlameError::NotFile => 5,
+            FileBlameError::MissingAtCommit => 6,
+            FileBlameError::GitNotFound => 7,
+            FileBlameError::UnknownRef(_, _) => 8,
+        }
+    }
+}
+
 impl Error for FileBlameError {}
 
+// File-level metadata of a blamed file at a specific commit.
+#[derive(PartialEq, Clone, Debug)]
+pub struct FileInfo {
+    // File mode as stored by Git, e.g. "100644" or "100755".
+    pub mode: String,
+    // Size of the file in bytes.
+    pub size: u64,
+    // Whether the file in the working tree is different from the file at the
+    // commit, including when it was deleted from the working tree.
+    pub differs_from_worktree: bool,
+    // Whether the file is left out of the working tree by a sparse checkout,
+    // in which case it isn't compared to the working tree.
+    pub sparse: bool,
+}
+
+impl FileInfo {
+    // Look up the metadata of a file at a commit with `git ls-tree` and
+    // compare the file to the working tree with `git diff`.
+    pub fn load(filepath: &str, commit_sha: &str) -> Option<FileInfo> {
+        let (git_root_dir, relative_path) = FileBlame::repo_paths(filepath)?;
+
+        // The output looks like "<mode> blob <object> <size>\t<path>"
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(&git_root_dir)
+                .arg("ls-tree")
+                .arg("-l")
+                .arg(commit_sha)
+                .arg("--")
+                .arg(&relative_path),
+        )
+        .ok()?;
+        let output = String::from_utf8_lossy(&output.stdout);
+        let (info, _) = output.lines().next()?.split_once('\t')?;
+        let fields: Vec<&str> = info.split_whitespace().collect();
+        let [mode, _, _, size] = fields[..] else {
+            return None;
+        };
+        let sparse = FileBlame::is_outside_sparse_checkout(&git_root_dir, &relative_path);
+
+        // With --quiet, the exit status is 1 if there are differences.
+        let differs_from_worktree = !metrics::output(
+            Command::new("git")
+                .current_dir(&git_root_dir)
+                .arg("diff")
+                .arg("--quiet")
+                .arg(commit_sha)
+                .arg("--")
+                .arg(&relative_path),
+        )
+        .ok()?
+        .status
+        .success();
+
+        Some(FileInfo {
+            mode: mode.to_string(),
+            size: size.parse().ok()?,
+            differs_from_worktree,
+            sparse,
+        })
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.mode == "100755"
+    }
+}
+
 impl FileBlame {
+    // Split the blame lines into blocks, i.e. contiguous groups of lines with
+    // the same commit. Each block is given as a range of indices into the
+    // blame lines.
+    pub fn blocks(&self) -> Vec<RangeInclusive<usize>> {
+        let mut blocks: Vec<RangeInclusive<usize>> = vec![];
+        for (i, line) in self.blame_lines.iter().enumerate() {
+            match blocks.last_mut() {
+                Some(block) if self.blame_lines[*block.start()].commit_sha == line.commit_sha => {
+                    *block = *block.start()..=i;
+                }
+                _ => blocks.push(i..=i),
+            }
+        }
+        blocks
+    }
+
+    // Author times of the oldest and the newest commit which lines of the
+    // file are blamed on, as seconds since the Unix epoch.
+    pub fn time_range(&self, commit_cache: &HashMap<String, Commit>) -> Option<(i64, i64)> {
+        let mut times = self
+            .blame_lines
+            .iter()
+            .filter_map(|line| commit_cache.get(&line.commit_sha))
+            .map(|commit| commit.time);
+        let first = times.next()?;
+        Some(times.fold((first, first), |(oldest, newest), time| {
+            (oldest.min(time), newest.max(time))
+        }))
+    }
+
+    // Check that the git binary can be run, since everything else relies on it.
+    pub fn check_git() -> Result<(), FileBlameError> {
+        match Command::new("git").arg("--version").output() {
+            Ok(output) if output.status.success() => Ok(()),
+            Ok(output) => Err(FileBlameError::Unknown(
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            )),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(FileBlameError::GitNotFound),
+            Err(e) => Err(FileBlameError::Unknown(e.to_string())),
+        }
+    }
+
     // Check if a file exists at a specific commit.
     pub fn exists_at_commit(filepath: &str, commit_sha: &str) -> bool {
         // Split the filepath into two parts:
         //   - git_root_dir  - the root of the Git repository which contains the file
         //   - relative_path - the file's path relative to the root of the repository
-        let path = Path::new(filepath).canonicalize().unwrap();
-        let git_root_dir = FileBlame::git_root_dir(&path);
-        let relative_path = path.strip_prefix(&git_root_dir).unwrap().to_str().unwrap();
+        let Some((git_root_dir, relative_path)) = FileBlame::repo_paths(filepath) else {
+            return false;
+        };
 
-        // Run the Git command for the check. If the file exists, there will be no
-        // output and the status will be success (0). Otherwise, the output will
-        // be an error message "fatal: invalid object name '32c2e2df'" if the commit
-        // doesn't exist, or "fatal: path 'foobar.rs' does not exist in '32c2e2df'"
-        // if the file doesn't exist at that commit. In both cases, an unsuccessful
-        // status is returned (>0).
-        return Command::new("git")
-            .arg("cat-file")
-            .current_dir(&git_root_dir)
-            .arg("-e")
-            .arg(format!("{}:{}", commit_sha, relative_path))
-            .output()
-            .unwrap()
-            .status
-            .success();
+        backend().exists_at_commit(&git_root_dir, commit_sha, &relative_path)
+    }
+
+    // Check whether a path is inside the working tree of a Git repository.
+    fn is_in_work_tree(path: &Path) -> bool {
+        let Some(dir) = path.ancestors().find(|p| p.is_dir()) else {
+            return false;
+        };
+        metrics::output(
+            Command::new("git")
+                .current_dir(dir)
+                .arg("rev-parse")
+                .arg("--is-inside-work-tree"),
+        )
+        .is_ok_and(|output| output.status.success())
+    }
+
+    // The ref to blame a file at when none is given: the working tree if the
+    // file has uncommitted changes, so that they're blamed too, and HEAD
+    // otherwise, whose blames can be cached and which is followed as it moves.
+    pub fn default_ref(filepath: &str) -> &'static str {
+        let Some((git_root_dir, relative_path)) = FileBlame::repo_paths(filepath) else {
+            return "HEAD";
+        };
+        if !Path::new(filepath).exists() {
+            return "HEAD";
+        }
+        // With --quiet, the exit status is 1 if there are differences.
+        let unchanged = metrics::output(
+            Command::new("git")
+                .current_dir(&git_root_dir)
+                .args(["diff", "--quiet", "HEAD", "--"])
+                .arg(&relative_path),
+        )
+        .map_or(true, |output| output.status.success());
+        if unchanged {
+            "HEAD"
+        } else {
+            WORKTREE
+        }
+    }
+
+    // Check the path, the repository and the ref in one go before starting,
+    // so that mistakes are reported up front, with suggestions for refs
+    // which were mistyped.
+    pub fn validate(filepath: &str, gitref: &str) -> Result<(), FileBlameError> {
+        let path = FileBlame::validate_path(filepath)?;
+
+        if gitref == WORKTREE {
+            return if path.exists() || FileBlame::is_sparse_path(&path) {
+                Ok(())
+            } else {
+                Err(FileBlame::unknown_path(filepath))
+            };
+        }
+
+        FileBlame::validate_ref(filepath, gitref)?;
+
+        if !FileBlame::exists_at_commit(filepath, gitref) {
+            return Err(if path.exists() {
+                FileBlameError::MissingAtCommit
+            } else {
+                FileBlame::unknown_path(filepath)
+            });
+        }
+        Ok(())
+    }
+
+    // Check that a ref exists in the repository of a file, suggesting
+    // similarly named refs if it doesn't.
+    pub fn validate_ref(filepath: &str, gitref: &str) -> Result<(), FileBlameError> {
+        if FileBlame::rev_parse(filepath, gitref).is_err() {
+            let (git_root_dir, _) =
+                FileBlame::repo_paths(filepath).ok_or(FileBlameError::NotExist)?;
+            let refs = FileBlame::ref_names(&git_root_dir);
+            let suggestions = suggest::closest(gitref, refs.iter().map(String::as_str), 3);
+            return Err(FileBlameError::UnknownRef(gitref.to_string(), suggestions));
+        }
+        Ok(())
+    }
+
+    // Check that a path is a file in a repository, before anything else
+    // looks into the repository, e.g. for the ref to blame it at.
+    pub fn validate_path(filepath: &str) -> Result<PathBuf, FileBlameError> {
+        let path = FileBlame::absolute_path(filepath).ok_or(FileBlameError::NotExist)?;
+        if path.exists() && !path.is_file() {
+            return Err(FileBlameError::NotFile);
+        }
+        if !FileBlame::is_in_work_tree(&path) {
+            return Err(FileBlameError::NotGit);
+        }
+        Ok(path)
+    }
+
+    // The error for a path which doesn't exist, with the tracked files whose
+    // paths are most similar to it. Suggestions are relative to the current
+    // directory when they're inside of it, like the given path.
+    fn unknown_path(filepath: &str) -> FileBlameError {
+        let Some((git_root_dir, relative_path)) = FileBlame::repo_paths(filepath) else {
+            return FileBlameError::NotExist;
+        };
+        let files = FileBlame::tracked_files(&git_root_dir);
+        let cwd = env::current_dir()
+            .and_then(|d| d.canonicalize())
+            .unwrap_or_default();
+        let suggestions = suggest::closest(&relative_path, files.iter().map(String::as_str), 3)
+            .into_iter()
+            .map(|file| {
+                let absolute = Path::new(&git_root_dir).join(&file);
+                absolute
+                    .strip_prefix(&cwd)
+                    .unwrap_or(&absolute)
+                    .display()
+                    .to_string()
+            })
+            .collect();
+        FileBlameError::UnknownPath(filepath.to_string(), suggestions)
+    }
+
+    // Whether a tracked file is left out of the working tree by a sparse
+    // checkout, which `git ls-files -t` marks with "S" for skip-worktree.
+    pub fn is_outside_sparse_checkout(git_root_dir: &str, relative_path: &str) -> bool {
+        metrics::output(Command::new("git").current_dir(git_root_dir).args([
+            "ls-files",
+            "-t",
+            "--",
+            relative_path,
+        ]))
+        .is_ok_and(|output| output.stdout.starts_with(b"S "))
+    }
+
+    fn is_sparse_path(path: &Path) -> bool {
+        path.to_str().and_then(FileBlame::repo_paths).is_some_and(
+            |(git_root_dir, relative_path)| {
+                FileBlame::is_outside_sparse_checkout(&git_root_dir, &relative_path)
+            },
+        )
+    }
+
+    // The file listing the commits which blames in the repository with the
+    // given root ignore: the one set with blame.ignoreRevsFile, or the
+    // repository's .git-blame-ignore-revs file, which GitHub uses too.
+    pub fn ignore_revs_file(git_root_dir: &str) -> Option<String> {
+        let configured = metrics::output(Command::new("git").current_dir(git_root_dir).args([
+            "config",
+            "--path",
+            "blame.ignoreRevsFile",
+        ]))
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|file| !file.is_empty());
+        let file =
+            Path::new(git_root_dir).join(configured.as_deref().unwrap_or(".git-blame-ignore-revs"));
+        file.is_file().then(|| file.display().to_string())
+    }
+
+    // Map a path outside of a repository, e.g. of a file in an extracted
+    // `git archive` tarball of a deployed release, to the file with the same
+    // path in the repository at the given directory. The archive's root isn't
+    // known, so the longest trailing part of the path which exists in the
+    // repository at the ref is used, e.g. "src/main.rs" for
+    // "/srv/app-1.2/src/main.rs". Returns the path of the file in the
+    // repository, relative to the current directory where possible.
+    pub fn map_into_repo(
+        filepath: &str,
+        repo_dir: &str,
+        gitref: &str,
+    ) -> Result<String, FileBlameError> {
+        let repo_path = FileBlame::absolute_path(repo_dir).ok_or(FileBlameError::NotExist)?;
+        if !FileBlame::is_in_work_tree(&repo_path) {
+            return Err(FileBlameError::NotGit);
+        }
+        let git_root_dir = FileBlame::git_root_dir(&repo_path).ok_or(FileBlameError::NotGit)?;
+
+        let path = FileBlame::absolute_path(filepath).ok_or(FileBlameError::NotExist)?;
+        let components: Vec<&str> = path
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(c) => c.to_str(),
+                _ => None,
+            })
+            .collect();
+        (0..components.len())
+            .map(|i| components[i..].join("/"))
+            .find(|relative_path| backend().exists_at_commit(&git_root_dir, gitref, relative_path))
+            .map(|relative_path| FileBlame::display_path(&git_root_dir, &relative_path))
+            .ok_or_else(|| FileBlameError::UnknownPath(filepath.to_string(), vec![]))
+    }
+
+    // Paths of the files tracked in a repository, relative to its root.
+    pub fn tracked_files(git_root_dir: &str) -> Vec<String> {
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("ls-files"),
+        );
+        match output {
+            Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(|l| l.to_string())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    // Paths of the files in a directory of a repository at a commit,
+    // relative to the root of the repository, or the path itself if it's a
+    // file. An empty path is the whole repository.
+    pub fn files_at_commit(
+        git_root_dir: &str,
+        commit_sha: &str,
+        relative_path: &str,
+    ) -> Result<Vec<String>, FileBlameError> {
+        backend().files_at_commit(git_root_dir, commit_sha, relative_path)
+    }
+
+    // The remote and branch of a remote-tracking ref, which may not exist,
+    // e.g. ("origin", "release-1.4") for "origin/release-1.4", if there is
+    // such a remote. Names of remotes can contain slashes, so the longest
+    // matching one is used.
+    pub fn remote_tracking_branch(git_root_dir: &str, gitref: &str) -> Option<(String, String)> {
+        let name = gitref
+            .strip_prefix("refs/remotes/")
+            .or_else(|| gitref.strip_prefix("remotes/"))
+            .unwrap_or(gitref);
+        let output =
+            metrics::output(Command::new("git").current_dir(git_root_dir).arg("remote")).ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|remote| Some((remote, name.strip_prefix(remote)?.strip_prefix('/')?)))
+            .filter(|(_, branch)| !branch.is_empty())
+            .max_by_key(|(remote, _)| remote.len())
+            .map(|(remote, branch)| (remote.to_string(), branch.to_string()))
+    }
+
+    // Fetch a branch from a remote into its remote-tracking ref, e.g. when
+    // the ref was pruned, showing git's progress on stderr.
+    pub fn fetch_remote_branch(
+        git_root_dir: &str,
+        remote: &str,
+        branch: &str,
+    ) -> Result<(), FileBlameError> {
+        let refspec = format!("+refs/heads/{}:refs/remotes/{}/{}", branch, remote, branch);
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .args(["fetch", "--progress", remote, &refspec])
+                .stdout(Stdio::inherit())
+                .stderr(Stdio::inherit()),
+        )
+        .map_err(|e| FileBlameError::Unknown(e.to_string()))?;
+        if !output.status.success() {
+            return Err(FileBlameError::Unknown(format!(
+                "Failed to fetch {} from {}",
+                branch, remote
+            )));
+        }
+        Ok(())
+    }
+
+    // Short names of the branches, tags and remote branches of a repository.
+    pub fn ref_names(git_root_dir: &str) -> Vec<String> {
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("for-each-ref")
+                .arg("--format=%(refname:short)")
+                .arg("refs/heads")
+                .arg("refs/tags")
+                .arg("refs/remotes"),
+        );
+        match output {
+            Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(|l| l.to_string())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    // Author time of the oldest root commit of a repository, i.e. when its
+    // history starts, as seconds since the Unix epoch.
+    pub fn history_start(git_root_dir: &str) -> Option<i64> {
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .arg("log")
+                .arg("--max-parents=0")
+                .arg("--format=%at")
+                .arg("HEAD"),
+        )
+        .ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|l| l.parse().ok())
+            .min()
+    }
+
+    // Resolve a ref to the abbreviated hash of the commit it points to.
+    pub fn rev_parse(filepath: &str, rev: &str) -> Result<String, FileBlameError> {
+        let (git_root_dir, _) = FileBlame::try_repo_paths(filepath)?;
+        backend().rev_parse(&git_root_dir, rev)
+    }
+
+    // Check whether a commit is an ancestor of (or the same as) another commit.
+    pub fn is_ancestor(filepath: &str, ancestor: &str, descendant: &str) -> bool {
+        let Some((git_root_dir, _)) = FileBlame::repo_paths(filepath) else {
+            return false;
+        };
+        backend().is_ancestor(&git_root_dir, ancestor, descendant)
+    }
+
+    // Find the best common ancestor of two commits in the repository which
+    // contains a file, e.g. the commit where a branch diverged from another.
+    pub fn merge_base(filepath: &str, a: &str, b: &str) -> Result<String, FileBlameError> {
+        let (git_root_dir, _) = FileBlame::repo_paths(filepath).ok_or(FileBlameError::NotExist)?;
+        backend().merge_base(&git_root_dir, a, b)
     }
 
     // Determine the root directory of a file in a Git repository. We
     // do this by first determining the parent directory containing the file
     // and then running a Git command in that directory to reveal the
-    // root of the repository.
-    fn git_root_dir(path: &PathBuf) -> String {
-        let parent = path.parent().unwrap();
+    // root of the repository. If the file was deleted together with its
+    // parent directory, the nearest directory which still exists is used.
+    // A directory is its own starting point. Outside of a repository, there
+    // is no root.
+    pub fn git_root_dir(path: &Path) -> Option<String> {
+        let parent = path.ancestors().find(|p| p.is_dir())?;
 
-        let root_output = Command::new("git")
-            .current_dir(parent)
-            .arg("rev-parse")
-            .arg("--show-toplevel")
-            .output()
-            .unwrap();
+        let root_output = metrics::output(
+            Command::new("git")
+                .current_dir(parent)
+                .arg("rev-parse")
+                .arg("--show-toplevel"),
+        )
+        .ok()?;
 
+        // Outside of a repository, git fails with "not a git repository".
         if !root_output.status.success() {
-            let stderr = String::from_utf8(root_output.stderr).unwrap();
-            panic!("Error when determining root directory: {}", stderr);
+            return None;
         }
 
-        return String::from_utf8(root_output.stdout)
-            .unwrap()
-            .trim_end()
+        Some(
+            String::from_utf8_lossy(&root_output.stdout)
+                .trim_end()
+                .to_string(),
+        )
+    }
+
+    // Determine the root directory of the Git repository containing a file, and
+    // the path of the file relative to that root.
+    pub fn repo_paths(filepath: &str) -> Option<(String, String)> {
+        FileBlame::try_repo_paths(filepath).ok()
+    }
+
+    // Like repo_paths, but telling paths which don't exist apart from paths
+    // outside of a repository, for reporting them.
+    pub fn try_repo_paths(filepath: &str) -> Result<(String, String), FileBlameError> {
+        let path = FileBlame::absolute_path(filepath).ok_or(FileBlameError::NotExist)?;
+        let git_root_dir = FileBlame::git_root_dir(&path).ok_or(FileBlameError::NotGit)?;
+        let relative_path = path
+            .strip_prefix(&git_root_dir)
+            .ok()
+            .and_then(|p| p.to_str())
+            .ok_or(FileBlameError::NotGit)?
             .to_string();
+        Ok((git_root_dir, relative_path))
+    }
+
+    // The path of a file in a repository relative to the current directory,
+    // like paths given on the command line, or the absolute path if the file
+    // isn't inside the current directory.
+    pub fn display_path(git_root_dir: &str, relative_path: &str) -> String {
+        let path = Path::new(git_root_dir).join(relative_path);
+        std::env::current_dir()
+            .and_then(|d| d.canonicalize())
+            .ok()
+            .and_then(|d| path.strip_prefix(d).ok().map(|p| p.to_path_buf()))
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    // Make a path absolute and resolve symbolic links. Unlike `canonicalize`,
+    // this also works for files which don't exist in the working tree, e.g.
+    // files which were deleted since an older commit, by resolving the nearest
+    // ancestor which does exist and appending the rest of the path to it.
+    pub fn absolute_path(filepath: &str) -> Option<PathBuf> {
+        let mut ancestor = Path::new(filepath);
+        let mut rest = vec![];
+        loop {
+            let existing = if ancestor.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                ancestor
+            };
+            if let Ok(base) = existing.canonicalize() {
+                return Some(rest.iter().rev().fold(base, |path, c| path.join(c)));
+            }
+            rest.push(ancestor.file_name()?);
+            ancestor = ancestor.parent()?;
+        }
+    }
+
+    // Run git blame for a file at a commit, with the options for which lines
+    // are blamed and how. Without a commit, git blames the file in the
+    // working tree.
+    pub fn blame_output(
+        git_root_dir: &str,
+        commit_sha: &str,
+        relative_path: &str,
+        options: &ParseOptions,
+    ) -> Result<String, FileBlameError> {
+        backend().blame(git_root_dir, commit_sha, relative_path, options)
     }
 
     // Construct the blame for a file at a specific commit, and use a
@@ -162,151 +1502,418 @@ impl FileBlame {
         commit_sha: &str,
         commit_cache: &mut HashMap<String, Commit>,
     ) -> Result<FileBlame, FileBlameError> {
-        let path = Path::new(filepath).canonicalize().unwrap();
+        FileBlame::parse_with_options(filepath, commit_sha, commit_cache, &ParseOptions::default())
+    }
 
-        if !path.exists() {
-            return Err(FileBlameError::NotExist);
-        }
+    // Construct the blame for a file at a specific commit, with options
+    // for limiting the work done for large files.
+    pub fn parse_with_options(
+        filepath: &str,
+        commit_sha: &str,
+        commit_cache: &mut HashMap<String, Commit>,
+        options: &ParseOptions,
+    ) -> Result<FileBlame, FileBlameError> {
+        FileBlame::parse_with_progress(
+            filepath,
+            commit_sha,
+            commit_cache,
+            &mut HighlightCache::unbounded(),
+            options,
+            |_| {},
+        )
+    }
 
-        if !path.is_file() {
+    // Construct the blame for a file at a specific commit, reporting how far
+    // along each phase is, e.g. for showing progress while it's loaded in
+    // the background.
+    pub fn parse_with_progress(
+        filepath: &str,
+        commit_sha: &str,
+        commit_cache: &mut HashMap<String, Commit>,
+        highlights: &mut HighlightCache,
+        options: &ParseOptions,
+        mut on_progress: impl FnMut(Progress),
+    ) -> Result<FileBlame, FileBlameError> {
+        // The file doesn't need to exist in the working tree, as long as it
+        // exists at the selected commit.
+        let path = FileBlame::absolute_path(filepath).ok_or(FileBlameError::NotExist)?;
+
+        if path.exists() && !path.is_file() {
             return Err(FileBlameError::NotFile);
         }
 
-        let parent = path.parent().unwrap();
-        let git_root_dir = FileBlame::git_root_dir(&path);
+        // check if the file is in a Git repository, before looking for the
+        // root of the repository
+        if !FileBlame::is_in_work_tree(&path) {
+            return Err(FileBlameError::NotGit);
+        }
+
+        let git_root_dir = FileBlame::git_root_dir(&path).ok_or(FileBlameError::NotGit)?;
         let filename = path.strip_prefix(&git_root_dir).unwrap().to_str().unwrap();
 
-        // check if the file is in a Git repository
-        if !Command::new("git")
-            .current_dir(&git_root_dir)
-            .arg("rev-parse")
-            .arg("--is-inside-work-tree")
-            .output()
-            .unwrap()
-            .status
-            .success()
+        // Files left out of a sparse checkout aren't in the working tree, so
+        // they're blamed as they are at HEAD instead.
+        let commit_sha = if commit_sha == WORKTREE
+            && !path.exists()
+            && FileBlame::is_outside_sparse_checkout(&git_root_dir, filename)
         {
-            return Err(FileBlameError::NotGit);
-        }
+            "HEAD"
+        } else {
+            commit_sha
+        };
 
         // check if the file exists at the selected commit
-        if !FileBlame::exists_at_commit(&filepath, &commit_sha) {
-            return Err(FileBlameError::MissingAtCommit);
+        if commit_sha == WORKTREE {
+            if !path.exists() {
+                return Err(FileBlameError::NotExist);
+            }
+        } else if !FileBlame::exists_at_commit(filepath, commit_sha) {
+            return Err(if path.exists() {
+                FileBlameError::MissingAtCommit
+            } else {
+                FileBlameError::NotExist
+            });
         }
 
-        // fetch git blame for the file and commit
-        let blame_output = Command::new("git")
-            .arg("blame")
-            .current_dir(&git_root_dir)
-            .arg(commit_sha)
-            .arg(filename)
-            .output()
-            .unwrap();
+        // Blames of whole files at commits are read from the disk cache if
+        // it was primed, instead of running git blame.
+        let disk_cache = DiskCache::open(&git_root_dir).filter(|c| c.exists());
+        let cached_blame = disk_cache
+            .as_ref()
+            .filter(|_| commit_sha != WORKTREE && options.is_plain_blame())
+            .and_then(|cache| {
+                let full_sha = DiskCache::resolve(&git_root_dir, commit_sha)?;
+                cache.blame(&full_sha, filename)
+            });
+        on_progress(Progress::start(LoadPhase::Blaming));
+        let blame_output = match cached_blame {
+            Some(output) => output,
+            None => FileBlame::blame_output(&git_root_dir, commit_sha, filename, options)?,
+        };
+        let mut porcelain_lines = parse_porcelain(&blame_output);
+        // Git blames the working tree in UTF-8, but versions committed in
+        // the file's working tree encoding need decoding.
+        if commit_sha != WORKTREE {
+            if let Some(encoding) = Encoding::for_path(&git_root_dir, filename) {
+                decode_contents(
+                    &git_root_dir,
+                    commit_sha,
+                    filename,
+                    encoding,
+                    &mut porcelain_lines,
+                );
+            }
+        }
 
-        if !blame_output.status.success() {
-            let stderr = String::from_utf8(blame_output.stderr).unwrap();
-            return Err(FileBlameError::Unknown(stderr));
+        // Lines which were blamed past ignored commits are found by blaming
+        // the file again without ignoring any, and are marked like git blame
+        // does with blame.markIgnoredLines.
+        let mut skipped_lines = vec![];
+        if options.ignores_revs() {
+            let unignored = ParseOptions {
+                ignore_revs_file: None,
+                ignore_revs: Vec::new(),
+                ..options.clone()
+            };
+            let output = FileBlame::blame_output(&git_root_dir, commit_sha, filename, &unignored)?;
+            let blamed: HashMap<usize, &str> = porcelain_lines
+                .iter()
+                .map(|line| (line.line_number, line.commit.sha.as_str()))
+                .collect();
+            skipped_lines = parse_porcelain(&output)
+                .into_iter()
+                .filter(|line| {
+                    blamed
+                        .get(&line.line_number)
+                        .is_some_and(|&sha| sha != line.commit.sha)
+                })
+                .collect();
         }
+        let mut skipped_commits: HashMap<usize, String> = skipped_lines
+            .iter()
+            .map(|line| (line.line_number, line.commit.sha.clone()))
+            .collect();
+
+        let highlighted = porcelain_lines.len() <= options.highlight_line_limit;
+        let lfs_pointer = LfsPointer::parse(porcelain_lines.iter().map(|l| l.contents.as_str()));
+
+        // The highlighted contents are reused if the same lines of the file
+        // were highlighted before.
+        let highlight_key = highlighted.then(|| highlight_key(filename, options, &porcelain_lines));
+        let cached_contents = highlight_key.and_then(|key| highlights.get(&key).cloned());
 
         // Prepare syntax highlighter
         let theme_set;
         let mut highlighter = None;
         let mut syntax_set = None;
-        let extension = path.extension();
-
-        match extension {
-            None => {}
-            Some(ext) => {
-                syntax_set = Some(SyntaxSet::load_defaults_newlines());
-                theme_set = Some(ThemeSet::load_defaults());
-                let syntax = syntax_set
-                    .as_mut()
-                    .unwrap()
-                    .find_syntax_by_extension(ext.to_str().unwrap())
-                    .unwrap();
-                highlighter = Some(HighlightLines::new(
-                    syntax,
-                    &theme_set.as_ref().unwrap().themes["base16-ocean.dark"],
-                ));
-            }
+        if highlighted && cached_contents.is_none() {
+            // Lines are highlighted without their newlines, which the
+            // syntaxes for lines with newlines need to end comments.
+            syntax_set = Some(SyntaxSet::load_defaults_nonewlines());
+            theme_set = Some(ThemeSet::load_defaults());
+            // The first line can only tell the syntax, e.g. with a shebang,
+            // if it's the first line of the file.
+            let first_line = porcelain_lines
+                .first()
+                .filter(|line| line.line_number == 1)
+                .map(|line| line.contents.as_str());
+            let syntax = find_syntax(syntax_set.as_ref().unwrap(), &path, first_line);
+            let themes = &theme_set.as_ref().unwrap().themes;
+            let theme = themes
+                .get(&options.syntax_theme)
+                .unwrap_or(&themes[DEFAULT_SYNTAX_THEME]);
+            highlighter = Some(HighlightLines::new(syntax, theme));
         }
 
-        // Parse each line of blame output and apply syntax highlighting
-        let blame_output = String::from_utf8(blame_output.stdout).unwrap();
-        let blame_lines = blame_output.lines();
-        let mut parsed_blame_lines: Vec<BlameLine> = vec![];
-
-        for blame_line in blame_lines {
-            let pattern = Regex::new(BLAME_LINE_REGEX).unwrap();
-            let captures = pattern.captures(blame_line).unwrap();
-
-            let commit = captures.get(1).unwrap().as_str();
-            let author = captures.get(2).unwrap().as_str();
-            let timestamp = captures.get(3).unwrap().as_str();
-            let line_number = captures.get(4).unwrap().as_str();
-            let mut line_contents = captures.get(5).unwrap().as_str().to_owned();
-
-            if highlighter.is_some() {
-                let ranges = highlighter
-                    .as_mut()
-                    .unwrap()
-                    .highlight_line(&line_contents, &(syntax_set.as_mut().unwrap()))
-                    .unwrap();
-                line_contents = as_24_bit_terminal_escaped(&ranges[..], false);
+        on_progress(Progress::start(LoadPhase::LoadingCommits));
+        // The commit information is in the blame output, apart from the
+        // parents and trailers, which are loaded at once for all commits which aren't
+        // cached yet. The skipped commits are cached too, for showing them.
+        let mut missing: Vec<&Commit> = vec![];
+        let mut missing_shas: HashSet<&str> = HashSet::new();
+        for line in porcelain_lines.iter().chain(&skipped_lines) {
+            let sha = line.commit.sha.as_str();
+            let cached = commit_cache.contains_key(sha) || missing_shas.contains(sha);
+            metrics::record_commit_cache_lookup(cached);
+            if cached {
+                continue;
             }
 
-            // if commit starts with ^ it is a boundary commit
-            // so we should remove that character
-            let commit = commit.trim_start_matches("^");
-
-            // Check the commit cache first to see if we've already fetched
-            // the information for this commit. If not, then fetch the info
-            // and store it in the cache.
-            if !commit_cache.contains_key(commit) {
-                let output = String::from_utf8(
-                    Command::new("git")
-                        .current_dir(parent)
-                        .arg("show")
-                        .arg(commit)
-                        .arg("--pretty=format:%p-%s")
-                        .arg("--no-patch")
-                        .output()
-                        .expect("failed to execute process")
-                        .stdout,
-                )
-                .unwrap();
-
-                let (parent_commit, commit_message) = output.split_once("-").unwrap();
-
-                let parent_commit_sha = if parent_commit.is_empty() {
-                    None
-                } else {
-                    Some(parent_commit.to_owned())
+            // Uncommitted lines are blamed on a commit with an all zero
+            // hash, which git log knows nothing about.
+            if line.commit.is_uncommitted() {
+                let info = Commit {
+                    author_email: String::new(),
+                    commit_message: "Not committed yet".to_owned(),
+                    ..line.commit.clone()
                 };
+                commit_cache.insert(sha.to_owned(), info);
+                continue;
+            }
 
-                commit_cache.insert(
-                    commit.to_owned(),
-                    Commit {
-                        author: author.to_owned(),
-                        commit_message: commit_message.to_owned(),
-                        timestamp: timestamp.to_owned(),
-                        sha: commit.to_owned(),
-                        parent_commit_sha,
-                    },
-                );
+            match disk_cache.as_ref().and_then(|cache| cache.commit(sha)) {
+                Some(info) => {
+                    commit_cache.insert(sha.to_owned(), info);
+                }
+                None => {
+                    missing_shas.insert(sha);
+                    missing.push(&line.commit);
+                }
             }
+        }
+        let mut missing: Vec<Commit> = missing.into_iter().cloned().collect();
+        Commit::complete(&git_root_dir, &mut missing);
+        for commit in missing {
+            commit_cache.insert(commit.sha.clone(), commit);
+        }
+
+        // Apply syntax highlighting to each line
+        let total_lines = porcelain_lines.len();
+        let mut parsed_blame_lines: Vec<BlameLine> = vec![];
+        let mut cached_contents = cached_contents.map(Vec::into_iter);
+        for (i, line) in porcelain_lines.into_iter().enumerate() {
+            // Progress is only reported when the percentage changes.
+            if highlighter.is_some() && i * 100 / total_lines != (i + 1) * 100 / total_lines {
+                on_progress(Progress {
+                    phase: LoadPhase::Highlighting,
+                    percent: ((i + 1) * 100 / total_lines) as u8,
+                });
+            }
+
+            // Very long lines, e.g. of minified files, are only highlighted
+            // and shown up to the limit, followed by a truncation marker.
+            let (shown, hidden) = truncate_line(&line.contents, options.max_line_length);
+            let line_contents = match cached_contents.as_mut().and_then(Iterator::next) {
+                Some(contents) => contents,
+                None => {
+                    let mut line_contents = shown.to_owned();
+                    if let Some(highlighter) = highlighter.as_mut() {
+                        let ranges = highlighter
+                            .highlight_line(&line_contents, syntax_set.as_ref().unwrap())
+                            .unwrap();
+                        line_contents = as_24_bit_terminal_escaped(&ranges[..], false);
+                    }
+                    if hidden > 0 {
+                        line_contents.push_str(&format!("\x1b[0m … [+{} chars]", hidden));
+                    }
+                    line_contents
+                }
+            };
 
             parsed_blame_lines.push(BlameLine {
-                line_number: line_number.to_owned(),
+                line_number: line.line_number.to_string(),
                 contents: line_contents,
-                commit_sha: commit.to_owned(),
+                plain_contents: line.contents,
+                commit_sha: line.commit.sha,
+                truncated: hidden > 0,
+                previous_sha: line.previous_sha,
+                previous_path: line.previous_path,
+                original_path: Some(line.original_path).filter(|p| p != filename),
+                original_line: line.original_line,
+                skipped_commit: skipped_commits.remove(&line.line_number),
             });
         }
 
+        if let (Some(key), Some(_)) = (highlight_key, highlighter) {
+            let contents = parsed_blame_lines.iter().map(|l| l.contents.clone());
+            highlights.put(key, contents.collect());
+        }
+
         Ok(FileBlame {
             commit_sha: commit_sha.to_owned(),
             filepath: filepath.to_owned(),
             blame_lines: parsed_blame_lines,
+            highlighted,
+            lfs_pointer,
         })
     }
 }
+
+// Replace the contents of blamed lines with the lines of the file decoded
+// from its working tree encoding, if the file was committed in it. Lines are
+// matched by their numbers, as long as the file has as many lines once it's
+// decoded as git found in it.
+fn decode_contents(
+    git_root_dir: &str,
+    commit_sha: &str,
+    relative_path: &str,
+    encoding: Encoding,
+    lines: &mut [PorcelainLine],
+) {
+    let Some(contents) = backend().file_contents(git_root_dir, commit_sha, relative_path) else {
+        return;
+    };
+    if !Encoding::is_encoded(&contents) {
+        return;
+    }
+    let decoded = encoding.decode(&contents);
+    let decoded: Vec<&str> = decoded.split('\n').collect();
+    if decoded.len() != contents.split(|&b| b == b'\n').count() {
+        return;
+    }
+    for line in lines {
+        if let Some(contents) = line.line_number.checked_sub(1).and_then(|i| decoded.get(i)) {
+            line.contents = contents.to_string();
+        }
+    }
+}
+
+// Split a line after the given number of characters, returning the part to
+// show and the number of characters which are cut off.
+fn truncate_line(line: &str, max_chars: usize) -> (&str, usize) {
+    match line.char_indices().nth(max_chars) {
+        Some((end, _)) => (&line[..end], line[end..].chars().count()),
+        None => (line, 0),
+    }
+}
+
+// Parse the trailers of a commit message, as output by git's
+// `%(trailers:only,unfold)` format, i.e. one "Key: value" per line.
+fn parse_trailers(trailers: &str) -> BTreeMap<String, Vec<String>> {
+    let mut parsed: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (key, value) in trailers.lines().filter_map(|l| l.split_once(':')) {
+        parsed
+            .entry(key.trim().to_string())
+            .or_default()
+            .push(value.trim().to_string());
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA: &str = "1e1d1c3c8b7d6f5e4a3b2c1d0e9f8a7b6c5d4e3f";
+    const PREVIOUS: &str = "9f8e7d6c5b4a3f2e1d0c9b8a7f6e5d4c3b2a1f0e";
+
+    fn header(sha: &str, original_line: usize, line: usize, previous: Option<&str>) -> String {
+        let mut header = format!(
+            "{} {} {} 1\n\
+             author John Doe\n\
+             author-mail <john@example.com>\n\
+             author-time 1546358400\n\
+             author-tz -0400\n\
+             summary Fix the thing\n",
+            sha, original_line, line
+        );
+        if let Some(previous) = previous {
+            header.push_str(&format!("previous {}\n", previous));
+        }
+        header
+    }
+
+    #[test]
+    fn parses_lines_and_commits() {
+        let output = format!(
+            "{}filename src/main.rs\n\tfn main() {{\n{}filename src/lib.rs\n\t}}\n",
+            header(SHA, 140, 1, Some(&format!("{} src/old.rs", PREVIOUS))),
+            header(SHA, 7, 2, None),
+        );
+        let lines = parse_porcelain(&output);
+        assert_eq!(lines.len(), 2);
+
+        let first = &lines[0];
+        assert_eq!(first.commit.sha, SHA);
+        assert_eq!(first.commit.author, "John Doe");
+        assert_eq!(first.commit.author_email, "john@example.com");
+        assert_eq!(first.commit.time, 1546358400);
+        assert_eq!(first.commit.commit_message, "Fix the thing");
+        assert_eq!(first.line_number, 1);
+        assert_eq!(first.contents, "fn main() {");
+        assert_eq!(first.original_path, "src/main.rs");
+        assert_eq!(first.original_line, 140);
+
+        let second = &lines[1];
+        assert_eq!(second.line_number, 2);
+        assert_eq!(second.contents, "}");
+        assert_eq!(second.original_path, "src/lib.rs");
+        assert_eq!(second.original_line, 7);
+    }
+
+    // The previous commit and path are per file, so they're kept with the
+    // line and not with the commit, which is cached across files.
+    #[test]
+    fn keeps_previous_commit_on_the_line() {
+        let output = format!(
+            "{}filename b.txt\n\tchanged\n{}filename b.txt\n\tadded\n",
+            header(SHA, 1, 1, Some(&format!("{} a.txt", PREVIOUS))),
+            header(SHA, 2, 2, None),
+        );
+        let lines = parse_porcelain(&output);
+        assert_eq!(lines[0].previous_sha.as_deref(), Some(PREVIOUS));
+        assert_eq!(lines[0].previous_path.as_deref(), Some("a.txt"));
+        assert_eq!(lines[1].previous_sha, None);
+        assert_eq!(lines[1].previous_path, None);
+        assert!(lines.iter().all(|l| l.commit.parent_commit_sha.is_none()));
+    }
+
+    #[test]
+    fn keeps_tabs_and_empty_lines() {
+        let output = format!(
+            "{}filename a.txt\n\t\tindented\n{}filename a.txt\n\t\n",
+            header(SHA, 1, 1, None),
+            header(SHA, 2, 2, None),
+        );
+        let lines = parse_porcelain(&output);
+        assert_eq!(lines[0].contents, "\tindented");
+        assert_eq!(lines[1].contents, "");
+    }
+
+    #[test]
+    fn parses_sha256_hashes() {
+        let sha = "a".repeat(64);
+        let output = format!("{}filename a.txt\n\tline\n", header(&sha, 3, 4, None));
+        let lines = parse_porcelain(&output);
+        assert_eq!(lines[0].commit.sha, sha);
+        assert_eq!(lines[0].original_line, 3);
+        assert_eq!(lines[0].line_number, 4);
+    }
+
+    #[test]
+    fn takes_the_first_parent() {
+        assert_eq!(first_parent(""), None);
+        assert_eq!(first_parent("abc\n"), Some("abc".to_string()));
+        assert_eq!(first_parent("abc def"), Some("abc".to_string()));
+    }
+}
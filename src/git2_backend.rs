@@ -0,0 +1,512 @@
+use crate::age;
+use crate::file_blame::{
+    CliBackend, Commit, CommitDetails, CopyDetection, FileBlameError, GitBackend,
+    ParentAndTrailers, ParseOptions, WORKTREE,
+};
+use crate::partial_clone::Promisor;
+use git2::{BlameOptions, Delta, DiffFindOptions, Oid, Repository, Signature, Time};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Backend which looks up blames, commits and files in-process with libgit2,
+// instead of running git for each lookup. Blames which libgit2 can't do the
+// way git does, i.e. with ignored commits, in reverse, with copy detection
+// or in a partial clone, where objects would have to be fetched, are still
+// done by running git.
+pub struct Git2Backend;
+
+impl GitBackend for Git2Backend {
+    fn blame(
+        &self,
+        git_root_dir: &str,
+        commit_sha: &str,
+        relative_path: &str,
+        options: &ParseOptions,
+    ) -> Result<String, FileBlameError> {
+        let in_process = !options.ignores_revs()
+            && options.reverse_until.is_none()
+            && options.copy_detection == CopyDetection::Off
+            && Promisor::detect(git_root_dir).is_none();
+        // Anything libgit2 fails at, e.g. repositories with SHA-256 hashes,
+        // is left to git, which also reports the errors the way they're
+        // shown elsewhere.
+        if in_process {
+            if let Ok(output) = blame(git_root_dir, commit_sha, relative_path, options) {
+                return Ok(output);
+            }
+        }
+        CliBackend.blame(git_root_dir, commit_sha, relative_path, options)
+    }
+
+    fn commit(&self, git_root_dir: &str, sha: &str) -> Commit {
+        let commit = || -> Result<Commit, git2::Error> {
+            let repo = Repository::open(git_root_dir)?;
+            let commit = repo.revparse_single(sha)?.peel_to_commit()?;
+            let author = commit.author_with_mailmap(&repo.mailmap()?)?;
+            let time = commit.author().when();
+            let info = Commit {
+                author: author.name().unwrap_or_default().to_owned(),
+                // Like git's %ae, the email isn't mapped.
+                author_email: commit.author().email().unwrap_or_default().to_owned(),
+                commit_message: commit.summary().unwrap_or_default().to_owned(),
+                timestamp: age::format_timestamp(time.seconds(), &offset(time)),
+                time: time.seconds(),
+                trailers: trailers(commit.message().unwrap_or_default()),
+                sha: sha.to_owned(),
+                parent_commit_sha: commit.parent_id(0).ok().map(|id| id.to_string()),
+            };
+            Ok(info)
+        };
+        commit().unwrap_or_else(|_| Commit {
+            sha: sha.to_owned(),
+            ..Commit::default()
+        })
+    }
+
+    fn commit_details(&self, git_root_dir: &str, sha: &str) -> CommitDetails {
+        let details = || -> Result<CommitDetails, git2::Error> {
+            let repo = Repository::open(git_root_dir)?;
+            let commit = repo.revparse_single(sha)?.peel_to_commit()?;
+            let parent_tree = commit.parent(0).ok().map(|p| p.tree()).transpose()?;
+            let mut diff =
+                repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+            diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+            // libgit2 doesn't tell how similar renamed files are, so their
+            // status is just R instead of e.g. R100.
+            let changed_files = diff
+                .deltas()
+                .filter_map(|delta| {
+                    let path = |file: git2::DiffFile| file.path()?.to_str().map(str::to_string);
+                    let status = match delta.status() {
+                        Delta::Added => "A",
+                        Delta::Deleted => "D",
+                        Delta::Renamed => "R",
+                        Delta::Copied => "C",
+                        Delta::Typechange => "T",
+                        _ => "M",
+                    };
+                    let path = match delta.status() {
+                        Delta::Deleted => path(delta.old_file())?,
+                        Delta::Renamed | Delta::Copied => {
+                            format!("{} -> {}", path(delta.old_file())?, path(delta.new_file())?)
+                        }
+                        _ => path(delta.new_file())?,
+                    };
+                    Some((status.to_string(), path))
+                })
+                .collect();
+            Ok(CommitDetails {
+                body: commit.body().unwrap_or_default().trim().to_string(),
+                changed_files,
+            })
+        };
+        details().unwrap_or_default()
+    }
+
+    fn parents_and_trailers(
+        &self,
+        git_root_dir: &str,
+        shas: &[&str],
+    ) -> HashMap<String, ParentAndTrailers> {
+        let Ok(repo) = Repository::open(git_root_dir) else {
+            return HashMap::new();
+        };
+        shas.iter()
+            .filter_map(|&sha| {
+                let commit = repo.revparse_single(sha).ok()?.peel_to_commit().ok()?;
+                let parent = commit.parent_id(0).ok().map(|id| id.to_string());
+                let trailers = trailers(commit.message().unwrap_or_default());
+                Some((commit.id().to_string(), (parent, trailers)))
+            })
+            .collect()
+    }
+
+    fn exists_at_commit(&self, git_root_dir: &str, commit_sha: &str, relative_path: &str) -> bool {
+        Repository::open(git_root_dir)
+            .and_then(|repo| {
+                let tree = repo.revparse_single(commit_sha)?.peel_to_tree()?;
+                tree.get_path(Path::new(relative_path)).map(|_| ())
+            })
+            .is_ok()
+    }
+
+    fn rev_parse(&self, git_root_dir: &str, rev: &str) -> Result<String, FileBlameError> {
+        let short_id = || -> Result<String, git2::Error> {
+            let repo = Repository::open(git_root_dir)?;
+            let commit = repo.revparse_single(rev)?.peel_to_commit()?;
+            let short_id = commit.as_object().short_id()?;
+            Ok(short_id.as_str().unwrap_or_default().to_string())
+        };
+        short_id().map_err(|e| FileBlameError::Unknown(e.message().to_string()))
+    }
+
+    fn file_contents(
+        &self,
+        git_root_dir: &str,
+        commit_sha: &str,
+        relative_path: &str,
+    ) -> Option<Vec<u8>> {
+        let repo = Repository::open(git_root_dir).ok()?;
+        let tree = repo.revparse_single(commit_sha).ok()?.peel_to_tree().ok()?;
+        let entry = tree.get_path(Path::new(relative_path)).ok()?;
+        let blob = repo.find_blob(entry.id()).ok()?;
+        Some(blob.content().to_vec())
+    }
+
+    fn is_ancestor(&self, git_root_dir: &str, ancestor: &str, descendant: &str) -> bool {
+        let is_ancestor = || -> Result<bool, git2::Error> {
+            let repo = Repository::open(git_root_dir)?;
+            let ancestor = repo.revparse_single(ancestor)?.peel_to_commit()?.id();
+            let descendant = repo.revparse_single(descendant)?.peel_to_commit()?.id();
+            Ok(ancestor == descendant || repo.graph_descendant_of(descendant, ancestor)?)
+        };
+        is_ancestor().unwrap_or(false)
+    }
+
+    fn merge_base(&self, git_root_dir: &str, a: &str, b: &str) -> Result<String, FileBlameError> {
+        let merge_base = || -> Result<Oid, git2::Error> {
+            let repo = Repository::open(git_root_dir)?;
+            let a = repo.revparse_single(a)?.peel_to_commit()?.id();
+            let b = repo.revparse_single(b)?.peel_to_commit()?.id();
+            repo.merge_base(a, b)
+        };
+        merge_base()
+            .map(|id| id.to_string())
+            .map_err(|e| FileBlameError::Unknown(e.message().to_string()))
+    }
+
+    fn files_at_commit(
+        &self,
+        git_root_dir: &str,
+        commit_sha: &str,
+        relative_path: &str,
+    ) -> Result<Vec<String>, FileBlameError> {
+        let files = || -> Result<Vec<String>, git2::Error> {
+            let repo = Repository::open(git_root_dir)?;
+            let tree = repo.revparse_single(commit_sha)?.peel_to_tree()?;
+            if relative_path.is_empty() {
+                return Ok(blobs(&repo, &tree, ""));
+            }
+            let entry = tree.get_path(Path::new(relative_path))?;
+            let files = match entry.to_object(&repo)?.into_tree() {
+                Ok(subtree) => blobs(&repo, &subtree, &format!("{}/", relative_path)),
+                Err(_) => vec![relative_path.to_string()],
+            };
+            Ok(files)
+        };
+        let files = files().map_err(|e| FileBlameError::Unknown(e.message().to_string()))?;
+        if files.is_empty() {
+            return Err(FileBlameError::MissingAtCommit);
+        }
+        Ok(files)
+    }
+}
+
+// Paths of the files in a tree and its subtrees, after the given prefix.
+fn blobs(repo: &Repository, tree: &git2::Tree, prefix: &str) -> Vec<String> {
+    let mut files = vec![];
+    for entry in tree.iter() {
+        let path = format!("{}{}", prefix, entry.name().unwrap_or_default());
+        match entry.to_object(repo).ok().and_then(|o| o.into_tree().ok()) {
+            Some(subtree) => files.extend(blobs(repo, &subtree, &format!("{}/", path))),
+            None => files.push(path),
+        }
+    }
+    files
+}
+
+// Blame a file like `git blame --line-porcelain`, so that the output is
+// parsed the same way as git's.
+fn blame(
+    git_root_dir: &str,
+    commit_sha: &str,
+    relative_path: &str,
+    options: &ParseOptions,
+) -> Result<String, git2::Error> {
+    let repo = Repository::open(git_root_dir)?;
+    let path = Path::new(relative_path);
+    let mut blame_options = BlameOptions::new();
+    blame_options.use_mailmap(true);
+
+    // The working tree is blamed by blaming HEAD and then the differences
+    // to the file in the working tree. All of its lines are blamed, since a
+    // range of lines in the working tree isn't the same range at HEAD.
+    let contents = if commit_sha == WORKTREE {
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| git2::Error::from_str("bare repository"))?;
+        std::fs::read(workdir.join(path)).map_err(|e| git2::Error::from_str(&e.to_string()))?
+    } else {
+        let commit = repo.revparse_single(commit_sha)?.peel_to_commit()?;
+        blame_options.newest_commit(commit.id());
+        if let Some((start, end)) = options.line_range {
+            blame_options.min_line(start).max_line(end);
+        }
+        let entry = commit.tree()?.get_path(path)?;
+        repo.find_blob(entry.id())?.content().to_vec()
+    };
+    let blame = repo.blame_file(path, Some(&mut blame_options))?;
+    let blame = if commit_sha == WORKTREE {
+        blame.blame_buffer(&contents)?
+    } else {
+        blame
+    };
+
+    let contents = String::from_utf8_lossy(&contents);
+    let mut lines: Vec<&str> = contents.split('\n').collect();
+    if lines.last() == Some(&"") {
+        lines.pop();
+    }
+    let (start, end) = options.line_range.unwrap_or((1, lines.len()));
+
+    let mailmap = repo.mailmap()?;
+    let mut previous: HashMap<(Oid, String), Option<(Oid, String)>> = HashMap::new();
+    let mut output = String::new();
+    for line_number in start..=end.min(lines.len()) {
+        let hunk = blame
+            .get_line(line_number)
+            .ok_or_else(|| git2::Error::from_str("line missing from blame"))?;
+        let original_path = hunk
+            .path()
+            .and_then(Path::to_str)
+            .unwrap_or(relative_path)
+            .to_string();
+        let original_line = hunk.orig_start_line() + line_number - hunk.final_start_line();
+
+        // Uncommitted lines are blamed on an all zero hash, like git does,
+        // and were changed from HEAD.
+        let id = hunk.final_commit_id();
+        let (author, time, summary, previous) = if id.is_zero() {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64);
+            let head = repo.head().ok().and_then(|h| h.target());
+            (
+                Signature::new("Not Committed Yet", "not.committed.yet", &Time::new(now, 0))?,
+                Time::new(now, 0),
+                format!("Version of {} from {}", relative_path, relative_path),
+                head.map(|head| (head, relative_path.to_string())),
+            )
+        } else {
+            let commit = repo.find_commit(id)?;
+            let author = commit.author_with_mailmap(&mailmap)?;
+            let time = author.when();
+            let previous = previous
+                .entry((id, original_path.clone()))
+                .or_insert_with(|| previous_version(&repo, &commit, &original_path))
+                .clone();
+            (
+                author,
+                time,
+                commit.summary().unwrap_or_default().to_string(),
+                previous,
+            )
+        };
+
+        let _ = writeln!(output, "{} {} {} 1", id, original_line, line_number);
+        let _ = writeln!(output, "author {}", author.name().unwrap_or_default());
+        let _ = writeln!(
+            output,
+            "author-mail <{}>",
+            author.email().unwrap_or_default()
+        );
+        let _ = writeln!(output, "author-time {}", time.seconds());
+        let _ = writeln!(output, "author-tz {}", offset(time));
+        let _ = writeln!(output, "summary {}", summary);
+        if let Some((sha, path)) = previous {
+            let _ = writeln!(output, "previous {} {}", sha, path);
+        }
+        let _ = writeln!(output, "filename {}", original_path);
+        let _ = writeln!(output, "\t{}", lines[line_number - 1]);
+    }
+    Ok(output)
+}
+
+// The first parent of a commit and the path of a file there, if the file
+// was in it, following renames like git blame does.
+fn previous_version(repo: &Repository, commit: &git2::Commit, path: &str) -> Option<(Oid, String)> {
+    let parent = commit.parent(0).ok()?;
+    let parent_tree = parent.tree().ok()?;
+    if parent_tree.get_path(Path::new(path)).is_ok() {
+        return Some((parent.id(), path.to_string()));
+    }
+    let mut diff = repo
+        .diff_tree_to_tree(Some(&parent_tree), Some(&commit.tree().ok()?), None)
+        .ok()?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))
+        .ok()?;
+    let renamed = diff.deltas().find(|delta| {
+        delta.status() == Delta::Renamed
+            && delta.new_file().path().and_then(Path::to_str) == Some(path)
+    })?;
+    let old_path = renamed.old_file().path()?.to_str()?.to_string();
+    Some((parent.id(), old_path))
+}
+
+// The time zone offset of a time, like "-0400".
+fn offset(time: Time) -> String {
+    let minutes = time.offset_minutes().abs();
+    format!("{}{:02}{:02}", time.sign(), minutes / 60, minutes % 60)
+}
+
+// The trailers of a commit message, like parsed from git's
+// `%(trailers:only,unfold)` format.
+fn trailers(message: &str) -> BTreeMap<String, Vec<String>> {
+    let mut parsed: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let Ok(trailers) = git2::message_trailers_strs(message) else {
+        return parsed;
+    };
+    for (key, value) in trailers.iter() {
+        parsed
+            .entry(key.trim().to_string())
+            .or_default()
+            .push(value.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process::Command;
+
+    // A repository with a file which was renamed and changed by another
+    // author, with a trailer, and changed again in the working tree.
+    fn repo(name: &str) -> String {
+        let dir = env::temp_dir().join(format!("blame-git2-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .current_dir(&dir)
+                .args(["-c", "user.name=Amy", "-c", "user.email=amy@example.com"])
+                .args(args)
+                .env("GIT_AUTHOR_DATE", "1546358400 -0400")
+                .env("GIT_COMMITTER_DATE", "1546358400 -0400")
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(&["init", "-q"]);
+        fs::write(dir.join("old.txt"), "one\ntwo\nthree\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "Add the file"]);
+        git(&["mv", "old.txt", "new.txt"]);
+        git(&["commit", "-q", "-m", "Rename the file"]);
+        fs::write(dir.join("new.txt"), "one\n2\nthree\n").unwrap();
+        fs::write(dir.join(".mailmap"), "Bobby <bob@example.com>\n").unwrap();
+        git(&["add", "."]);
+        git(&[
+            "commit",
+            "-q",
+            "--author",
+            "Bob <bob@example.com>",
+            "-m",
+            "Change the second line\n\nBecause.\n\nFixes: #12",
+        ]);
+        fs::write(dir.join("new.txt"), "one\n2\nthree\nfour\n").unwrap();
+        dir.display().to_string()
+    }
+
+    #[test]
+    fn blames_like_git() {
+        let dir = repo("blame");
+        for range in [None, Some((2, 3))] {
+            let options = ParseOptions {
+                line_range: range,
+                ..ParseOptions::default()
+            };
+            let git = CliBackend.blame(&dir, "HEAD", "new.txt", &options).unwrap();
+            // Boundary and committer lines aren't parsed, so they're left out.
+            let git: Vec<&str> = git
+                .lines()
+                .filter(|l| !l.starts_with("committer") && *l != "boundary")
+                .collect();
+            let git2 = blame(&dir, "HEAD", "new.txt", &options).unwrap();
+            assert_eq!(git2.lines().collect::<Vec<_>>(), git);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn blames_the_working_tree() {
+        let dir = repo("worktree");
+        let output = blame(&dir, WORKTREE, "new.txt", &ParseOptions::default()).unwrap();
+        let headers: Vec<&str> = output
+            .lines()
+            .filter(|l| l.len() > 40 && l.chars().take(40).all(|c| c.is_ascii_hexdigit()))
+            .collect();
+        assert_eq!(headers.len(), 4);
+        assert!(headers[3].starts_with(&format!("{} 4 4", "0".repeat(40))));
+        assert!(output.contains("author Not Committed Yet\n"));
+        assert!(output.ends_with("\tfour\n"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn loads_commits_like_git() {
+        let dir = repo("commits");
+        for sha in ["HEAD", "HEAD~1", "HEAD~2", "unknown"] {
+            assert_eq!(Git2Backend.commit(&dir, sha), CliBackend.commit(&dir, sha));
+        }
+        let shas = ["HEAD", "HEAD~2"];
+        let full_shas: Vec<String> = shas
+            .iter()
+            .map(|sha| Git2Backend.merge_base(&dir, sha, sha).unwrap())
+            .collect();
+        let full_shas: Vec<&str> = full_shas.iter().map(String::as_str).collect();
+        assert_eq!(
+            Git2Backend.parents_and_trailers(&dir, &full_shas),
+            CliBackend.parents_and_trailers(&dir, &full_shas)
+        );
+        let details = Git2Backend.commit_details(&dir, "HEAD");
+        assert_eq!(details, CliBackend.commit_details(&dir, "HEAD"));
+        let renamed = Git2Backend.commit_details(&dir, "HEAD~1");
+        assert_eq!(
+            renamed.changed_files,
+            vec![("R".to_string(), "old.txt -> new.txt".to_string())]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn looks_up_files_and_refs_like_git() {
+        let dir = repo("files");
+        for (sha, path) in [
+            ("HEAD", "new.txt"),
+            ("HEAD~2", "new.txt"),
+            ("HEAD~2", "old.txt"),
+        ] {
+            assert_eq!(
+                Git2Backend.exists_at_commit(&dir, sha, path),
+                CliBackend.exists_at_commit(&dir, sha, path)
+            );
+            assert_eq!(
+                Git2Backend.file_contents(&dir, sha, path),
+                CliBackend.file_contents(&dir, sha, path)
+            );
+        }
+        for path in ["", "new.txt"] {
+            assert_eq!(
+                Git2Backend.files_at_commit(&dir, "HEAD", path).unwrap(),
+                CliBackend.files_at_commit(&dir, "HEAD", path).unwrap()
+            );
+        }
+        assert_eq!(
+            Git2Backend.rev_parse(&dir, "HEAD~1").unwrap(),
+            CliBackend.rev_parse(&dir, "HEAD~1").unwrap()
+        );
+        assert!(Git2Backend.rev_parse(&dir, "unknown").is_err());
+        assert!(Git2Backend.is_ancestor(&dir, "HEAD~2", "HEAD"));
+        assert!(!Git2Backend.is_ancestor(&dir, "HEAD", "HEAD~2"));
+        assert_eq!(
+            Git2Backend.merge_base(&dir, "HEAD", "HEAD~1").unwrap(),
+            CliBackend.merge_base(&dir, "HEAD", "HEAD~1").unwrap()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
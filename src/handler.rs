@@ -1,28 +1,672 @@
-use crate::app::{App, AppResult};
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crate::action::Action;
+use crate::app::{App, AppResult, CommitAction, Pane, Popup};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 
 /// Handles the key events and updates the state of [`App`].
 pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
-    match key_event.code {
-        // Exit application on `ESC` or `q`
-        KeyCode::Esc | KeyCode::Char('q') => {
+    // Exit application on `Ctrl-C`, even when a popup is open
+    if let KeyCode::Char('c') | KeyCode::Char('C') = key_event.code {
+        if key_event.modifiers == KeyModifiers::CONTROL {
+            app.quit();
+            return Ok(());
+        }
+    }
+
+    // While the blame is loading, there's nothing to act on but quitting.
+    if app.is_loading() {
+        if let KeyCode::Esc | KeyCode::Char('q') = key_event.code {
             app.quit();
         }
-        // Exit application on `Ctrl-C`
-        KeyCode::Char('c') | KeyCode::Char('C') => {
-            if key_event.modifiers == KeyModifiers::CONTROL {
-                app.quit();
+        return Ok(());
+    }
+
+    if let Some(popup) = app.popup.clone() {
+        return handle_popup_key_events(key_event, &popup, app);
+    }
+
+    if app.focus == Pane::Tree {
+        return handle_tree_key_events(key_event, app);
+    }
+
+    if app.focus == Pane::Diff {
+        return handle_diff_key_events(key_event, app);
+    }
+
+    if let Some(action) = action_for_key(key_event, app) {
+        app.perform(action);
+    }
+    Ok(())
+}
+
+// Actions which only move around the blame, without opening popups or
+// panes, or running commands, for blames embedded in other applications.
+const EMBEDDED_ACTIONS: &[Action] = &[
+    Action::NextLine,
+    Action::PreviousLine,
+    Action::NextBlock,
+    Action::PreviousBlock,
+    Action::NextCommit,
+    Action::PreviousCommit,
+    Action::BlameAtCommit,
+    Action::PageDown,
+    Action::PageUp,
+    Action::HalfPageDown,
+    Action::HalfPageUp,
+    Action::FirstLine,
+    Action::LastLine,
+    Action::NextSearchMatch,
+    Action::PreviousSearchMatch,
+    Action::NextHotspot,
+    Action::TogglePlainView,
+    Action::Undo,
+];
+
+/// Handles the key events of a [`crate::ui::BlameWidget`] embedded in another
+/// application, performing only the actions which move around the blame.
+/// Returns whether the key was handled, so that the application can handle
+/// the other keys itself, e.g. `q` and `esc`.
+pub fn handle_embedded_key_events(key_event: KeyEvent, app: &mut App) -> bool {
+    if app.is_loading() {
+        return false;
+    }
+    match action_for_key(key_event, app) {
+        Some(action) if EMBEDDED_ACTIONS.contains(&action) => {
+            app.perform(action);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// The default keys of the blame table and the actions they trigger, which
+/// are also listed in the help overlay. Characters are matched regardless of
+/// shift, which is part of the character already, e.g. `G`.
+pub const KEY_BINDINGS: &[(KeyCode, KeyModifiers, Action)] = &[
+    // Scroll by half a page on `Ctrl-D` and `Ctrl-U`, like in vim and less
+    (
+        KeyCode::Char('d'),
+        KeyModifiers::CONTROL,
+        Action::HalfPageDown,
+    ),
+    (
+        KeyCode::Char('u'),
+        KeyModifiers::CONTROL,
+        Action::HalfPageUp,
+    ),
+    (KeyCode::Char('r'), KeyModifiers::CONTROL, Action::Redo),
+    // Exit application on `ESC` or `q`
+    (KeyCode::Char('q'), KeyModifiers::NONE, Action::Quit),
+    (KeyCode::Esc, KeyModifiers::NONE, Action::Quit),
+    (
+        KeyCode::Char('{'),
+        KeyModifiers::NONE,
+        Action::PreviousBlock,
+    ),
+    (KeyCode::Char('}'), KeyModifiers::NONE, Action::NextBlock),
+    (KeyCode::Down, KeyModifiers::NONE, Action::NextLine),
+    (KeyCode::Up, KeyModifiers::NONE, Action::PreviousLine),
+    (KeyCode::PageDown, KeyModifiers::NONE, Action::PageDown),
+    (KeyCode::PageUp, KeyModifiers::NONE, Action::PageUp),
+    (KeyCode::Home, KeyModifiers::NONE, Action::FirstLine),
+    (KeyCode::Char('g'), KeyModifiers::NONE, Action::FirstLine),
+    (KeyCode::End, KeyModifiers::NONE, Action::LastLine),
+    (KeyCode::Char('G'), KeyModifiers::NONE, Action::LastLine),
+    (KeyCode::Left, KeyModifiers::NONE, Action::NextCommit),
+    (
+        KeyCode::Char('c'),
+        KeyModifiers::NONE,
+        Action::BlameAtCommit,
+    ),
+    (
+        KeyCode::Char('M'),
+        KeyModifiers::NONE,
+        Action::CycleCopyDetection,
+    ),
+    (
+        KeyCode::Char('I'),
+        KeyModifiers::NONE,
+        Action::ToggleIgnoreRevs,
+    ),
+    (KeyCode::Char('X'), KeyModifiers::NONE, Action::IgnoreCommit),
+    (
+        KeyCode::Char('*'),
+        KeyModifiers::NONE,
+        Action::ShowSkippedCommits,
+    ),
+    (
+        KeyCode::Char('D'),
+        KeyModifiers::NONE,
+        Action::ToggleRelativeDates,
+    ),
+    (
+        KeyCode::Char('H'),
+        KeyModifiers::NONE,
+        Action::ToggleHeatmap,
+    ),
+    (KeyCode::Char('>'), KeyModifiers::NONE, Action::ScrollRight),
+    (KeyCode::Right, KeyModifiers::CONTROL, Action::ScrollRight),
+    (KeyCode::Char('<'), KeyModifiers::NONE, Action::ScrollLeft),
+    (KeyCode::Left, KeyModifiers::CONTROL, Action::ScrollLeft),
+    (KeyCode::Char('z'), KeyModifiers::NONE, Action::ToggleWrap),
+    (
+        KeyCode::Char('1'),
+        KeyModifiers::NONE,
+        Action::ToggleTimeColumn,
+    ),
+    (
+        KeyCode::Char('2'),
+        KeyModifiers::NONE,
+        Action::ToggleAuthorColumn,
+    ),
+    (
+        KeyCode::Char('3'),
+        KeyModifiers::NONE,
+        Action::ToggleCommitColumn,
+    ),
+    (
+        KeyCode::Char('4'),
+        KeyModifiers::NONE,
+        Action::ToggleMessageColumn,
+    ),
+    (
+        KeyCode::Char('5'),
+        KeyModifiers::NONE,
+        Action::ToggleLineColumn,
+    ),
+    (KeyCode::Char('y'), KeyModifiers::NONE, Action::CopySha),
+    (
+        KeyCode::Char('Y'),
+        KeyModifiers::NONE,
+        Action::CopyShaAndMessage,
+    ),
+    (
+        KeyCode::Char('O'),
+        KeyModifiers::NONE,
+        Action::OpenCommitOnWeb,
+    ),
+    (
+        KeyCode::Char('W'),
+        KeyModifiers::NONE,
+        Action::OpenFileOnWeb,
+    ),
+    (KeyCode::Char('B'), KeyModifiers::NONE, Action::Blocks),
+    (
+        KeyCode::Char('P'),
+        KeyModifiers::NONE,
+        Action::CommitHistory,
+    ),
+    (KeyCode::Right, KeyModifiers::NONE, Action::PreviousCommit),
+    (KeyCode::Enter, KeyModifiers::NONE, Action::ShowCommit),
+    (
+        KeyCode::Char('v'),
+        KeyModifiers::NONE,
+        Action::ToggleSelection,
+    ),
+    (
+        KeyCode::Char('m'),
+        KeyModifiers::NONE,
+        Action::ExportMarkdown,
+    ),
+    (
+        KeyCode::Char('o'),
+        KeyModifiers::NONE,
+        Action::ToggleOwnersAudit,
+    ),
+    (KeyCode::Char('i'), KeyModifiers::NONE, Action::OpenIssue),
+    (
+        KeyCode::Char('U'),
+        KeyModifiers::NONE,
+        Action::ShowRevertLink,
+    ),
+    (
+        KeyCode::Char('t'),
+        KeyModifiers::NONE,
+        Action::JumpToTrailer,
+    ),
+    (
+        KeyCode::Char('T'),
+        KeyModifiers::NONE,
+        Action::NextTrailerMatch,
+    ),
+    (
+        KeyCode::Char('F'),
+        KeyModifiers::NONE,
+        Action::JumpToCommitType,
+    ),
+    (
+        KeyCode::Char('J'),
+        KeyModifiers::NONE,
+        Action::NextCommitTypeMatch,
+    ),
+    (
+        KeyCode::Char('K'),
+        KeyModifiers::NONE,
+        Action::ToggleCommitTypeBadges,
+    ),
+    (
+        KeyCode::Char('A'),
+        KeyModifiers::NONE,
+        Action::NextAuthorBlock,
+    ),
+    (KeyCode::Char('s'), KeyModifiers::NONE, Action::Statistics),
+    (KeyCode::Char(' '), KeyModifiers::NONE, Action::ContextMenu),
+    (KeyCode::Char('u'), KeyModifiers::NONE, Action::Undo),
+    (
+        KeyCode::Char('|'),
+        KeyModifiers::NONE,
+        Action::ToggleParentSplit,
+    ),
+    (KeyCode::Tab, KeyModifiers::NONE, Action::FocusNextPane),
+    (
+        KeyCode::Char('L'),
+        KeyModifiers::NONE,
+        Action::ToggleScrollLock,
+    ),
+    (
+        KeyCode::Char('f'),
+        KeyModifiers::NONE,
+        Action::ToggleFileTree,
+    ),
+    (
+        KeyCode::Char('b'),
+        KeyModifiers::NONE,
+        Action::TogglePlainView,
+    ),
+    (
+        KeyCode::Char('w'),
+        KeyModifiers::NONE,
+        Action::ToggleWorktreeBlame,
+    ),
+    (KeyCode::Char(':'), KeyModifiers::NONE, Action::Command),
+    (KeyCode::Char('h'), KeyModifiers::NONE, Action::NextHotspot),
+    (KeyCode::Char('x'), KeyModifiers::NONE, Action::Todos),
+    (KeyCode::Char('e'), KeyModifiers::NONE, Action::ExpandLine),
+    (KeyCode::F(12), KeyModifiers::NONE, Action::ToggleMetrics),
+    (KeyCode::Char(']'), KeyModifiers::NONE, Action::NextTab),
+    (KeyCode::Char('['), KeyModifiers::NONE, Action::PreviousTab),
+    (KeyCode::Char('d'), KeyModifiers::NONE, Action::ToggleDiff),
+    (KeyCode::Char('l'), KeyModifiers::NONE, Action::Legend),
+    (KeyCode::Char('/'), KeyModifiers::NONE, Action::Search),
+    (
+        KeyCode::Char('n'),
+        KeyModifiers::NONE,
+        Action::NextSearchMatch,
+    ),
+    (
+        KeyCode::Char('N'),
+        KeyModifiers::NONE,
+        Action::PreviousSearchMatch,
+    ),
+    (KeyCode::Char('R'), KeyModifiers::NONE, Action::JumpToNewTip),
+    (KeyCode::Char('?'), KeyModifiers::NONE, Action::Help),
+];
+
+/// Name of a key for showing it to users, e.g. "ctrl-d" or "page up".
+pub fn key_label(code: KeyCode, modifiers: KeyModifiers) -> String {
+    let name = match code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        KeyCode::PageUp => "page up".to_string(),
+        KeyCode::PageDown => "page down".to_string(),
+        other => format!("{:?}", other).to_lowercase(),
+    };
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        format!("ctrl-{}", name)
+    } else {
+        name
+    }
+}
+
+/// Parses the name of a key as shown by [`key_label`], e.g. "ctrl-d", "J" or
+/// "page down", for reading it from the config file.
+pub fn parse_key(name: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let (name, modifiers) = match name
+        .strip_prefix("ctrl-")
+        .or_else(|| name.strip_prefix("ctrl+"))
+    {
+        Some(rest) => (rest, KeyModifiers::CONTROL),
+        None => (name, KeyModifiers::NONE),
+    };
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match name.to_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "page up" | "pageup" => KeyCode::PageUp,
+            "page down" | "pagedown" => KeyCode::PageDown,
+            other => KeyCode::F(other.strip_prefix('f')?.parse().ok()?),
+        },
+    };
+    Some((code, modifiers))
+}
+
+/// Which action each key of the blame table triggers: the defaults from
+/// [`KEY_BINDINGS`], with the keys of some actions changed in the config file.
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: Vec<(KeyCode, KeyModifiers, Action)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap {
+            bindings: KEY_BINDINGS.to_vec(),
+        }
+    }
+}
+
+impl KeyMap {
+    /// Bind an action to the given keys instead of its default ones. The keys
+    /// stop triggering the actions they were bound to before, and no keys
+    /// unbind the action.
+    pub fn bind(&mut self, action: Action, keys: &[(KeyCode, KeyModifiers)]) {
+        self.bindings.retain(|(code, modifiers, a)| {
+            *a != action
+                && !keys
+                    .iter()
+                    .any(|key| is_same_key(key, &(*code, *modifiers)))
+        });
+        self.bindings.extend(
+            keys.iter()
+                .map(|(code, modifiers)| (*code, *modifiers, action)),
+        );
+    }
+
+    /// The action triggered by a key, if any.
+    pub fn action(&self, key_event: KeyEvent) -> Option<Action> {
+        let key = (key_event.code, key_event.modifiers);
+        self.bindings
+            .iter()
+            .find(|(code, modifiers, _)| is_same_key(&key, &(*code, *modifiers)))
+            .map(|(_, _, action)| *action)
+    }
+
+    /// Names of the keys bound to an action.
+    pub fn keys(&self, action: Action) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, _, a)| *a == action)
+            .map(|(code, modifiers, _)| key_label(*code, *modifiers))
+            .collect()
+    }
+
+    /// The keys of each action which has any, in the order in which actions
+    /// are listed to users, e.g. ("g, home", [`Action::FirstLine`]).
+    pub fn help_entries(&self) -> Vec<(String, Action)> {
+        Action::ALL
+            .iter()
+            .map(|action| (self.keys(*action).join(", "), *action))
+            .filter(|(keys, _)| !keys.is_empty())
+            .collect()
+    }
+}
+
+// Whether two keys are the same, where only control matters of the
+// modifiers, since shift is part of the character already.
+fn is_same_key(a: &(KeyCode, KeyModifiers), b: &(KeyCode, KeyModifiers)) -> bool {
+    a.0 == b.0 && a.1.contains(KeyModifiers::CONTROL) == b.1.contains(KeyModifiers::CONTROL)
+}
+
+// Maps a key to the action it triggers in the blame table.
+fn action_for_key(key_event: KeyEvent, app: &App) -> Option<Action> {
+    match key_event.code {
+        // Stop selecting a range of lines on `ESC`
+        KeyCode::Esc if app.selection_anchor.is_some() => Some(Action::ToggleSelection),
+        // Blame the pointer of a file stored with Git LFS on `Enter`, from
+        // the notice shown instead of it
+        KeyCode::Enter if app.shows_lfs_notice() => Some(Action::BlameLfsPointer),
+        _ => app.key_map.action(key_event),
+    }
+}
+
+// Handles the key events while the file tree has focus.
+fn handle_tree_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    let Some(tree) = app.file_tree.as_mut() else {
+        return Ok(());
+    };
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => tree.move_selection(1),
+        KeyCode::Up | KeyCode::Char('k') => tree.move_selection(-1),
+        KeyCode::PageDown => tree.move_selection(10),
+        KeyCode::PageUp => tree.move_selection(-10),
+        KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => app.open_tree_selection(),
+        KeyCode::Left | KeyCode::Char('h') => {
+            if let Some(item) = tree.selected_item().filter(|i| i.is_dir && i.expanded) {
+                tree.toggle_expanded(&item.path);
             }
         }
+        KeyCode::Tab | KeyCode::Esc => app.perform(Action::FocusNextPane),
+        KeyCode::Char('f') => app.perform(Action::ToggleFileTree),
+        KeyCode::Char('q') => app.perform(Action::Quit),
+        _ => {}
+    }
+    Ok(())
+}
+
+// Handles the key events while the diff pane has focus. The diff is
+// scrolled independently of the blame.
+fn handle_diff_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    let Some(diff) = app.diff.as_mut() else {
+        return Ok(());
+    };
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => diff.scroll_by(1),
+        KeyCode::Up | KeyCode::Char('k') => diff.scroll_by(-1),
+        KeyCode::PageDown | KeyCode::Char(' ') => diff.scroll_by(20),
+        KeyCode::PageUp => diff.scroll_by(-20),
+        KeyCode::Home | KeyCode::Char('g') => diff.scroll = 0,
+        KeyCode::Tab => app.perform(Action::FocusNextPane),
+        KeyCode::Esc | KeyCode::Char('d') => app.perform(Action::ToggleDiff),
+        KeyCode::Char('q') => app.perform(Action::Quit),
+        _ => {}
+    }
+    Ok(())
+}
+
+// Handles the key events while a popup is open.
+fn handle_popup_key_events(key_event: KeyEvent, popup: &Popup, app: &mut App) -> AppResult<()> {
+    match popup {
+        Popup::CommitDetail(_, _) => match key_event.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Enter => app.close_popup(),
+            KeyCode::Char('r') => app.request_commit_action(CommitAction::Revert),
+            KeyCode::Char('p') => app.request_commit_action(CommitAction::CherryPick),
+            KeyCode::Char('u') => {
+                if let Some(Popup::CommitDetail(sha, _)) = &app.popup {
+                    app.show_revert_link(&sha.clone());
+                }
+            }
+            _ => {}
+        },
+        Popup::ConfirmCommitAction(_, _) => match key_event.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => app.confirm_commit_action(),
+            KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('N') => app.close_popup(),
+            _ => {}
+        },
+        Popup::Message(_) | Popup::Legend => app.close_popup(),
+        Popup::Statistics(_, _) => match key_event.code {
+            KeyCode::Down | KeyCode::Char('j') => app.move_statistics(1),
+            KeyCode::Up | KeyCode::Char('k') => app.move_statistics(-1),
+            KeyCode::PageDown => app.move_statistics(10),
+            KeyCode::PageUp => app.move_statistics(-10),
+            KeyCode::Home | KeyCode::Char('g') => app.move_statistics(isize::MIN),
+            KeyCode::End | KeyCode::Char('G') => app.move_statistics(isize::MAX),
+            KeyCode::Char('s') => app.cycle_author_order(),
+            KeyCode::Enter => app.filter_by_author(),
+            _ => app.close_popup(),
+        },
+        Popup::ContextMenu(_) => match key_event.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char(' ') => app.close_popup(),
+            KeyCode::Down | KeyCode::Char('j') => app.move_context_menu(true),
+            KeyCode::Up | KeyCode::Char('k') => app.move_context_menu(false),
+            KeyCode::Enter => app.run_context_menu_item(),
+            _ => {}
+        },
+        Popup::ExpandedLine(_, _) => match key_event.code {
+            KeyCode::Down | KeyCode::Char('j') => app.scroll_expanded_line(true),
+            KeyCode::Up | KeyCode::Char('k') => app.scroll_expanded_line(false),
+            _ => app.close_popup(),
+        },
+        Popup::Todos(_) => match key_event.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('x') => app.close_popup(),
+            KeyCode::Down | KeyCode::Char('j') => app.move_todos(true),
+            KeyCode::Up | KeyCode::Char('k') => app.move_todos(false),
+            KeyCode::Enter => app.jump_to_todo(),
+            _ => {}
+        },
+        Popup::Help(_) => match key_event.code {
+            KeyCode::Down | KeyCode::Char('j') => app.scroll_help(1),
+            KeyCode::Up | KeyCode::Char('k') => app.scroll_help(-1),
+            KeyCode::PageDown => app.scroll_help(10),
+            KeyCode::PageUp => app.scroll_help(-10),
+            _ => app.close_popup(),
+        },
+        Popup::Blocks(_) => match key_event.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('B') => app.close_popup(),
+            KeyCode::Down | KeyCode::Char('j') => app.move_blocks(1),
+            KeyCode::Up | KeyCode::Char('k') => app.move_blocks(-1),
+            KeyCode::PageDown => app.move_blocks(10),
+            KeyCode::PageUp => app.move_blocks(-10),
+            KeyCode::Home | KeyCode::Char('g') => app.move_blocks(isize::MIN),
+            KeyCode::End | KeyCode::Char('G') => app.move_blocks(isize::MAX),
+            KeyCode::Char('s') => app.cycle_block_order(),
+            KeyCode::Enter => app.jump_to_block(),
+            _ => {}
+        },
+        Popup::CommitHistory(_) => match key_event.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('P') => app.close_popup(),
+            KeyCode::Down | KeyCode::Char('j') => app.move_commit_history(1),
+            KeyCode::Up | KeyCode::Char('k') => app.move_commit_history(-1),
+            KeyCode::Home | KeyCode::Char('g') => app.move_commit_history(isize::MIN),
+            KeyCode::End | KeyCode::Char('G') => app.move_commit_history(isize::MAX),
+            KeyCode::Enter => app.jump_to_commit_history(),
+            _ => {}
+        },
+        Popup::Prompt(_, _) => match key_event.code {
+            KeyCode::Esc => app.close_popup(),
+            KeyCode::Enter => app.submit_prompt(),
+            KeyCode::Backspace => app.prompt_backspace(),
+            KeyCode::Char(c) => app.prompt_input(c),
+            _ => {}
+        },
+    }
+    Ok(())
+}
 
-        KeyCode::Char('{') => app.previous_block(),
-        KeyCode::Char('}') => app.next_block(),
-        KeyCode::Down => app.next_line(),
-        KeyCode::Up => app.previous_line(),
-        KeyCode::Left => app.next_commit(),
-        KeyCode::Right => app.previous_commit(),
+/// Handles the mouse events and updates the state of [`App`].
+pub fn handle_mouse_events(mouse_event: MouseEvent, app: &mut App) -> AppResult<()> {
+    if app.popup.is_some() || app.is_loading() {
+        return Ok(());
+    }
 
+    let (column, row) = (mouse_event.column, mouse_event.row);
+    match mouse_event.kind {
+        MouseEventKind::Down(MouseButton::Left) => app.click(column, row),
+        // The menu is for the line which was clicked.
+        MouseEventKind::Down(MouseButton::Right) => {
+            app.select_at(column, row);
+            app.perform(Action::ContextMenu);
+        }
+        MouseEventKind::ScrollDown => app.scroll_wheel(column, row, 1),
+        MouseEventKind::ScrollUp => app.scroll_wheel(column, row, -1),
         _ => {}
     }
     Ok(())
 }
+
+/// Handles text pasted into the terminal, which is entered into the open
+/// prompt, if any. Line breaks are left out, since prompts have one line.
+pub fn handle_paste_events(text: &str, app: &mut App) {
+    if let Some(Popup::Prompt(_, _)) = app.popup {
+        for c in text.chars().filter(|c| !c.is_control()) {
+            app.prompt_input(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_keys() {
+        assert_eq!(key_label(KeyCode::Char('j'), KeyModifiers::NONE), "j");
+        assert_eq!(
+            key_label(KeyCode::Char('d'), KeyModifiers::CONTROL),
+            "ctrl-d"
+        );
+        assert_eq!(key_label(KeyCode::Char(' '), KeyModifiers::NONE), "space");
+        assert_eq!(
+            key_label(KeyCode::PageDown, KeyModifiers::NONE),
+            "page down"
+        );
+        assert_eq!(key_label(KeyCode::F(1), KeyModifiers::NONE), "F1");
+        assert_eq!(key_label(KeyCode::Esc, KeyModifiers::NONE), "esc");
+    }
+
+    #[test]
+    fn lists_the_keys_of_each_action() {
+        let entries = KeyMap::default().help_entries();
+        assert!(entries.contains(&("home, g".to_string(), Action::FirstLine)));
+        assert!(entries.contains(&("?".to_string(), Action::Help)));
+    }
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(
+            parse_key("J"),
+            Some((KeyCode::Char('J'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key("ctrl-d"),
+            Some((KeyCode::Char('d'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse_key("ctrl+d"),
+            Some((KeyCode::Char('d'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse_key("Page Up"),
+            Some((KeyCode::PageUp, KeyModifiers::NONE))
+        );
+        assert_eq!(parse_key("f5"), Some((KeyCode::F(5), KeyModifiers::NONE)));
+        assert_eq!(parse_key("nope"), None);
+        // Labels of keys can be parsed back.
+        for (code, modifiers, _) in KEY_BINDINGS {
+            assert_eq!(
+                parse_key(&key_label(*code, *modifiers)),
+                Some((*code, *modifiers))
+            );
+        }
+    }
+
+    #[test]
+    fn binds_actions_to_other_keys() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let mut key_map = KeyMap::default();
+        key_map.bind(
+            Action::NextBlock,
+            &[(KeyCode::Char('g'), KeyModifiers::NONE)],
+        );
+        assert_eq!(key_map.action(key('g')), Some(Action::NextBlock));
+        assert_eq!(key_map.keys(Action::NextBlock), ["g"]);
+        // The key no longer triggers the action it was bound to by default.
+        assert_eq!(key_map.keys(Action::FirstLine), ["home"]);
+        assert_eq!(key_map.action(key('é')), None);
+
+        key_map.bind(Action::NextBlock, &[]);
+        assert!(key_map.keys(Action::NextBlock).is_empty());
+        assert_eq!(key_map.action(key('g')), None);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::age::DAY;
+use crate::file_blame::FileBlame;
+use crate::metrics;
+use std::ops::RangeInclusive;
+use std::process::Command;
+
+// Time after which a change counts half as much towards the hotspot score.
+const HALF_LIFE: i64 = 90 * DAY;
+
+// A block of lines with its hotspot score. Each change of the block's lines
+// in the history of the file adds to the score, weighted by how recent the
+// change is, so blocks which were changed often and recently score highest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hotspot {
+    // Range of indices into the blame lines.
+    pub block: RangeInclusive<usize>,
+    pub score: f64,
+    // Number of commits which changed the block's lines.
+    pub changes: usize,
+}
+
+// Compute the hotspot score of each block of a blame, sorted from the
+// hottest block to the coldest.
+pub fn compute(file_blame: &FileBlame, now: i64) -> Vec<Hotspot> {
+    let Some((git_root_dir, relative_path)) = FileBlame::repo_paths(&file_blame.filepath) else {
+        return vec![];
+    };
+
+    let mut hotspots: Vec<Hotspot> = file_blame
+        .blocks()
+        .into_iter()
+        .filter_map(|block| {
+            // Follow the history of the block's lines, printing only the
+            // author time of each commit which changed them.
+            let output = metrics::output(
+                Command::new("git")
+                    .current_dir(&git_root_dir)
+                    .arg("log")
+                    .arg(format!(
+                        "-L{},{}:{}",
+                        file_blame.blame_lines[*block.start()].line_number,
+                        file_blame.blame_lines[*block.end()].line_number,
+                        relative_path
+                    ))
+                    .arg("--format=%at")
+                    .arg("-s")
+                    .arg(&file_blame.commit_sha),
+            )
+            .ok()?;
+            if !output.status.success() {
+                return None;
+            }
+
+            let times: Vec<i64> = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|line| line.trim().parse().ok())
+                .collect();
+            Some(Hotspot {
+                block,
+                score: score(&times, now),
+                changes: times.len(),
+            })
+        })
+        .collect();
+
+    hotspots.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hotspots
+}
+
+// Sum of the changes at the given times, each halved for every half-life
+// which has passed since.
+pub fn score(times: &[i64], now: i64) -> f64 {
+    times
+        .iter()
+        .map(|time| 0.5f64.powf((now - time).max(0) as f64 / HALF_LIFE as f64))
+        .sum()
+}
@@ -0,0 +1,67 @@
+// A Git LFS pointer, which git stores in place of a large file whose
+// contents live on an LFS server. Blaming a pointer only shows who last
+// replaced the file, not who wrote its contents. See
+// https://github.com/git-lfs/git-lfs/blob/main/docs/spec.md
+#[derive(Debug, Clone, PartialEq)]
+pub struct LfsPointer {
+    // Hash of the contents, e.g. "sha256:4d7a...".
+    pub oid: String,
+    // Size of the contents in bytes.
+    pub size: u64,
+}
+
+impl LfsPointer {
+    // Parse the lines of a file as an LFS pointer, which starts with the
+    // version of the spec, followed by "key value" lines including the oid
+    // and size of the contents.
+    pub fn parse<'a>(mut lines: impl Iterator<Item = &'a str>) -> Option<LfsPointer> {
+        if !lines
+            .next()?
+            .starts_with("version https://git-lfs.github.com/spec/")
+        {
+            return None;
+        }
+        let (mut oid, mut size) = (None, None);
+        for line in lines {
+            match line.split_once(' ')? {
+                ("oid", value) => oid = Some(value.to_string()),
+                ("size", value) => size = value.parse().ok(),
+                _ => {}
+            }
+        }
+        Some(LfsPointer {
+            oid: oid?,
+            size: size?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pointers() {
+        let pointer = "version https://git-lfs.github.com/spec/v1\n\
+                       oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\n\
+                       size 12345";
+        assert_eq!(
+            LfsPointer::parse(pointer.lines()),
+            Some(LfsPointer {
+                oid: "sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393"
+                    .to_string(),
+                size: 12345,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert_eq!(LfsPointer::parse("fn main() {}".lines()), None);
+        assert_eq!(LfsPointer::parse("".lines()), None);
+        let no_size = "version https://git-lfs.github.com/spec/v1\noid sha256:4d7a";
+        assert_eq!(LfsPointer::parse(no_size.lines()), None);
+        let bad_size = "version https://git-lfs.github.com/spec/v1\noid sha256:4d7a\nsize big";
+        assert_eq!(LfsPointer::parse(bad_size.lines()), None);
+    }
+}
@@ -15,3 +15,101 @@ pub mod handler;
 
 /// File blame module.
 pub mod file_blame;
+
+/// In-process git access with libgit2.
+#[cfg(feature = "git2")]
+pub mod git2_backend;
+
+/// Per-line annotations overlaid next to the blame.
+pub mod annotations;
+
+/// Exporting blame information to other formats.
+pub mod export;
+
+/// CODEOWNERS parsing and matching.
+pub mod codeowners;
+
+/// Issue tracker links and opening URLs.
+pub mod links;
+
+/// Commit age computations.
+pub mod age;
+
+/// File ownership statistics.
+pub mod stats;
+
+/// Named actions performed on the application.
+pub mod action;
+
+/// Line number mapping between two versions of a file.
+pub mod line_map;
+
+/// Repository file tree at a commit.
+pub mod tree;
+
+/// Hotspots of churn and recent changes in a file.
+pub mod hotspots;
+
+/// JSON-RPC server for reusing blames from other tools.
+#[cfg(unix)]
+pub mod server;
+
+/// Terminal color support detection and color conversion.
+pub mod colors;
+
+/// Locale-specific formatting of dates and numbers.
+pub mod locale;
+
+/// Memory size estimates and cache eviction.
+pub mod memory;
+
+/// Performance counters for diagnosing slow repositories.
+pub mod metrics;
+
+/// Blames and commits cached on disk ahead of time.
+pub mod disk_cache;
+
+/// Suggestions for mistyped names.
+pub mod suggest;
+
+/// Reviewing the files changed in a range of commits.
+pub mod review;
+
+/// Commit diffs shown next to the blame.
+pub mod diff;
+
+/// Searching the contents of the blamed file.
+pub mod search;
+
+/// Sharing the selected line with editors following along.
+pub mod presenter;
+
+/// Partial clones, which fetch missing objects on demand.
+pub mod partial_clone;
+
+/// Copying to the system clipboard through the terminal.
+pub mod clipboard;
+
+/// Git LFS pointer files.
+pub mod lfs;
+
+/// Decoding files in other encodings than UTF-8.
+pub mod encoding;
+
+/// Web pages of commits and files on code hosting sites.
+pub mod remote;
+
+/// Settings read from the config file.
+pub mod config;
+
+/// Colors and characters of the blame table.
+pub mod theme;
+
+/// Files with lines last changed by an author.
+pub mod owners;
+
+/// Cleaning up commit messages for showing them.
+pub mod message;
+
+/// Linking revert commits and the commits they revert.
+pub mod reverts;
@@ -0,0 +1,235 @@
+use crate::file_blame::FileBlame;
+use crate::metrics;
+use std::process::Command;
+
+// A hunk of a zero-context diff, i.e. a range of lines in the old version of
+// a file which was replaced by a range of lines in the new version. Line
+// numbers are 1-based, and a range with a length of zero is an insertion or
+// deletion after the given line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Hunk {
+    old_start: usize,
+    old_len: usize,
+    new_start: usize,
+    new_len: usize,
+}
+
+impl Hunk {
+    // First line in the old version which is in or after the hunk.
+    fn old_begin(&self) -> usize {
+        if self.old_len == 0 {
+            self.old_start + 1
+        } else {
+            self.old_start
+        }
+    }
+
+    // First line in the new version which is in or after the hunk.
+    fn new_begin(&self) -> usize {
+        if self.new_len == 0 {
+            self.new_start + 1
+        } else {
+            self.new_start
+        }
+    }
+}
+
+// Mapping between the line numbers of two versions of a file, based on the
+// diff between them. Unchanged lines map to their counterpart in the other
+// version, while changed lines map to the start of the hunk they are in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineMap {
+    hunks: Vec<Hunk>,
+}
+
+impl LineMap {
+    // Build the mapping between a file at two commits by diffing them.
+    pub fn between(filepath: &str, old_commit: &str, new_commit: &str) -> LineMap {
+        let Some((git_root_dir, relative_path)) = FileBlame::repo_paths(filepath) else {
+            return LineMap::default();
+        };
+
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(&git_root_dir)
+                .arg("diff")
+                .arg("--no-color")
+                .arg("--no-ext-diff")
+                .arg("-U0")
+                .arg(old_commit)
+                .arg(new_commit)
+                .arg("--")
+                .arg(&relative_path),
+        );
+
+        match output {
+            Ok(output) if output.status.success() => {
+                LineMap::parse(&String::from_utf8_lossy(&output.stdout))
+            }
+            _ => LineMap::default(),
+        }
+    }
+
+    // Parse the hunk headers of a zero-context unified diff, which look like
+    // "@@ -12,3 +12,5 @@".
+    pub fn parse(diff: &str) -> LineMap {
+        let hunks = diff.lines().filter_map(parse_hunk_header).collect();
+        LineMap { hunks }
+    }
+
+    // Map a line number in the new version to the old version.
+    pub fn new_to_old(&self, line: usize) -> usize {
+        map_line(
+            line,
+            self.hunks
+                .iter()
+                .map(|h| (h.new_begin(), h.new_len, h.old_begin(), h.old_len)),
+        )
+    }
+
+    // Map a line number in the old version to the new version.
+    pub fn old_to_new(&self, line: usize) -> usize {
+        map_line(
+            line,
+            self.hunks
+                .iter()
+                .map(|h| (h.old_begin(), h.old_len, h.new_begin(), h.new_len)),
+        )
+    }
+}
+
+// Parse a hunk header like "@@ -12,3 +12,5 @@", which may be followed by
+// the function the hunk is in.
+fn parse_hunk_header(line: &str) -> Option<Hunk> {
+    let line = line.strip_prefix("@@ -")?;
+    let (old, rest) = line.split_once(" +")?;
+    let new = rest.split_once(" @@")?.0;
+    let (old_start, old_len) = parse_range(old)?;
+    let (new_start, new_len) = parse_range(new)?;
+    Some(Hunk {
+        old_start,
+        old_len,
+        new_start,
+        new_len,
+    })
+}
+
+// The first and last line in the new version of a file which a hunk header
+// covers. A hunk which only deletes lines covers the line it is after.
+pub fn hunk_new_lines(header: &str) -> Option<(usize, usize)> {
+    let hunk = parse_hunk_header(header.trim())?;
+    let start = hunk.new_start.max(1);
+    Some((start, start + hunk.new_len.max(1) - 1))
+}
+
+// The first line in the old version of a file which a hunk header covers.
+pub fn hunk_old_start(header: &str) -> Option<usize> {
+    parse_hunk_header(header.trim()).map(|hunk| hunk.old_start)
+}
+
+// The path of the first file in a unified diff, relative to the root of the
+// repository, and the lines which its first hunk covers in the new version.
+pub fn first_hunk(diff: &str) -> Option<(String, (usize, usize))> {
+    let mut path = None;
+    for line in diff.lines() {
+        if let Some(new_path) = line.strip_prefix("+++ ") {
+            // Paths are prefixed with "b/", unless the diff was made with
+            // --no-prefix.
+            let new_path = new_path.split('\t').next().unwrap_or(new_path);
+            path = Some(new_path.strip_prefix("b/").unwrap_or(new_path).to_string());
+        } else if let (Some(path), Some(lines)) = (&path, hunk_new_lines(line)) {
+            return Some((path.clone(), lines));
+        }
+    }
+    None
+}
+
+// Parse a diff range like "12,3" or "12" (which has a length of 1).
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+// Map a line from one version to the other, given the hunks as
+// (from_begin, from_len, to_begin, to_len) tuples in order.
+fn map_line(line: usize, hunks: impl Iterator<Item = (usize, usize, usize, usize)>) -> usize {
+    let mut offset: isize = 0;
+    for (from_begin, from_len, to_begin, to_len) in hunks {
+        if line < from_begin {
+            break;
+        }
+        if line < from_begin + from_len {
+            return to_begin;
+        }
+        offset = (to_begin + to_len) as isize - (from_begin + from_len) as isize;
+    }
+    (line as isize + offset).max(1) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two lines inserted after line 2, lines 5-6 replaced by one line, and
+    // line 10 deleted.
+    const DIFF: &str = "diff --git a/f b/f\n\
+                        --- a/f\n\
+                        +++ b/f\n\
+                        @@ -2,0 +3,2 @@ fn main() {\n\
+                        +a\n\
+                        +b\n\
+                        @@ -5,2 +7 @@\n\
+                        -c\n\
+                        -d\n\
+                        +e\n\
+                        @@ -10 +10,0 @@\n\
+                        -f\n";
+
+    #[test]
+    fn maps_old_lines_to_new_lines() {
+        let line_map = LineMap::parse(DIFF);
+        let mapped: Vec<_> = (1..=11).map(|l| line_map.old_to_new(l)).collect();
+        assert_eq!(mapped, [1, 2, 5, 6, 7, 7, 8, 9, 10, 11, 11]);
+    }
+
+    #[test]
+    fn maps_new_lines_to_old_lines() {
+        let line_map = LineMap::parse(DIFF);
+        let mapped: Vec<_> = (1..=11).map(|l| line_map.new_to_old(l)).collect();
+        assert_eq!(mapped, [1, 2, 3, 3, 3, 4, 5, 7, 8, 9, 11]);
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("12,3"), Some((12, 3)));
+        assert_eq!(parse_range("12"), Some((12, 1)));
+        assert_eq!(parse_range("x"), None);
+        assert_eq!(LineMap::parse("").old_to_new(7), 7);
+    }
+
+    #[test]
+    fn finds_the_lines_of_a_hunk() {
+        assert_eq!(
+            hunk_new_lines("@@ -120,7 +121,9 @@ fn main() {"),
+            Some((121, 129))
+        );
+        assert_eq!(hunk_new_lines("@@ -3 +4 @@"), Some((4, 4)));
+        // A deletion covers the line it is after.
+        assert_eq!(hunk_new_lines("@@ -10 +9,0 @@"), Some((9, 9)));
+        assert_eq!(hunk_new_lines("@@ -1,0 +0,0 @@"), Some((1, 1)));
+        assert_eq!(hunk_new_lines("not a hunk"), None);
+    }
+
+    #[test]
+    fn finds_the_first_hunk_of_a_diff() {
+        assert_eq!(first_hunk(DIFF), Some(("f".to_string(), (3, 4))));
+        let no_prefix = "--- src/app.rs\n+++ src/app.rs\t2024-01-01\n@@ -1 +1,2 @@\n";
+        assert_eq!(
+            first_hunk(no_prefix),
+            Some(("src/app.rs".to_string(), (1, 2)))
+        );
+        assert_eq!(first_hunk("@@ -1 +1 @@\n"), None);
+    }
+}
@@ -0,0 +1,58 @@
+use regex::Regex;
+use std::io;
+use std::ops::Range;
+use std::process::{Command, Stdio};
+
+// Detects issue tracker IDs (e.g. PROJ-123) in commit messages and turns
+// them into URLs using a template, e.g. https://tracker.example/browse/{id}.
+#[derive(Debug, Clone)]
+pub struct IssueLinker {
+    pattern: Regex,
+    url_template: String,
+}
+
+impl IssueLinker {
+    pub fn new(pattern: &str, url_template: &str) -> Result<IssueLinker, regex::Error> {
+        Ok(IssueLinker {
+            pattern: Regex::new(pattern)?,
+            url_template: url_template.to_string(),
+        })
+    }
+
+    // Find the byte ranges of all issue IDs in a text.
+    pub fn find(&self, text: &str) -> Vec<Range<usize>> {
+        self.pattern.find_iter(text).map(|m| m.range()).collect()
+    }
+
+    // Build the URL of an issue by replacing `{id}` in the template.
+    pub fn url(&self, id: &str) -> String {
+        self.url_template.replace("{id}", id)
+    }
+
+    // Get the URLs for all issue IDs in a text.
+    pub fn urls(&self, text: &str) -> Vec<String> {
+        self.find(text)
+            .into_iter()
+            .map(|r| self.url(&text[r]))
+            .collect()
+    }
+}
+
+// Open a URL in the user's browser, without waiting for the browser to exit.
+pub fn open_url(url: &str) -> io::Result<()> {
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+
+    Command::new(program)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
@@ -0,0 +1,110 @@
+use std::env;
+
+// Order of the parts of a date.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DateOrder {
+    #[default]
+    YearMonthDay,
+    MonthDayYear,
+    DayMonthYear,
+}
+
+// How dates and numbers are formatted for a locale. Only the conventions of
+// common locales are known, and other locales use ISO 8601 dates and plain
+// numbers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Locale {
+    pub date_order: DateOrder,
+    pub date_separator: char,
+    pub thousands_separator: Option<char>,
+    pub decimal_separator: char,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale {
+            date_order: DateOrder::YearMonthDay,
+            date_separator: '-',
+            thousands_separator: None,
+            decimal_separator: '.',
+        }
+    }
+}
+
+impl Locale {
+    // Look up a locale by its name, e.g. "de_DE.UTF-8", "en-GB" or "fr".
+    pub fn from_name(name: &str) -> Locale {
+        let name = name
+            .split(['.', '@'])
+            .next()
+            .unwrap_or("")
+            .replace('-', "_");
+        let (language, region) = name.split_once('_').unwrap_or((&name, ""));
+        let locale = |date_order, date_separator, thousands_separator, decimal_separator| Locale {
+            date_order,
+            date_separator,
+            thousands_separator: Some(thousands_separator),
+            decimal_separator,
+        };
+
+        match (language, region) {
+            ("en", "US") => locale(DateOrder::MonthDayYear, '/', ',', '.'),
+            ("en", _) => locale(DateOrder::DayMonthYear, '/', ',', '.'),
+            ("de" | "pl" | "ru" | "cs" | "fi" | "nb" | "da" | "tr", _) => {
+                locale(DateOrder::DayMonthYear, '.', '.', ',')
+            }
+            ("fr" | "es" | "it" | "pt", _) => locale(DateOrder::DayMonthYear, '/', '\u{a0}', ','),
+            ("nl", _) => locale(DateOrder::DayMonthYear, '-', '.', ','),
+            ("sv", _) => locale(DateOrder::YearMonthDay, '-', '\u{a0}', ','),
+            ("ja" | "zh" | "ko", _) => locale(DateOrder::YearMonthDay, '/', ',', '.'),
+            _ => Locale::default(),
+        }
+    }
+
+    // Detect the locale from the environment, in the order of precedence
+    // used by the C library.
+    pub fn detect() -> Locale {
+        ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .map(|value| Locale::from_name(&value))
+            .unwrap_or_default()
+    }
+
+    // Format the date of a timestamp which starts with an ISO 8601 date,
+    // e.g. "2019-01-01 12:00:00 -0400". Anything else is returned as is.
+    pub fn format_date(&self, timestamp: &str) -> String {
+        let date = timestamp.split(' ').next().unwrap_or("");
+        let [year, month, day] = date.splitn(3, '-').collect::<Vec<_>>()[..] else {
+            return timestamp.to_string();
+        };
+        let parts = match self.date_order {
+            DateOrder::YearMonthDay => [year, month, day],
+            DateOrder::MonthDayYear => [month, day, year],
+            DateOrder::DayMonthYear => [day, month, year],
+        };
+        parts.join(&self.date_separator.to_string())
+    }
+
+    // Format a count with the thousands grouped, e.g. "12,345".
+    pub fn format_number(&self, n: usize) -> String {
+        let digits = n.to_string();
+        let Some(separator) = self.thousands_separator else {
+            return digits;
+        };
+        let mut out = String::new();
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                out.push(separator);
+            }
+            out.push(c);
+        }
+        out
+    }
+
+    // Format a ratio between 0 and 1 as a percentage with one decimal, e.g. "12.5%".
+    pub fn format_percent(&self, ratio: f64) -> String {
+        format!("{:.1}%", ratio * 100.0).replace('.', &self.decimal_separator.to_string())
+    }
+}
@@ -1,35 +1,567 @@
-use blame::app::{App, AppResult};
+use blame::age::{self, AgeWarning, DateFormat};
+use blame::annotations::JsonAnnotationProvider;
+use blame::app::{App, AppResult, ColumnKind, ExternalCommand, Popup};
+use blame::colors::{self, ColorSupport};
+use blame::config::Config;
+use blame::disk_cache;
 use blame::event::{Event, EventHandler};
-use blame::handler::handle_key_events;
+use blame::export::{self, Delimited};
+use blame::file_blame::{
+    self, CopyDetection, FileBlame, FileBlameError, ParseOptions, DEFAULT_HIGHLIGHT_LINE_LIMIT,
+    DEFAULT_MAX_LINE_LENGTH,
+};
+use blame::handler::{handle_key_events, handle_mouse_events, handle_paste_events};
+use blame::line_map;
+use blame::links::IssueLinker;
+use blame::locale::Locale;
+use blame::memory;
+use blame::message::MessageCleaner;
+use blame::owners;
+use blame::presenter::Presenter;
+use blame::review::Review;
+use blame::theme;
 use blame::tui::Tui;
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
-use std::io;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::{self, Command};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    /// File path to display blame for.
-    filepath: String,
+    #[command(subcommand)]
+    command: Option<Subcommand>,
 
-    /// Ref for which to show blame for.
-    #[arg(short, long, default_value = "HEAD")]
-    gitref: String,
+    /// File path to display blame for. Lines can be selected with a fragment
+    /// like in links to files on GitHub, e.g. src/app.rs#L100-L140.
+    #[arg(required_unless_present_any = ["serve", "batch", "hunk", "range", "commit", "list_themes"])]
+    filepath: Option<String>,
+
+    /// Ref for which to show blame for, or WORKTREE for the file in the
+    /// working tree. Defaults to WORKTREE if the file has uncommitted
+    /// changes, and to HEAD otherwise.
+    #[arg(short, long)]
+    gitref: Option<String>,
+
+    /// JSON file with per-line annotations to show next to the blame.
+    #[arg(long)]
+    annotations: Option<String>,
+
+    /// Print the blame in the given format instead of opening the user interface.
+    #[arg(long, value_enum)]
+    output: Option<OutputFormat>,
+
+    /// Write a JSON report of line range ownership to the given file instead of
+    /// opening the user interface.
+    #[arg(long)]
+    report: Option<String>,
+
+    /// Format of the ownership report: blame's own JSON, or SARIF 2.1.0 for
+    /// code scanning tools.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Json, requires = "report")]
+    report_format: ReportFormat,
+
+    /// Regex matching issue IDs in commit messages, e.g. 'PROJ-\d+'.
+    #[arg(long, requires = "issue_url")]
+    issue_pattern: Option<String>,
+
+    /// URL template for opening issues, with {id} replaced by the issue ID.
+    #[arg(long, requires = "issue_pattern")]
+    issue_url: Option<String>,
+
+    /// Highlight lines last changed longer ago than this, e.g. 5y, 6m, 2w or 30d.
+    #[arg(long, value_parser = age::parse_duration)]
+    warn_older_than: Option<i64>,
+
+    /// Highlight lines last changed more recently than this, e.g. 5y, 6m, 2w or 30d.
+    #[arg(long, value_parser = age::parse_duration)]
+    warn_newer_than: Option<i64>,
+
+    /// Show each line's age as a bar in a narrow column, relative to the
+    /// repository's history, without relying on colors.
+    #[arg(long)]
+    age_gauge: bool,
+
+    /// Tint the commit column with a subtle color derived from each commit's
+    /// hash, so that neighboring commits can be told apart at a glance.
+    #[arg(long)]
+    commit_colors: bool,
+
+    /// Tint the time column on a gradient from red for the file's newest
+    /// changes to blue for its oldest ones, like a heatmap.
+    #[arg(long)]
+    heatmap: bool,
+
+    /// Leave the types of conventional commits, e.g. "fix(parser):", out of
+    /// the messages in the MESSAGE column.
+    #[arg(long)]
+    strip_commit_types: bool,
+
+    /// Show the types of conventional commits as colored badges in front of
+    /// the messages, e.g. a red "fix". The K key toggles the badges.
+    #[arg(long)]
+    commit_type_badges: bool,
+
+    /// Hide columns to give more room to the contents, e.g. author,message.
+    /// The keys 1 to 5 hide and show the time, author, commit, message and
+    /// line columns.
+    #[arg(long, value_name = "COLUMNS", value_delimiter = ',', value_parser = ColumnKind::parse_hideable)]
+    hide_columns: Vec<ColumnKind>,
+
+    /// Read settings, e.g. key bindings, from this file instead of
+    /// ~/.config/blame/config.toml.
+    #[arg(long, value_name = "FILE")]
+    config: Option<String>,
+
+    /// Fetch the branch of a remote-tracking ref, e.g. origin/release-1.4,
+    /// before blaming it, so that it's up to date. Without it, missing remote
+    /// branches are only fetched after asking.
+    #[arg(long)]
+    fetch: bool,
+
+    /// Colors of the blame table: dark, light or solarized. Single colors can
+    /// be changed in the [theme] table of the config file.
+    #[arg(long, value_name = "NAME")]
+    theme: Option<String>,
+
+    /// Syntax highlighting theme of the file's contents, instead of the one
+    /// of the --theme, e.g. "Solarized (light)".
+    #[arg(long, value_name = "NAME", value_parser = theme::parse_syntax_theme)]
+    syntax_theme: Option<String>,
+
+    /// Print the names of the syntax highlighting themes and exit.
+    #[arg(long)]
+    list_themes: bool,
+
+    /// Write a JSON map of each line to its commit, author and age to the given
+    /// file for editor plugins, instead of opening the user interface.
+    #[arg(long)]
+    emit_annotations: Option<String>,
+
+    /// Serve blames over JSON-RPC on the given unix socket instead of opening
+    /// the user interface, e.g. for editor integrations.
+    #[arg(long, value_name = "SOCKET")]
+    serve: Option<String>,
+
+    /// Read "path<TAB>ref" lines from stdin and print the ownership report of
+    /// each file as a line of JSON, instead of opening the user interface.
+    #[arg(long)]
+    batch: bool,
+
+    /// Render the blame as plain lines of text without borders, for screen readers.
+    #[arg(long)]
+    accessible: bool,
+
+    /// Format of the dates of commits: absolute, e.g. 2023-04-01, or relative,
+    /// e.g. "3 weeks ago". `D` switches between them.
+    #[arg(long, value_name = "FORMAT", default_value = "absolute", value_parser = age::parse_date_format)]
+    date_format: DateFormat,
+
+    /// Locale for formatting dates and numbers, e.g. de_DE. Defaults to the
+    /// locale set in the environment.
+    #[arg(long)]
+    locale: Option<String>,
+
+    /// Number of characters of a line which are highlighted and shown, so that
+    /// e.g. minified files stay fast. Longer lines can be expanded with `e`.
+    #[arg(long, default_value_t = DEFAULT_MAX_LINE_LENGTH)]
+    max_line_length: usize,
+
+    /// Number of lines above which files aren't syntax highlighted, so that
+    /// huge files open quickly.
+    #[arg(long, default_value_t = DEFAULT_HIGHLIGHT_LINE_LIMIT)]
+    highlight_line_limit: usize,
+
+    /// Only blame the given range of lines, e.g. 100,200.
+    #[arg(short = 'L', long = "lines", visible_alias = "line-range", value_name = "START,END", value_parser = file_blame::parse_line_range)]
+    line_range: Option<(usize, usize)>,
+
+    /// Detect lines moved or copied within the file, and blame them on the
+    /// commit which added them instead of the one which moved them.
+    #[arg(short = 'M')]
+    detect_moves: bool,
+
+    /// Detect lines moved or copied from other files changed in the same
+    /// commit. Given twice, also from any file in the commit which created
+    /// the file. Implies -M.
+    #[arg(short = 'C', action = clap::ArgAction::Count)]
+    detect_copies: u8,
+
+    /// In a partial clone, don't fetch old versions of the file which are
+    /// missing, and blame the lines from before them on the oldest commit
+    /// which can be blamed instead.
+    #[arg(long)]
+    no_fetch: bool,
+
+    /// File listing commits to ignore, e.g. bulk reformatting commits, whose
+    /// lines are blamed on the commits before them instead. Defaults to the
+    /// file set with blame.ignoreRevsFile, or the repository's
+    /// .git-blame-ignore-revs file. An empty path ignores no commits.
+    #[arg(long, value_name = "FILE")]
+    ignore_revs_file: Option<String>,
+
+    /// Blame in reverse, from the ref up to the given commit: each line of the
+    /// file at the ref is blamed on the last commit in which it still existed,
+    /// and Right jumps to the commit which deleted it.
+    #[arg(long, value_name = "COMMIT", requires = "gitref")]
+    reverse: Option<String>,
+
+    /// When to use colors, in the user interface and in the printed blame.
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Memory which cached blames, highlighted contents and commits may use,
+    /// e.g. 512M or 1G, after which the least recently used ones are evicted.
+    #[arg(long, value_parser = memory::parse_size, default_value = "256M")]
+    memory_budget: usize,
+
+    /// Open the blame at the lines of a diff hunk, given as
+    /// "path:@@ -120,7 +121,9 @@", or "-" for reading a unified diff from stdin
+    /// and using its first hunk.
+    #[arg(long, value_name = "PATH:HUNK", conflicts_with = "filepath")]
+    hunk: Option<String>,
+
+    /// Review all files changed in a range of commits, e.g. origin/main..HEAD,
+    /// each in a tab and showing only the lines introduced in the range.
+    #[arg(long, conflicts_with_all = ["filepath", "hunk", "gitref", "output", "report", "emit_annotations"])]
+    range: Option<String>,
+
+    /// Blame all files added or changed in a commit at that commit, each in a
+    /// tab and with the commit's own lines marked.
+    #[arg(long, conflicts_with_all = ["filepath", "hunk", "gitref", "range", "output", "report", "emit_annotations"])]
+    commit: Option<String>,
+
+    /// Repository to blame the file in, when the file is outside of it, e.g. in
+    /// an extracted `git archive` tarball of a deployed release. The file is
+    /// matched by its path relative to the root of the archive.
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["range", "commit"])]
+    repo: Option<String>,
+
+    /// Oldest commit to travel back to in history, e.g. the fork point of a branch.
+    #[arg(long)]
+    floor: Option<String>,
+
+    /// Number of parent commits which can be traveled back through, so that
+    /// drilling down into a huge history doesn't go on for minutes by accident.
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Share the selected line as "path:line" so that editors can follow the
+    /// blame cursor, e.g. while pairing, by writing it to the given file on
+    /// each change, or sending it to the given unix socket if it is one.
+    #[arg(long, value_name = "PATH")]
+    share_cursor: Option<String>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Subcommand {
+    /// Store the blames of a file, or of all files in a directory, in an on-disk
+    /// cache ahead of time, e.g. from editors or shell hooks, so that they open
+    /// quickly later.
+    Prime {
+        /// File or directory to prime the cache for.
+        path: String,
+
+        /// Ref at which to blame the files.
+        #[arg(short, long, default_value = "HEAD")]
+        gitref: String,
+    },
+    /// Print the keys of all actions, with the keys from the config file, e.g.
+    /// for generating a cheatsheet.
+    Keymap {
+        /// Format of the list.
+        #[arg(long, value_enum, default_value = "md")]
+        output: KeymapFormat,
+
+        /// Read the key bindings from this file instead of
+        /// ~/.config/blame/config.toml.
+        #[arg(long, value_name = "FILE")]
+        config: Option<String>,
+    },
+    /// List the files in a directory with lines last changed by an author,
+    /// with the number and share of their lines, most lines first.
+    Owners {
+        /// Text in the names of the authors, ignoring case.
+        #[arg(long)]
+        author: String,
+
+        /// File or directory to look in.
+        #[arg(default_value = ".")]
+        path: String,
+
+        /// Ref at which to blame the files.
+        #[arg(short, long, default_value = "HEAD")]
+        gitref: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ColorChoice {
+    /// Use colors when writing to a terminal, unless NO_COLOR is set.
+    Auto,
+    /// Always use colors.
+    Always,
+    /// Never use colors.
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => colors::colors_by_default(is_terminal),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum KeymapFormat {
+    /// A JSON array with the keys and the description of each action.
+    Json,
+    /// A markdown table.
+    Md,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ReportFormat {
+    /// Ranges of lines with their commit and author.
+    Json,
+    /// A SARIF 2.1.0 log with a result for each range of lines.
+    Sarif,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum OutputFormat {
+    /// Tab-separated values, one row per line.
+    Tsv,
+    /// Comma-separated values, one row per line.
+    Csv,
 }
 
-fn main() -> AppResult<()> {
-    let args = Args::parse();
+fn main() {
+    if let Err(e) = run(Args::parse()) {
+        eprintln!("Error: {}", e);
+        // Blame errors have their own exit codes, so that scripts can tell
+        // what went wrong.
+        let code = e
+            .downcast_ref::<FileBlameError>()
+            .map_or(1, |e| e.exit_code());
+        process::exit(code);
+    }
+}
+
+fn run(args: Args) -> AppResult<()> {
+    // Without git, nothing works, so fail with a clear message before
+    // anything else, and in particular before taking over the terminal.
+    FileBlame::check_git()?;
+
+    match &args.command {
+        Some(Subcommand::Prime { path, gitref }) => {
+            let files = disk_cache::prime(path, gitref)?;
+            eprintln!("Primed the cache with {} files", files);
+            return Ok(());
+        }
+        Some(Subcommand::Keymap { output, config }) => {
+            let key_map = Config::load(config.as_deref())?.key_map();
+            match output {
+                KeymapFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&export::key_bindings(&key_map))?
+                ),
+                KeymapFormat::Md => print!("{}", export::key_bindings_markdown(&key_map)),
+            }
+            return Ok(());
+        }
+        Some(Subcommand::Owners {
+            author,
+            path,
+            gitref,
+        }) => return print_owners(author, path, gitref),
+        None => {}
+    }
+
+    #[cfg(unix)]
+    if let Some(socket_path) = &args.serve {
+        return blame::server::serve(socket_path, args.memory_budget);
+    }
+    #[cfg(not(unix))]
+    if args.serve.is_some() {
+        return Err("--serve is only supported on unix".into());
+    }
 
-    // Create an application.
-    let mut app = App::new(args.filepath, args.gitref);
+    if args.batch {
+        return run_batch(&parse_options(&args, None));
+    }
+
+    if args.list_themes {
+        for name in file_blame::syntax_themes() {
+            println!("{}", name);
+        }
+        return Ok(());
+    }
+
+    // In review mode, the files changed in the range or the commit are
+    // blamed at its end, starting with the first one.
+    let review = match (&args.range, &args.commit) {
+        (Some(range), _) => Some(Review::load(range)?),
+        (None, Some(commit)) => Some(Review::for_commit(commit)?),
+        (None, None) => None,
+    };
+    let gitref = match &review {
+        Some(review) => Some(review.gitref.clone()),
+        None => args.gitref.clone(),
+    };
 
-    // Initialize the terminal user interface.
+    // The file path is only optional when serving, in batch mode, or when
+    // it's given by a diff hunk or a range. Like the lines of a hunk, the
+    // lines of a fragment in the path are selected.
+    let (filepath, hunk_lines) = match (&args.hunk, &review) {
+        (Some(hunk), _) => {
+            let (filepath, lines) = parse_hunk(hunk)?;
+            (filepath, Some(lines))
+        }
+        (None, Some(review)) => (review.files[0].clone(), None),
+        (None, None) => {
+            let filepath = args.filepath.clone().expect("file path is required");
+            match parse_line_fragment(&filepath) {
+                Some((filepath, lines)) => (filepath, Some(lines)),
+                None => (filepath, None),
+            }
+        }
+    };
+
+    // A file outside of the repository, e.g. in an exported tarball, is
+    // blamed as the file with the same relative path in the repository.
+    let filepath = match &args.repo {
+        Some(repo) => {
+            FileBlame::map_into_repo(&filepath, repo, gitref.as_deref().unwrap_or("HEAD"))?
+        }
+        None => filepath,
+    };
+    // Report mistakes in the path or ref before starting, instead of only
+    // finding them once the blame is loaded. The path is checked before
+    // anything looks into its repository. Missing remote branches, e.g.
+    // ones which were pruned, can be fetched before checking the ref.
+    FileBlame::validate_path(&filepath)?;
+    // Without a ref, uncommitted changes are blamed too, except for files
+    // outside of the repository, whose working tree is a different one.
+    let gitref = match gitref {
+        Some(gitref) => gitref,
+        None if args.repo.is_some() => "HEAD".to_string(),
+        None => FileBlame::default_ref(&filepath).to_string(),
+    };
+    fetch_remote_branch(&filepath, &gitref, args.fetch)?;
+    FileBlame::validate(&filepath, &gitref)?;
+    if let Some(end) = &args.reverse {
+        if gitref == file_blame::WORKTREE {
+            return Err("--reverse needs a commit to start from, not WORKTREE".into());
+        }
+        FileBlame::validate_ref(&filepath, end)?;
+    }
+
+    // Mistakes in the config file are reported before taking over the
+    // terminal. The theme also decides how the contents are highlighted.
+    let config = Config::load(args.config.as_deref())?;
+    let mut theme = config.theme(args.theme.as_deref())?;
+    if let Some(syntax_theme) = &args.syntax_theme {
+        theme.syntax = syntax_theme.clone();
+    }
+
+    let parse_options = ParseOptions {
+        syntax_theme: theme.syntax.clone(),
+        ..parse_options(&args, Some(&filepath))
+    };
+
+    if let Some(format) = args.output {
+        let colored = args.color.enabled(io::stdout().is_terminal());
+        return print_blame(&filepath, &gitref, &parse_options, format, colored);
+    }
+
+    if let Some(report_path) = args.report {
+        return write_report(
+            &filepath,
+            &gitref,
+            &parse_options,
+            &report_path,
+            args.report_format,
+        );
+    }
+
+    if let Some(sidecar_path) = args.emit_annotations {
+        return write_annotation_sidecar(&filepath, &gitref, &parse_options, &sidecar_path);
+    }
+
+    // Initialize the terminal user interface first, so that the progress can
+    // be shown while the blame is loaded.
     let backend = CrosstermBackend::new(io::stderr());
     let terminal = Terminal::new(backend)?;
     let events = EventHandler::new(250);
     let mut tui = Tui::new(terminal, events);
+
+    // Create an application, which loads the blame in the background and
+    // sends its progress to the main loop, which picks it up when it's done.
+    let sender = tui.events.sender();
+    let mut app = App::loading(filepath, gitref, parse_options, move |progress| {
+        let _ = sender.send(Event::Progress(progress));
+    });
+    if let Some(review) = review {
+        app.set_review(review);
+    }
+    if let Some((start, end)) = hunk_lines {
+        app.select_lines(start, end);
+    }
+    if !args.color.enabled(io::stderr().is_terminal()) {
+        app.color_support = ColorSupport::None;
+    }
+    app.accessible = args.accessible;
+    app.commit_accents = args.commit_colors;
+    app.heatmap = args.heatmap;
+    app.message_cleaner = MessageCleaner {
+        strip_commit_types: args.strip_commit_types,
+    };
+    app.commit_type_badges = args.commit_type_badges;
+    app.key_map = config.key_map();
+    app.set_theme(theme);
+    app.memory_budget = args.memory_budget;
+    if let Some(locale) = &args.locale {
+        app.locale = Locale::from_name(locale);
+    }
+    app.set_date_format(args.date_format);
+    for kind in &args.hide_columns {
+        app.toggle_column(*kind);
+    }
+    if args.age_gauge {
+        app.show_age_gauge();
+    }
+    app.set_copy_detection(app.parse_options.copy_detection);
+    app.age_warning = AgeWarning {
+        older_than: args.warn_older_than,
+        newer_than: args.warn_newer_than,
+    };
+    if let (Some(pattern), Some(url)) = (&args.issue_pattern, &args.issue_url) {
+        app.issue_linker = Some(IssueLinker::new(pattern, url)?);
+    }
+    if let Some(floor) = &args.floor {
+        app.set_floor(Some(floor))?;
+    }
+    app.max_depth = args.max_depth;
+    if let Some(path) = args.annotations {
+        app.set_annotation_provider(Box::new(JsonAnnotationProvider::load(&path)?));
+    }
+
+    let mut presenter = args
+        .share_cursor
+        .as_deref()
+        .map(Presenter::open)
+        .transpose()?;
+
     tui.init()?;
 
     // Start the main loop.
@@ -39,17 +571,331 @@ fn main() -> AppResult<()> {
         // Handle events.
         match tui.events.next()? {
             Event::Tick => app.tick(),
+            Event::Progress(progress) => app.set_progress(progress),
             Event::Key(key_event) => handle_key_events(key_event, &mut app)?,
-            Event::Mouse(_) => {}
+            Event::Mouse(mouse_event) => handle_mouse_events(mouse_event, &mut app)?,
+            Event::Paste(text) => handle_paste_events(&text, &mut app),
             Event::Resize(_, _) => {}
         }
+
+        // Share the selected line if it changed. If it can't be shared, e.g.
+        // because the editor listening on the socket exited, stop sharing it.
+        if let (Some(p), Some(location)) = (&mut presenter, app.cursor_location()) {
+            if let Err(e) = p.update(&location) {
+                app.popup = Some(Popup::Message(format!("Stopped sharing the cursor: {}", e)));
+                presenter = None;
+            }
+        }
+
+        // Run any external command requested by the application with the
+        // terminal user interface suspended.
+        if let Some(command) = app.external_command.take() {
+            tui.suspend()?;
+            run_external_command(&command)?;
+            tui.resume()?;
+        }
     }
 
     // Exit the user interface.
     tui.exit()?;
 
-    if app.load_err.is_some() {
-        println!("Error: {}", app.load_err.as_ref().unwrap());
+    match app.load_err.take() {
+        Some(err) => Err(err.into()),
+        None => Ok(()),
+    }
+}
+
+// Parse the argument of --hunk into the path of the file and the lines of
+// the hunk in it.
+fn parse_hunk(hunk: &str) -> AppResult<(String, (usize, usize))> {
+    if hunk == "-" {
+        let mut diff = String::new();
+        io::stdin().read_to_string(&mut diff)?;
+        let (path, lines) = line_map::first_hunk(&diff).ok_or("no hunk found in the diff")?;
+        return Ok((repo_relative_path(&path), lines));
+    }
+
+    let (path, header) = hunk
+        .split_once(":@@")
+        .ok_or("expected the hunk as \"path:@@ -120,7 +121,9 @@\"")?;
+    let lines = line_map::hunk_new_lines(&format!("@@{}", header))
+        .ok_or_else(|| format!("invalid hunk header '@@{}'", header))?;
+    Ok((repo_relative_path(path), lines))
+}
+
+// Split a fragment with lines, like in links to files on GitHub, off a path,
+// e.g. "src/app.rs#L100-L140" into the path and the first and last line.
+// GitLab's "#L100-140" and single lines, e.g. "#L100", work too. Paths of
+// existing files with a "#" in their names are left alone.
+fn parse_line_fragment(path: &str) -> Option<(String, (usize, usize))> {
+    if Path::new(path).exists() {
+        return None;
+    }
+    let (path, fragment) = path.rsplit_once("#L")?;
+    let (start, end) = match fragment.split_once('-') {
+        Some((start, end)) => (start, end.strip_prefix('L').unwrap_or(end)),
+        None => (fragment, fragment),
+    };
+    let (start, end): (usize, usize) = (start.parse().ok()?, end.parse().ok()?);
+    if start == 0 || end == 0 {
+        return None;
+    }
+    Some((path.to_string(), (start.min(end), start.max(end))))
+}
+
+// Fetch the branch of a remote-tracking ref, if it's missing and the user
+// agrees to fetching it, or always if asked to with --fetch, e.g. because
+// the ref is stale.
+fn fetch_remote_branch(filepath: &str, gitref: &str, always: bool) -> AppResult<()> {
+    let Some((git_root_dir, _)) = FileBlame::repo_paths(filepath) else {
+        return Ok(());
+    };
+    let Some((remote, branch)) = FileBlame::remote_tracking_branch(&git_root_dir, gitref) else {
+        return Ok(());
+    };
+    if !always {
+        if FileBlame::rev_parse(filepath, gitref).is_ok() {
+            return Ok(());
+        }
+        // Without a terminal to ask on, the ref is reported as not found.
+        if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+            return Ok(());
+        }
+        eprint!(
+            "Ref '{}' not found. Fetch {} from {}? [Y/n] ",
+            gitref, branch, remote
+        );
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if answer.trim().to_lowercase().starts_with('n') {
+            return Ok(());
+        }
+    }
+    FileBlame::fetch_remote_branch(&git_root_dir, &remote, &branch)?;
+    Ok(())
+}
+
+// Paths in diffs are relative to the root of the repository, so they are
+// looked up there if they don't exist relative to the current directory.
+fn repo_relative_path(path: &str) -> String {
+    if Path::new(path).exists() {
+        return path.to_string();
+    }
+    let root = Command::new("git")
+        .arg("rev-parse")
+        .arg("--show-toplevel")
+        .output();
+    match root {
+        Ok(output) if output.status.success() => {
+            Path::new(String::from_utf8_lossy(&output.stdout).trim())
+                .join(path)
+                .display()
+                .to_string()
+        }
+        _ => path.to_string(),
+    }
+}
+
+// Prints the files with lines last changed by an author, one per line with
+// the number of lines and their share of the file, and the total at the end.
+fn print_owners(author: &str, path: &str, gitref: &str) -> AppResult<()> {
+    let shares = owners::author_shares(path, gitref, author)?;
+    if shares.is_empty() {
+        return Err(format!("no lines by '{}' in {} at {}", author, path, gitref).into());
+    }
+    let mut stdout = io::stdout().lock();
+    for share in &shares {
+        writeln!(
+            stdout,
+            "{:>7} {:>6.1}%  {}",
+            share.lines,
+            share.ratio() * 100.0,
+            share.path
+        )?;
+    }
+    let lines: usize = shares.iter().map(|s| s.lines).sum();
+    let files = if shares.len() == 1 { "file" } else { "files" };
+    writeln!(stdout, "{:>7} lines in {} {}", lines, shares.len(), files)?;
+    Ok(())
+}
+
+// Prints the blame for a file in a plain-text format, without starting the
+// terminal user interface.
+fn print_blame(
+    filepath: &str,
+    gitref: &str,
+    options: &ParseOptions,
+    format: OutputFormat,
+    colored: bool,
+) -> AppResult<()> {
+    let mut commit_cache = HashMap::new();
+    let file_blame = FileBlame::parse_with_options(filepath, gitref, &mut commit_cache, options)?;
+    let format = match format {
+        OutputFormat::Tsv => Delimited::Tsv,
+        OutputFormat::Csv => Delimited::Csv,
+    };
+    print!(
+        "{}",
+        export::delimited(&file_blame, &commit_cache, format, colored)
+    );
+    Ok(())
+}
+
+// Writes the ownership report for a file to the given path, without starting
+// the terminal user interface.
+fn write_report(
+    filepath: &str,
+    gitref: &str,
+    options: &ParseOptions,
+    report_path: &str,
+    format: ReportFormat,
+) -> AppResult<()> {
+    let mut commit_cache = HashMap::new();
+    let file_blame = FileBlame::parse_with_options(filepath, gitref, &mut commit_cache, options)?;
+    let report = export::ownership_report(&file_blame, &commit_cache);
+    let json = match format {
+        ReportFormat::Json => serde_json::to_string_pretty(&report)?,
+        ReportFormat::Sarif => serde_json::to_string_pretty(&export::ownership_sarif(&report))?,
+    };
+    fs::write(report_path, json)?;
+    Ok(())
+}
+
+// Writes the per-line annotation map for editor plugins to the given path,
+// without starting the terminal user interface.
+fn write_annotation_sidecar(
+    filepath: &str,
+    gitref: &str,
+    options: &ParseOptions,
+    sidecar_path: &str,
+) -> AppResult<()> {
+    let mut commit_cache = HashMap::new();
+    let file_blame = FileBlame::parse_with_options(filepath, gitref, &mut commit_cache, options)?;
+    let sidecar = export::annotation_sidecar(&file_blame, &commit_cache, age::now());
+    fs::write(sidecar_path, serde_json::to_string(&sidecar)?)?;
+    Ok(())
+}
+
+// The options for blaming a file, from the arguments. Without a file, e.g. in
+// batch mode, where the files may be in different repositories, only an
+// ignore revs file which was given is used.
+fn parse_options(args: &Args, filepath: Option<&str>) -> ParseOptions {
+    ParseOptions {
+        max_line_length: args.max_line_length,
+        highlight_line_limit: args.highlight_line_limit,
+        line_range: args.line_range,
+        copy_detection: match (args.detect_moves, args.detect_copies) {
+            (false, 0) => CopyDetection::Off,
+            (true, 0) => CopyDetection::Moves,
+            (_, 1) => CopyDetection::Copies,
+            (_, _) => CopyDetection::MoreCopies,
+        },
+        no_fetch: args.no_fetch,
+        ignore_revs_file: match &args.ignore_revs_file {
+            Some(file) if file.is_empty() => None,
+            Some(file) => Some(
+                FileBlame::absolute_path(file)
+                    .map_or_else(|| file.clone(), |path| path.display().to_string()),
+            ),
+            None => filepath
+                .and_then(FileBlame::repo_paths)
+                .and_then(|(git_root_dir, _)| FileBlame::ignore_revs_file(&git_root_dir)),
+        },
+        ignore_revs: Vec::new(),
+        syntax_theme: args
+            .syntax_theme
+            .clone()
+            .unwrap_or_else(|| file_blame::DEFAULT_SYNTAX_THEME.to_string()),
+        reverse_until: args.reverse.clone(),
+    }
+}
+
+// Reads "path<TAB>ref" lines from stdin, where the ref defaults to HEAD, and
+// prints the ownership report of each file as a line of JSON. Files which
+// can't be blamed get a line with the error instead, so that one bad input
+// doesn't stop the batch.
+fn run_batch(options: &ParseOptions) -> AppResult<()> {
+    let mut commit_cache = HashMap::new();
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (filepath, gitref) = line.split_once('\t').unwrap_or((&line, "HEAD"));
+
+        let result =
+            match FileBlame::parse_with_options(filepath, gitref, &mut commit_cache, options) {
+                Ok(file_blame) => {
+                    serde_json::to_string(&export::ownership_report(&file_blame, &commit_cache))?
+                }
+                Err(e) => serde_json::json!({
+                    "path": filepath,
+                    "ref": gitref,
+                    "error": e.to_string(),
+                })
+                .to_string(),
+            };
+        writeln!(stdout, "{}", result)?;
+    }
+    Ok(())
+}
+
+// Runs an external command in the user's terminal, and waits for the user
+// to acknowledge the result before returning to the blame view.
+fn run_external_command(command: &ExternalCommand) -> AppResult<()> {
+    println!("$ {} {}", command.program, command.args.join(" "));
+    match Command::new(&command.program)
+        .args(&command.args)
+        .current_dir(&command.current_dir)
+        .status()
+    {
+        Ok(status) if status.success() => println!("Command finished successfully."),
+        Ok(status) => println!("Command failed: {}", status),
+        Err(e) => println!("Failed to run command: {}", e),
     }
+
+    print!("Press Enter to return to blame...");
+    io::stdout().flush()?;
+    io::stdin().lock().read_line(&mut String::new())?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hunks() {
+        let (path, lines) = parse_hunk("src/main.rs:@@ -120,7 +121,9 @@").unwrap();
+        assert_eq!(path, "src/main.rs");
+        assert_eq!(lines, (121, 129));
+        assert!(parse_hunk("src/main.rs").is_err());
+        assert!(parse_hunk("src/main.rs:@@ nonsense @@").is_err());
+    }
+
+    #[test]
+    fn parses_line_fragments() {
+        assert_eq!(
+            parse_line_fragment("src/app.rs#L100-L140"),
+            Some(("src/app.rs".to_string(), (100, 140)))
+        );
+        assert_eq!(
+            parse_line_fragment("src/app.rs#L100-140"),
+            Some(("src/app.rs".to_string(), (100, 140)))
+        );
+        assert_eq!(
+            parse_line_fragment("src/app.rs#L140-L100"),
+            Some(("src/app.rs".to_string(), (100, 140)))
+        );
+        assert_eq!(
+            parse_line_fragment("src/app.rs#L7"),
+            Some(("src/app.rs".to_string(), (7, 7)))
+        );
+        assert_eq!(parse_line_fragment("src/app.rs#L0"), None);
+        assert_eq!(parse_line_fragment("src/app.rs#Lx"), None);
+        assert_eq!(parse_line_fragment("src/app.rs"), None);
+        // Existing files are left alone.
+        assert_eq!(parse_line_fragment("src/main.rs"), None);
+    }
+}
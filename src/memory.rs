@@ -0,0 +1,341 @@
+use crate::file_blame::{BlameLine, Commit, FileBlame, HighlightCache};
+use lru::LruCache;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::mem::{size_of, size_of_val};
+
+// Default memory budget for cached blames, highlighted contents and
+// commits, in bytes.
+pub const DEFAULT_MEMORY_BUDGET: usize = 256 * 1024 * 1024;
+
+// Approximate number of bytes used by a value, including what it owns on
+// the heap. Used for keeping caches within a memory budget, so it only needs
+// to be in the right ballpark.
+pub trait MemorySize {
+    fn memory_size(&self) -> usize;
+}
+
+impl MemorySize for String {
+    fn memory_size(&self) -> usize {
+        size_of::<String>() + self.capacity()
+    }
+}
+
+impl MemorySize for Commit {
+    fn memory_size(&self) -> usize {
+        size_of::<Commit>()
+            + self.sha.capacity()
+            + self.author.capacity()
+            + self.author_email.capacity()
+            + self.commit_message.capacity()
+            + self.parent_commit_sha.as_ref().map_or(0, |s| s.capacity())
+            + self.timestamp.capacity()
+            + self
+                .trailers
+                .iter()
+                .map(|(k, v)| k.memory_size() + v.iter().map(|s| s.memory_size()).sum::<usize>())
+                .sum::<usize>()
+    }
+}
+
+impl MemorySize for BlameLine {
+    fn memory_size(&self) -> usize {
+        size_of::<BlameLine>()
+            + self.commit_sha.capacity()
+            + self.contents.capacity()
+            + self.plain_contents.capacity()
+            + self.line_number.capacity()
+            + self.previous_sha.as_ref().map_or(0, |s| s.capacity())
+            + self.previous_path.as_ref().map_or(0, |p| p.capacity())
+            + self.original_path.as_ref().map_or(0, |p| p.capacity())
+            + self.skipped_commit.as_ref().map_or(0, |s| s.capacity())
+    }
+}
+
+impl MemorySize for FileBlame {
+    fn memory_size(&self) -> usize {
+        size_of::<FileBlame>()
+            + self.filepath.capacity()
+            + self.commit_sha.capacity()
+            + self
+                .blame_lines
+                .iter()
+                .map(|l| l.memory_size())
+                .sum::<usize>()
+    }
+}
+
+// Parse a size like "512K", "256M" or "1G", or a plain number of bytes.
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let (number, unit) = match s.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&s[..i], 1024),
+        Some((i, 'M' | 'm')) => (&s[..i], 1024 * 1024),
+        Some((i, 'G' | 'g')) => (&s[..i], 1024 * 1024 * 1024),
+        _ => (s, 1),
+    };
+    let invalid = || format!("invalid size '{}', expected e.g. 512K, 256M or 1G", s);
+    let n = number.parse::<usize>().map_err(|_| invalid())?;
+    n.checked_mul(unit)
+        .ok_or_else(|| format!("size '{}' is too large", s))
+}
+
+// The order in which the commits in a commit cache were last used, least
+// recently used first. The commit cache itself is a plain map, which blames
+// are parsed with.
+pub type CommitRecency = LruCache<String, ()>;
+
+// Total memory size of the commits in a cache.
+pub fn commit_cache_size(commit_cache: &HashMap<String, Commit>) -> usize {
+    commit_cache
+        .iter()
+        .map(|(sha, commit)| sha.memory_size() + commit.memory_size())
+        .sum()
+}
+
+// Total memory size of the highlighted contents in a cache.
+pub fn highlight_cache_size(highlights: &HighlightCache) -> usize {
+    highlights
+        .iter()
+        .map(|(key, contents)| {
+            size_of_val(key) + contents.iter().map(|c| c.memory_size()).sum::<usize>()
+        })
+        .sum()
+}
+
+// Evict the least recently used commits from the cache until it fits in the
+// budget, keeping the commits which some blames use, given in the order in
+// which they were used, least recently used first. Their commits count as
+// used just now, and commits which were added to the cache since the last
+// time as used just before. Evicted commits are loaded again when they are
+// needed.
+fn trim_commit_cache(
+    commit_cache: &mut HashMap<String, Commit>,
+    recency: &mut CommitRecency,
+    budget: usize,
+    in_use: &[&FileBlame],
+) {
+    for sha in commit_cache.keys() {
+        if !recency.contains(sha) {
+            recency.put(sha.clone(), ());
+        }
+    }
+    let mut used = HashSet::new();
+    for line in in_use.iter().flat_map(|b| &b.blame_lines) {
+        recency.promote(&line.commit_sha);
+        used.insert(line.commit_sha.as_str());
+    }
+
+    let mut size = commit_cache_size(commit_cache);
+    while size > budget {
+        match recency.peek_lru() {
+            Some((sha, _)) if !used.contains(sha.as_str()) => {
+                let (sha, _) = recency.pop_lru().unwrap();
+                if let Some(commit) = commit_cache.remove(&sha) {
+                    size -= sha.memory_size() + commit.memory_size();
+                }
+            }
+            _ => break,
+        }
+    }
+}
+
+// Keep the caches together within the budget, along with the blames which
+// are shown, which are always kept. The least recently used blames are
+// evicted first, then the least recently used highlighted contents, and then
+// the least recently used commits which no blame left uses.
+pub fn trim_caches<K: Hash + Eq>(
+    blames: &mut LruCache<K, FileBlame>,
+    highlights: &mut HighlightCache,
+    commit_cache: &mut HashMap<String, Commit>,
+    recency: &mut CommitRecency,
+    budget: usize,
+    shown: &[&FileBlame],
+) {
+    let shown_size: usize = shown.iter().map(|b| b.memory_size()).sum();
+    let mut blames_size: usize = blames.iter().map(|(_, b)| b.memory_size()).sum();
+    let mut highlights_size = highlight_cache_size(highlights);
+    let commits_size = commit_cache_size(commit_cache);
+    let over_budget = |blames_size, highlights_size| {
+        shown_size + blames_size + highlights_size + commits_size > budget
+    };
+    while over_budget(blames_size, highlights_size) {
+        let Some((_, blame)) = blames.pop_lru() else {
+            break;
+        };
+        blames_size -= blame.memory_size();
+    }
+    while over_budget(blames_size, highlights_size) {
+        let Some((key, contents)) = highlights.pop_lru() else {
+            break;
+        };
+        highlights_size -=
+            size_of_val(&key) + contents.iter().map(|c| c.memory_size()).sum::<usize>();
+    }
+
+    let in_use: Vec<&FileBlame> = blames
+        .iter()
+        .rev()
+        .map(|(_, b)| b)
+        .chain(shown.iter().copied())
+        .collect();
+    trim_commit_cache(
+        commit_cache,
+        recency,
+        budget.saturating_sub(shown_size + blames_size + highlights_size),
+        &in_use,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_blame::BlameLine;
+
+    fn commit(sha: &str) -> Commit {
+        Commit {
+            sha: sha.to_string(),
+            ..Commit::default()
+        }
+    }
+
+    // Size of a commit in the commit cache, along with its key.
+    fn entry_size(sha: &str) -> usize {
+        sha.to_string().memory_size() + commit(sha).memory_size()
+    }
+
+    fn blame(shas: &[&str]) -> FileBlame {
+        FileBlame {
+            blame_lines: shas
+                .iter()
+                .map(|sha| BlameLine {
+                    commit_sha: sha.to_string(),
+                    contents: String::new(),
+                    plain_contents: String::new(),
+                    line_number: String::new(),
+                    truncated: false,
+                    previous_sha: None,
+                    previous_path: None,
+                    original_path: None,
+                    original_line: 0,
+                    skipped_commit: None,
+                })
+                .collect(),
+            filepath: String::new(),
+            commit_sha: String::new(),
+            highlighted: false,
+            lfs_pointer: None,
+        }
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("123"), Ok(123));
+        assert_eq!(parse_size("512K"), Ok(512 * 1024));
+        assert_eq!(parse_size(" 256m "), Ok(256 * 1024 * 1024));
+        assert_eq!(parse_size("1G"), Ok(1024 * 1024 * 1024));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("-1M").is_err());
+        assert!(parse_size("1T").is_err());
+        assert!(parse_size(&format!("{}G", usize::MAX)).is_err());
+        assert!(parse_size(&format!("{}K", usize::MAX / 1024 + 1)).is_err());
+    }
+
+    #[test]
+    fn evicts_least_recently_used_commits() {
+        let mut commit_cache = HashMap::new();
+        let mut recency = CommitRecency::unbounded();
+        let mut blames: LruCache<u32, FileBlame> = LruCache::unbounded();
+        let mut highlights = HighlightCache::unbounded();
+        let size = entry_size("a");
+
+        for sha in ["a", "b", "c"] {
+            commit_cache.insert(sha.to_string(), commit(sha));
+            recency.put(sha.to_string(), ());
+        }
+        // Using a makes b the least recently used commit.
+        recency.promote("a");
+        trim_caches(
+            &mut blames,
+            &mut highlights,
+            &mut commit_cache,
+            &mut recency,
+            2 * size,
+            &[],
+        );
+        let mut left: Vec<&String> = commit_cache.keys().collect();
+        left.sort();
+        assert_eq!(left, ["a", "c"]);
+    }
+
+    #[test]
+    fn evicts_blames_before_their_commits() {
+        let mut commit_cache = HashMap::new();
+        let mut recency = CommitRecency::unbounded();
+        let mut blames: LruCache<u32, FileBlame> = LruCache::unbounded();
+        let mut highlights = HighlightCache::unbounded();
+        for sha in ["a", "b", "c"] {
+            commit_cache.insert(sha.to_string(), commit(sha));
+        }
+        blames.put(1, blame(&["a"]));
+        blames.put(2, blame(&["b"]));
+        let shown = blame(&["c"]);
+
+        // Nothing fits, so all cached blames are evicted, and then all
+        // commits but the one of the shown blame.
+        trim_caches(
+            &mut blames,
+            &mut highlights,
+            &mut commit_cache,
+            &mut recency,
+            0,
+            &[&shown],
+        );
+        assert!(blames.is_empty());
+        assert_eq!(commit_cache.keys().collect::<Vec<_>>(), ["c"]);
+
+        // Commits of cached blames are kept.
+        commit_cache.insert("a".to_string(), commit("a"));
+        blames.put(1, blame(&["a"]));
+        let budget = shown.memory_size()
+            + blames.peek(&1).unwrap().memory_size()
+            + entry_size("a")
+            + entry_size("c");
+        trim_caches(
+            &mut blames,
+            &mut highlights,
+            &mut commit_cache,
+            &mut recency,
+            budget,
+            &[&shown],
+        );
+        assert!(blames.contains(&1));
+        assert_eq!(commit_cache.len(), 2);
+    }
+
+    #[test]
+    fn evicts_highlights_before_commits() {
+        let mut commit_cache = HashMap::new();
+        let mut recency = CommitRecency::unbounded();
+        let mut blames: LruCache<u32, FileBlame> = LruCache::unbounded();
+        let mut highlights = HighlightCache::unbounded();
+        commit_cache.insert("a".to_string(), commit("a"));
+        highlights.put(1, vec!["x".repeat(100)]);
+        highlights.put(2, vec!["y".repeat(100)]);
+        highlights.get(&1);
+
+        let budget = commit_cache_size(&commit_cache) + highlight_cache_size(&highlights) - 1;
+        trim_caches(
+            &mut blames,
+            &mut highlights,
+            &mut commit_cache,
+            &mut recency,
+            budget,
+            &[],
+        );
+        assert!(highlights.contains(&1));
+        assert!(!highlights.contains(&2));
+        assert_eq!(commit_cache.len(), 1);
+    }
+}
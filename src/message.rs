@@ -0,0 +1,98 @@
+use std::borrow::Cow;
+
+// Cleans up the summaries of commit messages for the narrow MESSAGE column:
+// trailing whitespace is dropped and tabs become spaces, and optionally the
+// type of conventional commits is dropped, e.g. "fix(parser): " of
+// "fix(parser): handle empty input", which leaves room for the rest.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MessageCleaner {
+    pub strip_commit_types: bool,
+}
+
+impl MessageCleaner {
+    pub fn clean<'a>(&self, message: &'a str) -> Cow<'a, str> {
+        let mut message = message.trim_end();
+        if self.strip_commit_types {
+            if let Some(len) = commit_type_len(message) {
+                message = message[len..].trim_start();
+            }
+        }
+        if message.contains('\t') {
+            Cow::Owned(message.replace('\t', " "))
+        } else {
+            Cow::Borrowed(message)
+        }
+    }
+}
+
+// Length of the type of a conventional commit message, e.g. "feat: ",
+// "fix(scope): " or "refactor!: ", including the colon.
+pub fn commit_type_len(message: &str) -> Option<usize> {
+    parse_commit_type(message).map(|(_, len)| len)
+}
+
+// The type of a conventional commit message without its scope, e.g. "fix"
+// of "fix(parser): handle empty input".
+pub fn commit_type(message: &str) -> Option<&str> {
+    parse_commit_type(message).map(|(kind, _)| kind)
+}
+
+// The type of a conventional commit message and the length of its prefix.
+// Types are in lower case, so that e.g. "Note: " isn't taken for one.
+fn parse_commit_type(message: &str) -> Option<(&str, usize)> {
+    let (prefix, _) = message.split_once(':')?;
+    let kind = prefix.strip_suffix('!').unwrap_or(prefix);
+    let kind = match kind.split_once('(') {
+        Some((kind, scope)) if scope.ends_with(')') && !scope[..scope.len() - 1].contains(')') => {
+            kind
+        }
+        Some(_) => return None,
+        None => kind,
+    };
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_lowercase()) {
+        return None;
+    }
+    Some((kind, prefix.len() + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_commit_types() {
+        assert_eq!(commit_type_len("feat: add x"), Some(5));
+        assert_eq!(commit_type_len("fix(parser): x"), Some(12));
+        assert_eq!(commit_type_len("refactor!: x"), Some(10));
+        assert_eq!(commit_type_len("feat(api)!: x"), Some(11));
+        assert_eq!(commit_type_len("Note: x"), None);
+        assert_eq!(commit_type_len("fix(a)b): x"), None);
+        assert_eq!(commit_type_len(": x"), None);
+        assert_eq!(commit_type_len("no type"), None);
+    }
+
+    #[test]
+    fn parses_commit_types() {
+        assert_eq!(commit_type("fix(parser): handle empty input"), Some("fix"));
+        assert_eq!(commit_type("feat!: drop support"), Some("feat"));
+        assert_eq!(commit_type("docs: x"), Some("docs"));
+        assert_eq!(commit_type("Merge branch 'main'"), None);
+        assert_eq!(commit_type("WIP: x"), None);
+    }
+
+    #[test]
+    fn cleans_messages() {
+        let cleaner = MessageCleaner::default();
+        assert_eq!(cleaner.clean("fix: a\tb  "), "fix: a b");
+        assert!(matches!(cleaner.clean("plain"), Cow::Borrowed("plain")));
+
+        let cleaner = MessageCleaner {
+            strip_commit_types: true,
+        };
+        assert_eq!(
+            cleaner.clean("fix(parser):  handle empty input"),
+            "handle empty input"
+        );
+        assert_eq!(cleaner.clean("Note: keep this"), "Note: keep this");
+    }
+}
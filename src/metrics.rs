@@ -0,0 +1,74 @@
+use std::io;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Counters for diagnosing performance, shared by everything which runs git
+// or uses the commit cache, so that they don't need access to the app.
+static GIT_COMMANDS: AtomicU64 = AtomicU64::new(0);
+static COMMIT_CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static COMMIT_CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+static LAST_GIT_COMMAND: Mutex<Option<(String, Duration)>> = Mutex::new(None);
+
+// Run a command and wait for its output, like `Command::output`, recording
+// how long it took.
+pub fn output(command: &mut Command) -> io::Result<Output> {
+    let start = Instant::now();
+    let output = command.output();
+    let elapsed = start.elapsed();
+
+    // Only the subcommand is recorded, e.g. "blame", as the arguments can
+    // be long.
+    let name = command
+        .get_args()
+        .next()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .unwrap_or_default();
+    GIT_COMMANDS.fetch_add(1, Ordering::Relaxed);
+    if let Ok(mut last) = LAST_GIT_COMMAND.lock() {
+        *last = Some((name, elapsed));
+    }
+    output
+}
+
+// Record a lookup in the commit cache.
+pub fn record_commit_cache_lookup(hit: bool) {
+    let counter = if hit {
+        &COMMIT_CACHE_HITS
+    } else {
+        &COMMIT_CACHE_MISSES
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+// The counters at a point in time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    pub git_commands: u64,
+    // Subcommand and duration of the last git command which was run.
+    pub last_git_command: Option<(String, Duration)>,
+    pub commit_cache_hits: u64,
+    pub commit_cache_misses: u64,
+}
+
+impl Snapshot {
+    // Share of commit cache lookups which were hits, between 0 and 1.
+    pub fn commit_cache_hit_rate(&self) -> f64 {
+        let lookups = self.commit_cache_hits + self.commit_cache_misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.commit_cache_hits as f64 / lookups as f64
+        }
+    }
+}
+
+pub fn snapshot() -> Snapshot {
+    Snapshot {
+        git_commands: GIT_COMMANDS.load(Ordering::Relaxed),
+        last_git_command: LAST_GIT_COMMAND.lock().ok().and_then(|last| last.clone()),
+        commit_cache_hits: COMMIT_CACHE_HITS.load(Ordering::Relaxed),
+        commit_cache_misses: COMMIT_CACHE_MISSES.load(Ordering::Relaxed),
+    }
+}
@@ -0,0 +1,73 @@
+use crate::disk_cache::DiskCache;
+use crate::file_blame::{FileBlame, FileBlameError, ParseOptions};
+use crate::stats::FileStats;
+use std::collections::HashMap;
+use std::path::Path;
+
+// Number of lines of a file last changed by an author, for `blame owners`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileShare {
+    // Path of the file, relative to the root of the repository.
+    pub path: String,
+    pub lines: usize,
+    pub total_lines: usize,
+}
+
+impl FileShare {
+    // Share of the file's lines last changed by the author, between 0 and 1.
+    pub fn ratio(&self) -> f64 {
+        if self.total_lines == 0 {
+            0.0
+        } else {
+            self.lines as f64 / self.total_lines as f64
+        }
+    }
+}
+
+// The files in a directory, or a single file, with lines last changed by an
+// author at a ref, most lines first. Authors are matched by their names
+// containing the given text, ignoring case, like `git log --author`.
+pub fn author_shares(
+    path: &str,
+    gitref: &str,
+    author: &str,
+) -> Result<Vec<FileShare>, FileBlameError> {
+    let (git_root_dir, relative_path) = FileBlame::try_repo_paths(path)?;
+    let commit_sha = DiskCache::resolve(&git_root_dir, gitref)
+        .ok_or_else(|| FileBlameError::Unknown(format!("unknown ref '{}'", gitref)))?;
+    let files = FileBlame::files_at_commit(&git_root_dir, &commit_sha, &relative_path)?;
+
+    // Only the authors of the lines are needed, so nothing is highlighted.
+    let options = ParseOptions {
+        highlight_line_limit: 0,
+        ..ParseOptions::default()
+    };
+    let author = author.to_lowercase();
+    let mut commit_cache = HashMap::new();
+    let mut shares = vec![];
+    for file in files {
+        let filepath = Path::new(&git_root_dir).join(&file);
+        let file_blame = FileBlame::parse_with_options(
+            &filepath.to_string_lossy(),
+            &commit_sha,
+            &mut commit_cache,
+            &options,
+        )?;
+        let stats = FileStats::compute(&file_blame, &commit_cache);
+        let lines = stats
+            .authors
+            .iter()
+            .filter(|a| a.author.to_lowercase().contains(&author))
+            .map(|a| a.lines)
+            .sum();
+        if lines > 0 {
+            shares.push(FileShare {
+                path: file,
+                lines,
+                total_lines: stats.total_lines,
+            });
+        }
+    }
+    shares.sort_by(|a, b| b.lines.cmp(&a.lines).then(a.path.cmp(&b.path)));
+    Ok(shares)
+}
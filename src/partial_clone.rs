@@ -0,0 +1,156 @@
+use crate::file_blame::{git_lines, FileBlameError, WORKTREE};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// The remote which a partial clone, e.g. one made with
+// `git clone --filter=blob:none`, fetches missing objects from on demand.
+// Blaming a file in such a clone can fetch each old version of the file one
+// by one, so the fetches are counted for showing how the loading is going.
+#[derive(Debug, Clone)]
+pub struct Promisor {
+    pub remote: String,
+    // Directory of the repository's packs, where each fetch adds a pack with
+    // a ".promisor" file next to it.
+    pack_dir: PathBuf,
+    // Number of promisor packs when blaming started.
+    initial_packs: usize,
+}
+
+impl Promisor {
+    // The promisor remote of the repository with the given root, if it's a
+    // partial clone.
+    pub fn detect(git_root_dir: &str) -> Option<Promisor> {
+        let remote = promisor_remote(git_root_dir)?;
+        let git_dir = git_lines(git_root_dir, &["rev-parse", "--git-common-dir"])
+            .ok()?
+            .concat();
+        let pack_dir = Path::new(git_root_dir).join(git_dir).join("objects/pack");
+        Some(Promisor {
+            remote,
+            initial_packs: count_promisor_packs(&pack_dir),
+            pack_dir,
+        })
+    }
+
+    // Number of times objects were fetched from the remote since blaming
+    // started.
+    pub fn fetches(&self) -> usize {
+        count_promisor_packs(&self.pack_dir).saturating_sub(self.initial_packs)
+    }
+}
+
+// Name of the remote which missing objects are fetched from, configured
+// either as the repository's partial clone extension or with
+// remote.<name>.promisor.
+fn promisor_remote(git_root_dir: &str) -> Option<String> {
+    let extension = git_lines(
+        git_root_dir,
+        &["config", "--get", "extensions.partialClone"],
+    )
+    .ok()
+    .and_then(|lines| lines.into_iter().next());
+    extension.or_else(|| {
+        git_lines(
+            git_root_dir,
+            &[
+                "config",
+                "--bool",
+                "--get-regexp",
+                r"^remote\..*\.promisor$",
+            ],
+        )
+        .ok()?
+        .iter()
+        .filter_map(|line| line.strip_suffix(" true"))
+        .filter_map(|key| key.strip_prefix("remote.")?.strip_suffix(".promisor"))
+        .map(|name| name.to_string())
+        .next()
+    })
+}
+
+fn count_promisor_packs(pack_dir: &Path) -> usize {
+    fs::read_dir(pack_dir).map_or(0, |entries| {
+        entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|x| x == "promisor"))
+            .count()
+    })
+}
+
+// The oldest commit which a blame of a file at a commit can go back to
+// without fetching objects, for passing as "^<commit>" to git blame, or None
+// if the whole history of the file is available locally.
+//
+// Going back from the commit, the versions of the file are checked until one
+// is missing. The commit which changed the file from that version is the
+// boundary: git blame needs its version of the file, but not the missing one
+// before it, and blames the lines from before on the boundary.
+pub fn local_boundary(
+    git_root_dir: &str,
+    commit_sha: &str,
+    relative_path: &str,
+) -> Result<Option<String>, FileBlameError> {
+    let rev = if commit_sha == WORKTREE {
+        "HEAD"
+    } else {
+        commit_sha
+    };
+    // Each commit is followed by its raw diff line for the file, e.g.
+    // ":100644 100644 <old blob> <new blob> M\tpath".
+    let log = git_lines(
+        git_root_dir,
+        &[
+            "log",
+            "--format=%H",
+            "--raw",
+            "--no-abbrev",
+            "--no-renames",
+            rev,
+            "--",
+            relative_path,
+        ],
+    )?;
+    let mut changes = vec![];
+    let mut commit: Option<String> = None;
+    for line in log.iter().filter(|l| !l.is_empty()) {
+        match line.strip_prefix(':') {
+            Some(raw) => {
+                let mut fields = raw.split_whitespace().skip(2);
+                if let (Some(sha), Some(old), Some(new)) = (&commit, fields.next(), fields.next()) {
+                    changes.push((sha.clone(), old.to_string(), new.to_string()));
+                }
+            }
+            None => commit = Some(line.to_string()),
+        }
+    }
+
+    // rev-list lists the objects among the given ones which are available
+    // locally, without fetching the others.
+    let blobs: Vec<&str> = changes
+        .iter()
+        .flat_map(|(_, old, new)| [old.as_str(), new.as_str()])
+        .filter(|blob| blob.bytes().any(|b| b != b'0'))
+        .collect();
+    if blobs.is_empty() {
+        return Ok(None);
+    }
+    let mut args = vec![
+        "rev-list",
+        "--objects",
+        "--ignore-missing",
+        "--missing=print",
+    ];
+    args.extend(&blobs);
+    let available: HashSet<String> = git_lines(git_root_dir, &args)?
+        .iter()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|blob| blob.to_string())
+        .collect();
+
+    let is_missing = |blob: &str| blob.bytes().any(|b| b != b'0') && !available.contains(blob);
+    Ok(changes
+        .into_iter()
+        .find(|(_, old, _)| is_missing(old))
+        .map(|(sha, _, _)| sha))
+}
@@ -0,0 +1,66 @@
+use std::fs;
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+// Shares the location of the selected line as "path:line" with other tools,
+// so that e.g. an editor can follow the blame cursor while pairing. The
+// location is either written to a file, which is replaced on each change, or
+// sent as a line to a unix socket which another program is listening on.
+#[derive(Debug)]
+pub struct Presenter {
+    target: Target,
+    // Last location shared, so that it's only shared again when it changes.
+    last: Option<String>,
+}
+
+#[derive(Debug)]
+enum Target {
+    File(PathBuf),
+    #[cfg(unix)]
+    Socket(UnixStream),
+}
+
+impl Presenter {
+    // Share locations on the socket at the given path if there is one, or
+    // in the file at the path otherwise.
+    pub fn open(path: &str) -> io::Result<Presenter> {
+        #[cfg(unix)]
+        if fs::metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+            return Ok(Presenter {
+                target: Target::Socket(UnixStream::connect(path)?),
+                last: None,
+            });
+        }
+
+        Ok(Presenter {
+            target: Target::File(PathBuf::from(path)),
+            last: None,
+        })
+    }
+
+    // Share a location unless it was the last one shared.
+    pub fn update(&mut self, location: &str) -> io::Result<()> {
+        if self.last.as_deref() == Some(location) {
+            return Ok(());
+        }
+
+        match &mut self.target {
+            Target::File(path) => {
+                // Write to a temporary file first and move it in place, so
+                // that tools watching the file never read half of it.
+                let mut temporary = path.clone().into_os_string();
+                temporary.push(".tmp");
+                fs::write(&temporary, format!("{}\n", location))?;
+                fs::rename(&temporary, path)?;
+            }
+            #[cfg(unix)]
+            Target::Socket(stream) => writeln!(stream, "{}", location)?,
+        }
+        self.last = Some(location.to_string());
+        Ok(())
+    }
+}
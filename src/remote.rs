@@ -0,0 +1,170 @@
+use crate::metrics;
+use std::process::Command;
+
+// Code hosting sites whose web pages of commits and files can be opened.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Host {
+    GitHub,
+    GitLab,
+    Bitbucket,
+}
+
+// The web address of a repository on a code hosting site, e.g.
+// https://github.com/izuzak/blame, derived from the URL of a remote.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebRemote {
+    pub host: Host,
+    pub base_url: String,
+}
+
+impl WebRemote {
+    // The web address of the origin remote of the repository with the given
+    // root, if it's on a known code hosting site.
+    pub fn origin(git_root_dir: &str) -> Option<WebRemote> {
+        // get-url applies url.<base>.insteadOf rewrites.
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(git_root_dir)
+                .args(["remote", "get-url", "origin"]),
+        )
+        .ok()
+        .filter(|output| output.status.success())?;
+        WebRemote::parse(String::from_utf8_lossy(&output.stdout).trim())
+    }
+
+    // Parse the URL of a remote, in any of the forms git accepts:
+    // "git@github.com:owner/repo.git", "ssh://git@host:22/owner/repo.git"
+    // and "https://user@host/owner/repo". Self-hosted sites are recognized
+    // by their host names, e.g. gitlab.example.com.
+    pub fn parse(url: &str) -> Option<WebRemote> {
+        let (host_name, path) = match url.split_once("://") {
+            Some((_, rest)) => rest.split_once('/')?,
+            // scp-like syntax, which has no scheme and a colon before the path.
+            None => url.split_once(':')?,
+        };
+        // Drop the user and the port.
+        let host_name = host_name.rsplit('@').next()?;
+        let host_name = host_name.split(':').next()?.to_lowercase();
+        let path = path.trim_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        if host_name.is_empty() || path.is_empty() {
+            return None;
+        }
+
+        let host = if host_name.contains("github") {
+            Host::GitHub
+        } else if host_name.contains("gitlab") {
+            Host::GitLab
+        } else if host_name.contains("bitbucket") {
+            Host::Bitbucket
+        } else {
+            return None;
+        };
+        // GitHub's SSH host is also used for its web pages, but the SSH host
+        // names of other sites may differ, e.g. "altssh.gitlab.com".
+        let host_name = host_name
+            .strip_prefix("ssh.")
+            .or_else(|| host_name.strip_prefix("altssh."))
+            .unwrap_or(&host_name);
+
+        Some(WebRemote {
+            host,
+            base_url: format!("https://{}/{}", host_name, path),
+        })
+    }
+
+    // The address of the web page of a commit.
+    pub fn commit_url(&self, sha: &str) -> String {
+        match self.host {
+            Host::GitHub => format!("{}/commit/{}", self.base_url, sha),
+            Host::GitLab => format!("{}/-/commit/{}", self.base_url, sha),
+            Host::Bitbucket => format!("{}/commits/{}", self.base_url, sha),
+        }
+    }
+
+    // The address of the web page of a file at a commit, scrolled to a line.
+    pub fn file_url(&self, sha: &str, path: &str, line: usize) -> String {
+        match self.host {
+            Host::GitHub => format!("{}/blob/{}/{}#L{}", self.base_url, sha, path, line),
+            Host::GitLab => format!("{}/-/blob/{}/{}#L{}", self.base_url, sha, path, line),
+            Host::Bitbucket => format!("{}/src/{}/{}#lines-{}", self.base_url, sha, path, line),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remote(host: Host, base_url: &str) -> Option<WebRemote> {
+        Some(WebRemote {
+            host,
+            base_url: base_url.to_string(),
+        })
+    }
+
+    #[test]
+    fn parses_scp_like_urls() {
+        assert_eq!(
+            WebRemote::parse("git@github.com:izuzak/blame.git"),
+            remote(Host::GitHub, "https://github.com/izuzak/blame")
+        );
+        assert_eq!(
+            WebRemote::parse("git@gitlab.example.com:group/subgroup/repo"),
+            remote(
+                Host::GitLab,
+                "https://gitlab.example.com/group/subgroup/repo"
+            )
+        );
+    }
+
+    #[test]
+    fn parses_ssh_urls() {
+        assert_eq!(
+            WebRemote::parse("ssh://git@ssh.github.com:443/izuzak/blame.git"),
+            remote(Host::GitHub, "https://github.com/izuzak/blame")
+        );
+        assert_eq!(
+            WebRemote::parse("ssh://git@altssh.gitlab.com/owner/repo.git"),
+            remote(Host::GitLab, "https://gitlab.com/owner/repo")
+        );
+    }
+
+    #[test]
+    fn parses_https_urls() {
+        assert_eq!(
+            WebRemote::parse("https://github.com/izuzak/blame.git"),
+            remote(Host::GitHub, "https://github.com/izuzak/blame")
+        );
+        assert_eq!(
+            WebRemote::parse("https://user@bitbucket.org/owner/repo/"),
+            remote(Host::Bitbucket, "https://bitbucket.org/owner/repo")
+        );
+    }
+
+    #[test]
+    fn ignores_unknown_hosts() {
+        assert_eq!(WebRemote::parse("git@example.com:owner/repo.git"), None);
+        assert_eq!(WebRemote::parse("https://example.com/owner/repo"), None);
+        assert_eq!(WebRemote::parse("/srv/git/repo.git"), None);
+        assert_eq!(WebRemote::parse("https://github.com/"), None);
+    }
+
+    #[test]
+    fn builds_web_page_urls() {
+        let github = remote(Host::GitHub, "https://github.com/o/r").unwrap();
+        assert_eq!(
+            github.commit_url("abc"),
+            "https://github.com/o/r/commit/abc"
+        );
+        assert_eq!(
+            github.file_url("abc", "src/main.rs", 7),
+            "https://github.com/o/r/blob/abc/src/main.rs#L7"
+        );
+        let bitbucket = remote(Host::Bitbucket, "https://bitbucket.org/o/r").unwrap();
+        assert_eq!(
+            bitbucket.file_url("abc", "a.rs", 3),
+            "https://bitbucket.org/o/r/src/abc/a.rs#lines-3"
+        );
+    }
+}
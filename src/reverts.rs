@@ -0,0 +1,127 @@
+use crate::disk_cache::DiskCache;
+use crate::file_blame::{FileBlame, WORKTREE};
+use crate::metrics;
+use std::collections::HashMap;
+use std::process::Command;
+
+// Commits in the history of a file which revert other commits, linked both
+// ways, so that a line whose last change was a revert can be told apart
+// from an ordinary change, and the commit it undid can be looked at.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reverts {
+    // The reverted commit of each revert commit.
+    reverted: HashMap<String, String>,
+    // The commit reverting each reverted commit, the newest one if it was
+    // reverted more than once.
+    reverted_by: HashMap<String, String>,
+}
+
+impl Reverts {
+    // Find the reverts among the commits which changed a file, up to the
+    // blamed commit and HEAD, so that commits are also known to be reverted
+    // while traveling back in time to before their revert.
+    pub fn load(filepath: &str, commit_sha: &str) -> Reverts {
+        let Some((git_root_dir, relative_path)) = FileBlame::repo_paths(filepath) else {
+            return Reverts::default();
+        };
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(&git_root_dir)
+                .arg("log")
+                .arg("--fixed-strings")
+                .arg("--grep=This reverts commit ")
+                .arg("--format=%H%x00%B%x1e")
+                .arg("HEAD")
+                .args(Some(commit_sha).filter(|&c| c != WORKTREE && c != "HEAD"))
+                .arg("--")
+                .arg(&relative_path),
+        );
+        let Ok(output) = output else {
+            return Reverts::default();
+        };
+
+        let mut reverts = Reverts::default();
+        // Each message is followed by a record separator, since messages span
+        // multiple lines. The log lists the newest commits first.
+        for record in String::from_utf8_lossy(&output.stdout).split('\x1e') {
+            let Some((sha, message)) = record.trim_start().split_once('\0') else {
+                continue;
+            };
+            let Some(reverted) = reverted_sha(message) else {
+                continue;
+            };
+            // Messages written by hand may abbreviate the hash.
+            let reverted = if reverted.len() == 40 {
+                reverted.to_string()
+            } else {
+                match DiskCache::resolve(&git_root_dir, reverted) {
+                    Some(full_sha) => full_sha,
+                    None => continue,
+                }
+            };
+            reverts
+                .reverted_by
+                .entry(reverted.clone())
+                .or_insert_with(|| sha.to_string());
+            reverts.reverted.insert(sha.to_string(), reverted);
+        }
+        reverts
+    }
+
+    // The commit which a commit reverts, if it's a revert.
+    pub fn reverted(&self, sha: &str) -> Option<&str> {
+        self.reverted.get(sha).map(String::as_str)
+    }
+
+    // The commit which reverts a commit, if it was reverted.
+    pub fn reverted_by(&self, sha: &str) -> Option<&str> {
+        self.reverted_by.get(sha).map(String::as_str)
+    }
+
+    // The commit linked to a commit by a revert: the one it reverts, or else
+    // the one reverting it.
+    pub fn linked(&self, sha: &str) -> Option<&str> {
+        self.reverted(sha).or_else(|| self.reverted_by(sha))
+    }
+}
+
+// The hash of the reverted commit in the message of a revert made by
+// `git revert`, which says "This reverts commit <hash>." in its body.
+fn reverted_sha(message: &str) -> Option<&str> {
+    let (_, rest) = message.split_once("This reverts commit ")?;
+    let len = rest
+        .find(|c: char| !c.is_ascii_hexdigit())
+        .unwrap_or(rest.len());
+    (len >= 7).then(|| &rest[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_reverted_commits() {
+        let sha = "1e1d1c3c8b7d6f5e4a3b2c1d0e9f8a7b6c5d4e3f";
+        let message = format!("Revert \"Add x\"\n\nThis reverts commit {}.\n", sha);
+        assert_eq!(reverted_sha(&message), Some(sha));
+        assert_eq!(
+            reverted_sha("This reverts commit 1e1d1c3, oops"),
+            Some("1e1d1c3")
+        );
+        // Hashes shorter than git's shortest abbreviation aren't hashes.
+        assert_eq!(reverted_sha("This reverts commit 1e1d."), None);
+        assert_eq!(reverted_sha("Revert \"Add x\""), None);
+    }
+
+    #[test]
+    fn links_reverts_both_ways() {
+        let mut reverts = Reverts::default();
+        reverts.reverted.insert("b".to_string(), "a".to_string());
+        reverts.reverted_by.insert("a".to_string(), "b".to_string());
+        assert_eq!(reverts.reverted("b"), Some("a"));
+        assert_eq!(reverts.reverted_by("a"), Some("b"));
+        assert_eq!(reverts.linked("a"), Some("b"));
+        assert_eq!(reverts.linked("b"), Some("a"));
+        assert_eq!(reverts.linked("c"), None);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::file_blame::{git_lines, FileBlame, FileBlameError};
+use std::collections::HashSet;
+use std::path::Path;
+
+// Files changed in a range of commits, e.g. the commits of a pull request,
+// or in a single commit, which are reviewed one after another in tabs. The
+// files are blamed at the end of the range, and the lines introduced in the
+// range are either the only ones shown, or marked.
+#[derive(Debug, Clone)]
+pub struct Review {
+    pub range: String,
+    // Ref at the end of the range, e.g. HEAD for "origin/main..HEAD".
+    pub gitref: String,
+    // Paths of the changed files, relative to the current directory where
+    // possible, like paths given on the command line.
+    pub files: Vec<String>,
+    // Full hashes of the commits in the range.
+    commits: HashSet<String>,
+    // Whether lines which weren't introduced in the range are hidden, or
+    // shown with the introduced lines marked.
+    pub hide_other_lines: bool,
+    // Selected line of each file, so that it's kept when switching tabs.
+    pub selected: Vec<Option<usize>>,
+}
+
+impl Review {
+    // Load the files changed and the commits in a range like
+    // "origin/main..HEAD" or "main...feature", in the repository of the
+    // current directory. Deleted files are left out since they can't be
+    // blamed at the end of the range.
+    pub fn load(range: &str) -> Result<Review, FileBlameError> {
+        let (git_root_dir, _) = FileBlame::try_repo_paths(".")?;
+        let end = range.rsplit_once("..").map(|(_, end)| end).ok_or_else(|| {
+            FileBlameError::Unknown(format!(
+                "invalid range '{}', expected e.g. origin/main..HEAD",
+                range
+            ))
+        })?;
+        let gitref = if end.is_empty() { "HEAD" } else { end }.to_string();
+
+        let files: Vec<String> = git_lines(
+            &git_root_dir,
+            &["diff", "--name-only", "--diff-filter=d", range, "--"],
+        )?
+        .iter()
+        .map(|file| display_path(&Path::new(&git_root_dir).join(file)))
+        .collect();
+        if files.is_empty() {
+            return Err(FileBlameError::Unknown(format!(
+                "no files were changed in {}",
+                range
+            )));
+        }
+        let commits = git_lines(&git_root_dir, &["rev-list", range, "--"])?
+            .into_iter()
+            .collect();
+
+        Ok(Review {
+            range: range.to_string(),
+            gitref,
+            selected: vec![None; files.len()],
+            files,
+            commits,
+            hide_other_lines: true,
+        })
+    }
+
+    // Load the files changed in a single commit, for blaming them at the
+    // commit with the commit's own lines marked. Deleted files are left out.
+    pub fn for_commit(commit: &str) -> Result<Review, FileBlameError> {
+        let (git_root_dir, _) = FileBlame::try_repo_paths(".")?;
+        let sha = git_lines(
+            &git_root_dir,
+            &["rev-parse", "--verify", &format!("{}^{{commit}}", commit)],
+        )?
+        .concat();
+
+        // With --root, the files of the initial commit are listed too.
+        let files: Vec<String> = git_lines(
+            &git_root_dir,
+            &[
+                "diff-tree",
+                "--no-commit-id",
+                "--name-only",
+                "-r",
+                "--root",
+                "--diff-filter=d",
+                &sha,
+            ],
+        )?
+        .iter()
+        .map(|file| display_path(&Path::new(&git_root_dir).join(file)))
+        .collect();
+        if files.is_empty() {
+            return Err(FileBlameError::Unknown(format!(
+                "no files were added or changed in {}",
+                commit
+            )));
+        }
+
+        Ok(Review {
+            range: commit.to_string(),
+            gitref: commit.to_string(),
+            selected: vec![None; files.len()],
+            files,
+            commits: HashSet::from([sha]),
+            hide_other_lines: false,
+        })
+    }
+
+    // Whether a line last changed by a commit was introduced in the range.
+    pub fn is_introduced(&self, commit_sha: &str) -> bool {
+        self.commits.contains(commit_sha)
+    }
+
+    // Index of the tab of a file, if it's one of the changed files.
+    pub fn tab_of(&self, file_path: &str) -> Option<usize> {
+        self.files.iter().position(|f| f == file_path)
+    }
+
+    // Keep only the lines of a blame at the end of the range which were
+    // introduced in the range. If none were, e.g. because lines were only
+    // removed from the file, the whole blame is kept for context.
+    pub fn filter(&self, file_blame: &mut FileBlame) {
+        if !self.hide_other_lines || file_blame.commit_sha != self.gitref {
+            return;
+        }
+        let introduced = |sha: &String| self.commits.contains(sha);
+        if file_blame
+            .blame_lines
+            .iter()
+            .any(|l| introduced(&l.commit_sha))
+        {
+            file_blame.blame_lines.retain(|l| introduced(&l.commit_sha));
+        }
+    }
+}
+
+// A path relative to the current directory if it's inside it.
+fn display_path(path: &Path) -> String {
+    std::env::current_dir()
+        .and_then(|d| d.canonicalize())
+        .ok()
+        .and_then(|d| path.strip_prefix(d).ok().map(|p| p.to_path_buf()))
+        .unwrap_or_else(|| path.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
@@ -0,0 +1,47 @@
+use std::ops::Range;
+
+// Byte ranges of the non-overlapping occurrences of a query in a text. The
+// search ignores case unless the query contains uppercase letters, like
+// smartcase in vim. Only ASCII letters are folded, so that the ranges are
+// valid in the original text.
+pub fn find_matches(text: &str, query: &str) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return vec![];
+    }
+    let ignore_case = !query.chars().any(|c| c.is_uppercase());
+    let (text, query) = if ignore_case {
+        (text.to_ascii_lowercase(), query.to_ascii_lowercase())
+    } else {
+        (text.to_string(), query.to_string())
+    };
+    text.match_indices(&query)
+        .map(|(start, m)| start..start + m.len())
+        .collect()
+}
+
+// Whether a text contains a query, with the same rules as `find_matches`.
+pub fn matches(text: &str, query: &str) -> bool {
+    !find_matches(text, query).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_matches_with_smartcase() {
+        assert_eq!(find_matches("Foo foo FOO", "foo"), [0..3, 4..7, 8..11]);
+        assert_eq!(find_matches("Foo foo FOO Foo", "Foo"), [0..3, 12..15]);
+        assert_eq!(find_matches("aaaa", "aa"), [0..2, 2..4]);
+        assert!(find_matches("foo", "").is_empty());
+    }
+
+    #[test]
+    fn finds_matches_in_non_ascii_text() {
+        let text = "über Über";
+        assert_eq!(find_matches(text, "ber"), [2..5, 8..11]);
+        assert_eq!(&text[8..11], "ber");
+        assert!(matches(text, "über"));
+        assert!(!matches(text, "xyz"));
+    }
+}
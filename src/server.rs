@@ -0,0 +1,270 @@
+use crate::disk_cache::DiskCache;
+use crate::file_blame::{Commit, FileBlame, HighlightCache, ParseOptions};
+use crate::memory::{self, CommitRecency};
+use crate::metrics;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::process::Command;
+
+// Error codes defined by the JSON-RPC 2.0 specification.
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+const SERVER_ERROR: i64 = -32000;
+
+// A JSON-RPC request. Requests are sent as one JSON object per line.
+#[derive(Deserialize, Debug)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize, Debug)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<Value>,
+}
+
+#[derive(Serialize, Debug)]
+struct LineResult<'a> {
+    line: &'a str,
+    sha: &'a str,
+    contents: &'a str,
+}
+
+// A commit which changed a line, as listed by `line_history`.
+#[derive(Serialize, Debug)]
+struct HistoryEntry {
+    sha: String,
+    author: String,
+    time: i64,
+    summary: String,
+}
+
+// Long-running server which keeps blames and commit information cached
+// between requests, so that editors and other tools don't need to run git
+// for each of them. The methods are:
+//   - "blame" {path, ref} - the commit and contents of each line of a file
+//   - "commit_info" {path, sha} - information about a commit in the file's repository
+//   - "line_history" {path, line, ref} - the commits which changed a line
+// The ref defaults to HEAD. The least recently used blames, highlighted
+// contents and commits are evicted when the caches grow over the memory
+// budget.
+pub struct Server {
+    commit_cache: HashMap<String, Commit>,
+    commit_recency: CommitRecency,
+    // Blames keyed by the file path and the commit the ref resolved to.
+    blames: LruCache<(String, String), FileBlame>,
+    highlights: HighlightCache,
+    memory_budget: usize,
+}
+
+// Listen for connections on a unix socket and answer their requests, one
+// connection at a time.
+pub fn serve(socket_path: &str, memory_budget: usize) -> Result<(), Box<dyn Error>> {
+    // Remove the socket of a previous server, but never any other file.
+    if fs::metadata(socket_path).is_ok_and(|m| m.file_type().is_socket()) {
+        fs::remove_file(socket_path)?;
+    }
+
+    let listener = UnixListener::bind(socket_path)?;
+    let mut server = Server::new(memory_budget);
+    for stream in listener.incoming() {
+        // A broken connection only affects the client on the other end.
+        let _ = server.handle_connection(stream?);
+    }
+    Ok(())
+}
+
+impl Server {
+    pub fn new(memory_budget: usize) -> Server {
+        Server {
+            commit_cache: HashMap::new(),
+            commit_recency: CommitRecency::unbounded(),
+            blames: LruCache::unbounded(),
+            highlights: HighlightCache::unbounded(),
+            memory_budget,
+        }
+    }
+
+    fn handle_connection(&mut self, stream: UnixStream) -> std::io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // A request which fails unexpectedly is answered with an error,
+            // instead of taking the server down for all clients.
+            let response = panic::catch_unwind(AssertUnwindSafe(|| self.handle_request(&line)))
+                .unwrap_or_else(|_| {
+                    error_response(Value::Null, INTERNAL_ERROR, "internal error".to_string())
+                });
+            writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, line: &str) -> Response {
+        let request: Request = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return error_response(Value::Null, PARSE_ERROR, e.to_string()),
+        };
+
+        let result = match request.method.as_str() {
+            "blame" => self.blame(&request.params),
+            "commit_info" => self.commit_info(&request.params),
+            "line_history" => line_history(&request.params),
+            method => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+        };
+
+        match result {
+            Ok(result) => Response {
+                jsonrpc: "2.0",
+                id: request.id,
+                result: Some(result),
+                error: None,
+            },
+            Err((code, message)) => error_response(request.id, code, message),
+        }
+    }
+
+    fn blame(&mut self, params: &Value) -> Result<Value, (i64, String)> {
+        let path = string_param(params, "path")?;
+        let gitref = params["ref"].as_str().unwrap_or("HEAD");
+        let sha = FileBlame::rev_parse(path, gitref).map_err(server_error)?;
+
+        let key = (path.to_string(), sha.clone());
+        if !self.blames.contains(&key) {
+            let file_blame = FileBlame::parse_with_progress(
+                path,
+                &sha,
+                &mut self.commit_cache,
+                &mut self.highlights,
+                &ParseOptions::default(),
+                |_| {},
+            )
+            .map_err(server_error)?;
+            self.trim_caches(&[&file_blame]);
+            self.blames.put(key.clone(), file_blame);
+        }
+
+        let lines: Vec<LineResult> = self
+            .blames
+            .get(&key)
+            .expect("the requested blame is the most recently used")
+            .blame_lines
+            .iter()
+            .map(|line| LineResult {
+                line: &line.line_number,
+                sha: &line.commit_sha,
+                contents: &line.plain_contents,
+            })
+            .collect();
+        Ok(json!({ "path": path, "ref": gitref, "commit": sha, "lines": lines }))
+    }
+
+    fn commit_info(&mut self, params: &Value) -> Result<Value, (i64, String)> {
+        let path = string_param(params, "path")?;
+        let sha = string_param(params, "sha")?;
+        let (git_root_dir, _) = FileBlame::try_repo_paths(path).map_err(server_error)?;
+        let sha = DiskCache::resolve(&git_root_dir, sha)
+            .ok_or_else(|| (SERVER_ERROR, format!("unknown commit '{}'", sha)))?;
+
+        let commit = self
+            .commit_cache
+            .entry(sha.clone())
+            .or_insert_with(|| Commit::load(&git_root_dir, &sha));
+        let result = serde_json::to_value(commit).map_err(|e| (SERVER_ERROR, e.to_string()));
+        self.commit_recency.put(sha, ());
+        self.trim_caches(&[]);
+        result
+    }
+
+    // Keep the caches within the memory budget, along with a blame which is
+    // about to be cached.
+    fn trim_caches(&mut self, shown: &[&FileBlame]) {
+        memory::trim_caches(
+            &mut self.blames,
+            &mut self.highlights,
+            &mut self.commit_cache,
+            &mut self.commit_recency,
+            self.memory_budget,
+            shown,
+        );
+    }
+}
+
+// List the commits which changed a line up to a commit, newest first.
+fn line_history(params: &Value) -> Result<Value, (i64, String)> {
+    let path = string_param(params, "path")?;
+    let line = params["line"]
+        .as_u64()
+        .ok_or_else(|| (INVALID_PARAMS, "missing parameter 'line'".to_string()))?;
+    let gitref = params["ref"].as_str().unwrap_or("HEAD");
+    let (git_root_dir, relative_path) = FileBlame::try_repo_paths(path).map_err(server_error)?;
+
+    let output = metrics::output(
+        Command::new("git")
+            .current_dir(&git_root_dir)
+            .arg("log")
+            .arg(format!("-L{},{}:{}", line, line, relative_path))
+            .arg("--format=%h%x00%aN%x00%at%x00%s")
+            .arg("-s")
+            .arg(gitref),
+    )
+    .map_err(|e| (SERVER_ERROR, e.to_string()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err((SERVER_ERROR, stderr));
+    }
+
+    let history: Vec<HistoryEntry> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\0');
+            Some(HistoryEntry {
+                sha: fields.next()?.to_string(),
+                author: fields.next()?.to_string(),
+                time: fields.next()?.parse().ok()?,
+                summary: fields.next()?.to_string(),
+            })
+        })
+        .collect();
+    Ok(json!(history))
+}
+
+fn string_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, (i64, String)> {
+    params[name]
+        .as_str()
+        .ok_or_else(|| (INVALID_PARAMS, format!("missing parameter '{}'", name)))
+}
+
+fn server_error(e: impl ToString) -> (i64, String) {
+    (SERVER_ERROR, e.to_string())
+}
+
+fn error_response(id: Value, code: i64, message: String) -> Response {
+    Response {
+        jsonrpc: "2.0",
+        id,
+        result: None,
+        error: Some(json!({ "code": code, "message": message })),
+    }
+}
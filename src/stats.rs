@@ -0,0 +1,178 @@
+use crate::file_blame::{Commit, FileBlame, WORKTREE};
+use crate::metrics;
+use std::collections::HashMap;
+use std::process::Command;
+
+// Number of lines last changed by a single author.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorStats {
+    pub author: String,
+    pub lines: usize,
+}
+
+// Ownership statistics for a file blame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileStats {
+    pub total_lines: usize,
+    // Authors sorted by the number of lines they last changed, most first.
+    pub authors: Vec<AuthorStats>,
+    // The smallest number of authors who together last changed more than half
+    // of the lines. A bus factor of 1 means that a single author owns most of
+    // the file.
+    pub bus_factor: usize,
+    pub monthly_commits: MonthlyCommits,
+}
+
+// Order of the authors in the statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AuthorOrder {
+    // Most lines first, and by name for the same number of lines.
+    #[default]
+    Lines,
+    // By name, ignoring case.
+    Name,
+}
+
+impl AuthorOrder {
+    pub fn next(self) -> AuthorOrder {
+        match self {
+            AuthorOrder::Lines => AuthorOrder::Name,
+            AuthorOrder::Name => AuthorOrder::Lines,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AuthorOrder::Lines => "most lines first",
+            AuthorOrder::Name => "by name",
+        }
+    }
+}
+
+// Number of commits which changed a file in each month of its history.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MonthlyCommits {
+    // First and last month with commits, as "YYYY-MM".
+    pub first_month: String,
+    pub last_month: String,
+    // One count per month from the first to the last month, including the
+    // months without commits.
+    pub counts: Vec<u64>,
+}
+
+impl MonthlyCommits {
+    // Load the history of a file up to a commit with `git log`.
+    pub fn load(filepath: &str, commit_sha: &str) -> MonthlyCommits {
+        let Some((git_root_dir, relative_path)) = FileBlame::repo_paths(filepath) else {
+            return MonthlyCommits::default();
+        };
+        let commit_sha = if commit_sha == WORKTREE {
+            "HEAD"
+        } else {
+            commit_sha
+        };
+
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(&git_root_dir)
+                .arg("log")
+                .arg("--date=format:%Y-%m")
+                .arg("--format=%ad")
+                .arg(commit_sha)
+                .arg("--")
+                .arg(&relative_path),
+        );
+
+        match output {
+            Ok(output) if output.status.success() => {
+                MonthlyCommits::parse(&String::from_utf8_lossy(&output.stdout))
+            }
+            _ => MonthlyCommits::default(),
+        }
+    }
+
+    // Parse a list of commit months, one "YYYY-MM" per line, in any order.
+    pub fn parse(log: &str) -> MonthlyCommits {
+        // Months are counted from year 0, so that consecutive months have
+        // consecutive numbers.
+        let months: Vec<i64> = log
+            .lines()
+            .filter_map(|line| {
+                let (year, month) = line.trim().split_once('-')?;
+                Some(year.parse::<i64>().ok()? * 12 + month.parse::<i64>().ok()? - 1)
+            })
+            .collect();
+        let (Some(&first), Some(&last)) = (months.iter().min(), months.iter().max()) else {
+            return MonthlyCommits::default();
+        };
+
+        let mut counts = vec![0; (last - first + 1) as usize];
+        for month in months {
+            counts[(month - first) as usize] += 1;
+        }
+        let format = |month: i64| format!("{:04}-{:02}", month / 12, month % 12 + 1);
+        MonthlyCommits {
+            first_month: format(first),
+            last_month: format(last),
+            counts,
+        }
+    }
+}
+
+impl FileStats {
+    pub fn compute(file_blame: &FileBlame, commit_cache: &HashMap<String, Commit>) -> FileStats {
+        let mut lines_by_author: HashMap<&str, usize> = HashMap::new();
+        for line in &file_blame.blame_lines {
+            let author = commit_cache
+                .get(&line.commit_sha)
+                .map(|c| c.author.as_str())
+                .unwrap_or("");
+            *lines_by_author.entry(author).or_default() += 1;
+        }
+
+        let mut authors: Vec<AuthorStats> = lines_by_author
+            .into_iter()
+            .map(|(author, lines)| AuthorStats {
+                author: author.to_string(),
+                lines,
+            })
+            .collect();
+        authors.sort_by(|a, b| b.lines.cmp(&a.lines).then(a.author.cmp(&b.author)));
+
+        let total_lines = file_blame.blame_lines.len();
+        let mut covered = 0;
+        let mut bus_factor = 0;
+        for author in &authors {
+            if covered * 2 > total_lines {
+                break;
+            }
+            covered += author.lines;
+            bus_factor += 1;
+        }
+
+        FileStats {
+            total_lines,
+            authors,
+            bus_factor,
+            monthly_commits: MonthlyCommits::default(),
+        }
+    }
+
+    pub fn sort_authors(&mut self, order: AuthorOrder) {
+        match order {
+            AuthorOrder::Lines => self
+                .authors
+                .sort_by(|a, b| b.lines.cmp(&a.lines).then(a.author.cmp(&b.author))),
+            AuthorOrder::Name => self.authors.sort_by_cached_key(|a| a.author.to_lowercase()),
+        }
+    }
+
+    // Share of the file's lines last changed by an author, between 0 and 1.
+    pub fn ratio(&self, author: &AuthorStats) -> f64 {
+        if self.total_lines == 0 {
+            0.0
+        } else {
+            author.lines as f64 / self.total_lines as f64
+        }
+    }
+}
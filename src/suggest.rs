@@ -0,0 +1,79 @@
+use std::cmp::Ordering;
+
+// How similar a candidate needs to be to a mistyped name to be suggested,
+// as a Jaro-Winkler similarity between 0 and 1.
+const MIN_SIMILARITY: f64 = 0.8;
+
+// The candidates which are most similar to a mistyped name, most similar
+// first, for "did you mean" suggestions.
+pub fn closest<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+    limit: usize,
+) -> Vec<String> {
+    let mut scored: Vec<(f64, &str)> = candidates
+        .into_iter()
+        .filter(|c| *c != name)
+        .map(|c| (strsim::jaro_winkler(name, c), c))
+        .filter(|(score, _)| *score >= MIN_SIMILARITY)
+        .collect();
+    scored.sort_by(|a, b| {
+        b.0.partial_cmp(&a.0)
+            .unwrap_or(Ordering::Equal)
+            .then(a.1.cmp(b.1))
+    });
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, c)| c.to_string())
+        .collect()
+}
+
+// Format suggestions as " Did you mean 'a' or 'b'?", or nothing if there
+// are none.
+pub fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [only] => format!(" Did you mean '{}'?", only),
+        [rest @ .., last] => format!(
+            " Did you mean {} or '{}'?",
+            rest.iter()
+                .map(|s| format!("'{}'", s))
+                .collect::<Vec<_>>()
+                .join(", "),
+            last
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_similar_names_most_similar_first() {
+        let refs = ["main", "master", "develop", "maint", "mian"];
+        assert_eq!(closest("mian", refs, 3), ["main", "maint"]);
+        assert_eq!(closest("mian", refs, 1), ["main"]);
+        assert!(closest("feature", refs, 3).is_empty());
+    }
+
+    #[test]
+    fn formats_suggestions() {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(did_you_mean(&[]), "");
+        assert_eq!(did_you_mean(&names(&["main"])), " Did you mean 'main'?");
+        assert_eq!(
+            did_you_mean(&names(&["main", "maint", "master"])),
+            " Did you mean 'main', 'maint' or 'master'?"
+        );
+    }
+
+    #[test]
+    fn suggests_similar_paths() {
+        let files = ["src/main.rs", "src/app.rs", "src/ui.rs", "README.md"];
+        assert_eq!(closest("src/mian.rs", files, 1), ["src/main.rs"]);
+        assert_eq!(closest("scr/app.rs", files, 1), ["src/app.rs"]);
+        assert!(!closest("src/mian.rs", files, 4).contains(&"README.md".to_string()));
+    }
+}
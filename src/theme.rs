@@ -0,0 +1,225 @@
+use crate::app::ColumnKind;
+use crate::colors;
+use crate::file_blame::{self, DEFAULT_SYNTAX_THEME};
+use ratatui::style::{Color, Modifier, Style};
+use std::str::FromStr;
+
+// Names of the built-in themes, which --theme and the preset setting accept.
+pub const PRESETS: &[&str] = &["dark", "light", "solarized"];
+
+// Colors and characters of the blame table and the lists in popups. A theme
+// starts from one of the presets, and single settings can be changed in the
+// [theme] table of the config file, e.g. `author = "magenta bold"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub time: Style,
+    pub author: Style,
+    pub commit: Style,
+    pub message: Style,
+    pub annotation: Style,
+    pub age: Style,
+    pub origin: Style,
+    pub line: Style,
+    pub contents: Style,
+    // Names of the columns above the table.
+    pub header: Style,
+    // The selected line, and the highlighted item of lists.
+    pub selected: Style,
+    // The other lines of a selected range of lines.
+    pub range: Style,
+    // The commit columns of lines changed in the working tree, which aren't
+    // committed yet.
+    pub uncommitted: Style,
+    // Character between columns.
+    pub divider: String,
+    // Name of the syntax highlighting theme of the contents, one of
+    // file_blame::syntax_themes().
+    pub syntax: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    // Colors for terminals with a dark background.
+    pub fn dark() -> Theme {
+        Theme {
+            time: Style::default().fg(Color::Blue),
+            author: Style::default().fg(Color::Red),
+            commit: Style::default().fg(Color::Green),
+            message: Style::default().fg(Color::Green),
+            annotation: Style::default(),
+            age: Style::default().fg(Color::Magenta),
+            origin: Style::default().fg(Color::Cyan),
+            line: Style::default().fg(Color::Yellow),
+            contents: Style::default(),
+            header: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            selected: Style::default().bg(Color::Rgb(0x3f, 0x3f, 0x3f)),
+            range: Style::default().bg(Color::Rgb(0x2a, 0x2a, 0x3a)),
+            uncommitted: Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::ITALIC),
+            divider: "│".to_string(),
+            syntax: DEFAULT_SYNTAX_THEME.to_string(),
+        }
+    }
+
+    // Colors for terminals with a light background, where yellow and green
+    // text is hard to read and the selection needs a light background.
+    pub fn light() -> Theme {
+        Theme {
+            time: Style::default().fg(Color::Blue),
+            author: Style::default().fg(Color::Red),
+            commit: Style::default().fg(Color::Magenta),
+            message: Style::default().fg(Color::Black),
+            line: Style::default().fg(Color::DarkGray),
+            header: Style::default()
+                .fg(Color::Blue)
+                .add_modifier(Modifier::BOLD),
+            selected: Style::default().bg(Color::Rgb(0xd7, 0xd7, 0xd7)),
+            range: Style::default().bg(Color::Rgb(0xe4, 0xe8, 0xf4)),
+            uncommitted: Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::ITALIC),
+            syntax: "InspiredGitHub".to_string(),
+            ..Theme::dark()
+        }
+    }
+
+    // The accent colors of the Solarized palette, on its dark background
+    // tones.
+    pub fn solarized() -> Theme {
+        Theme {
+            time: Style::default().fg(Color::Rgb(0x26, 0x8b, 0xd2)),
+            author: Style::default().fg(Color::Rgb(0xdc, 0x32, 0x2f)),
+            commit: Style::default().fg(Color::Rgb(0x85, 0x99, 0x00)),
+            message: Style::default().fg(Color::Rgb(0x93, 0xa1, 0xa1)),
+            age: Style::default().fg(Color::Rgb(0xd3, 0x36, 0x82)),
+            origin: Style::default().fg(Color::Rgb(0x2a, 0xa1, 0x98)),
+            line: Style::default().fg(Color::Rgb(0xb5, 0x89, 0x00)),
+            header: Style::default()
+                .fg(Color::Rgb(0xcb, 0x4b, 0x16))
+                .add_modifier(Modifier::BOLD),
+            selected: Style::default().bg(Color::Rgb(0x07, 0x36, 0x42)),
+            range: Style::default().bg(Color::Rgb(0x00, 0x2b, 0x36)),
+            uncommitted: Style::default()
+                .fg(Color::Rgb(0xb5, 0x89, 0x00))
+                .add_modifier(Modifier::ITALIC),
+            syntax: "Solarized (dark)".to_string(),
+            ..Theme::dark()
+        }
+    }
+
+    // The built-in theme with the given name.
+    pub fn preset(name: &str) -> Option<Theme> {
+        match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            "solarized" => Some(Theme::solarized()),
+            _ => None,
+        }
+    }
+
+    // Name of the preset to use when none is chosen, which depends on
+    // whether the terminal's background is light.
+    pub fn default_preset() -> &'static str {
+        if colors::is_light_background() {
+            "light"
+        } else {
+            "dark"
+        }
+    }
+
+    // The style of a column.
+    pub fn column_style(&self, kind: ColumnKind) -> Style {
+        match kind {
+            ColumnKind::Time => self.time,
+            ColumnKind::Author => self.author,
+            ColumnKind::Commit => self.commit,
+            ColumnKind::Message => self.message,
+            ColumnKind::Annotation => self.annotation,
+            ColumnKind::Age => self.age,
+            ColumnKind::Origin => self.origin,
+            ColumnKind::Line => self.line,
+            ColumnKind::Contents => self.contents,
+        }
+    }
+
+    // Change a single setting, e.g. ("header", "blue bold") or
+    // ("divider", "┃").
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let style = match name {
+            "divider" => {
+                self.divider = value.to_string();
+                return Ok(());
+            }
+            "syntax" => {
+                self.syntax = parse_syntax_theme(value)?;
+                return Ok(());
+            }
+            "time" => &mut self.time,
+            "author" => &mut self.author,
+            "commit" => &mut self.commit,
+            "message" => &mut self.message,
+            "annotation" => &mut self.annotation,
+            "age" => &mut self.age,
+            "origin" => &mut self.origin,
+            "line" => &mut self.line,
+            "contents" => &mut self.contents,
+            "header" => &mut self.header,
+            "selected" => &mut self.selected,
+            "range" => &mut self.range,
+            "uncommitted" => &mut self.uncommitted,
+            _ => return Err(format!("unknown theme setting '{}'", name)),
+        };
+        *style = parse_style(value)?;
+        Ok(())
+    }
+}
+
+// Parse a style from words, e.g. "yellow", "#b58900 on #073642 bold" or
+// "on light-blue". The first color is the foreground, and a color after "on"
+// the background.
+pub fn parse_style(text: &str) -> Result<Style, String> {
+    let mut style = Style::default();
+    let mut words = text.split_whitespace();
+    while let Some(word) = words.next() {
+        let modifier = match word {
+            "bold" => Modifier::BOLD,
+            "dim" => Modifier::DIM,
+            "italic" => Modifier::ITALIC,
+            "underlined" => Modifier::UNDERLINED,
+            "reversed" => Modifier::REVERSED,
+            "on" => {
+                let color = words.next().ok_or("expected a color after 'on'")?;
+                style = style.bg(parse_color(color)?);
+                continue;
+            }
+            color => {
+                style = style.fg(parse_color(color)?);
+                continue;
+            }
+        };
+        style = style.add_modifier(modifier);
+    }
+    Ok(style)
+}
+
+// Check the name of a syntax highlighting theme, e.g. "Solarized (light)".
+pub fn parse_syntax_theme(name: &str) -> Result<String, String> {
+    if file_blame::syntax_themes().iter().any(|t| t == name) {
+        Ok(name.to_string())
+    } else {
+        Err(format!(
+            "unknown syntax theme '{}', see --list-themes for the available ones",
+            name
+        ))
+    }
+}
+
+fn parse_color(name: &str) -> Result<Color, String> {
+    Color::from_str(name).map_err(|_| format!("unknown color '{}'", name))
+}
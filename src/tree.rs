@@ -0,0 +1,142 @@
+use crate::file_blame::FileBlameError;
+use crate::metrics;
+use ratatui::widgets::ListState;
+use std::collections::HashSet;
+use std::path::Path;
+use std::process::Command;
+
+// A single visible entry in the file tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeItem {
+    // Path relative to the root of the repository.
+    pub path: String,
+    pub name: String,
+    pub depth: usize,
+    pub is_dir: bool,
+    pub expanded: bool,
+    // Whether the entry still exists in the working tree. Entries which were
+    // deleted since the commit can still be opened.
+    pub in_worktree: bool,
+}
+
+// Tree of all files and directories of a repository at a specific commit,
+// with directories which can be expanded and collapsed.
+#[derive(Debug, Clone)]
+pub struct FileTree {
+    pub git_root_dir: String,
+    pub commit_sha: String,
+    paths: Vec<String>,
+    dirs: HashSet<String>,
+    expanded: HashSet<String>,
+    pub state: ListState,
+}
+
+impl FileTree {
+    // Load the tree of the repository with the given root at a commit.
+    pub fn load(git_root_dir: &str, commit_sha: &str) -> Result<FileTree, FileBlameError> {
+        let mut tree = FileTree {
+            git_root_dir: git_root_dir.to_string(),
+            commit_sha: String::new(),
+            paths: vec![],
+            dirs: HashSet::new(),
+            expanded: HashSet::new(),
+            state: ListState::default(),
+        };
+        tree.reload(commit_sha)?;
+        Ok(tree)
+    }
+
+    // Load the tree at another commit, keeping the expanded directories.
+    pub fn reload(&mut self, commit_sha: &str) -> Result<(), FileBlameError> {
+        // With -t, directories are listed (before their contents) in addition
+        // to files, and the type column tells them apart.
+        let output = metrics::output(
+            Command::new("git")
+                .current_dir(&self.git_root_dir)
+                .arg("ls-tree")
+                .arg("-r")
+                .arg("-t")
+                .arg(commit_sha),
+        )
+        .map_err(|e| FileBlameError::Unknown(e.to_string()))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            return Err(FileBlameError::Unknown(stderr));
+        }
+
+        self.paths.clear();
+        self.dirs.clear();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            // Each line looks like "<mode> <type> <object>\t<path>"
+            let Some((info, path)) = line.split_once('\t') else {
+                continue;
+            };
+            if info.split(' ').nth(1) == Some("tree") {
+                self.dirs.insert(path.to_string());
+            }
+            self.paths.push(path.to_string());
+        }
+        self.commit_sha = commit_sha.to_string();
+        Ok(())
+    }
+
+    // The entries which are visible, i.e. whose parent directories are all expanded.
+    pub fn visible_items(&self) -> Vec<TreeItem> {
+        self.paths
+            .iter()
+            .filter(|path| {
+                let mut parent = path.as_str();
+                while let Some((p, _)) = parent.rsplit_once('/') {
+                    if !self.expanded.contains(p) {
+                        return false;
+                    }
+                    parent = p;
+                }
+                true
+            })
+            .map(|path| TreeItem {
+                path: path.clone(),
+                name: path.rsplit('/').next().unwrap_or(path).to_string(),
+                depth: path.matches('/').count(),
+                is_dir: self.dirs.contains(path),
+                expanded: self.expanded.contains(path),
+                in_worktree: Path::new(&self.git_root_dir).join(path).exists(),
+            })
+            .collect()
+    }
+
+    // The entry which is currently selected.
+    pub fn selected_item(&self) -> Option<TreeItem> {
+        self.visible_items().get(self.state.selected()?).cloned()
+    }
+
+    // Expand all parent directories of a path, and select it.
+    pub fn reveal(&mut self, path: &str) {
+        let mut parent = path;
+        while let Some((p, _)) = parent.rsplit_once('/') {
+            self.expanded.insert(p.to_string());
+            parent = p;
+        }
+        let index = self.visible_items().iter().position(|i| i.path == path);
+        self.state.select(index.or(Some(0)));
+    }
+
+    // Expand a collapsed directory, or collapse an expanded one.
+    pub fn toggle_expanded(&mut self, path: &str) {
+        if !self.expanded.remove(path) {
+            self.expanded.insert(path.to_string());
+        }
+    }
+
+    // Move the selection by the given number of entries, staying within the list.
+    pub fn move_selection(&mut self, delta: isize) {
+        let len = self.visible_items().len();
+        if len == 0 {
+            return;
+        }
+        let current = self.state.selected().unwrap_or(0) as isize;
+        let next = (current + delta).clamp(0, len as isize - 1);
+        self.state.select(Some(next as usize));
+    }
+}
@@ -1,12 +1,15 @@
 use crate::app::{App, AppResult};
 use crate::event::EventHandler;
 use crate::ui;
-use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::event::{
+    DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+};
 use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
 use ratatui::backend::Backend;
 use ratatui::Terminal;
 use std::io;
 use std::panic;
+use std::time::Instant;
 
 /// Representation of a terminal user interface.
 ///
@@ -30,8 +33,7 @@ impl<B: Backend> Tui<B> {
     ///
     /// It enables the raw mode and sets terminal properties.
     pub fn init(&mut self) -> AppResult<()> {
-        terminal::enable_raw_mode()?;
-        crossterm::execute!(io::stderr(), EnterAlternateScreen, EnableMouseCapture)?;
+        Self::enter()?;
 
         // Define a custom panic hook to reset the terminal properties.
         // This way, you won't have your terminal messed up if an unexpected error happens.
@@ -51,17 +53,68 @@ impl<B: Backend> Tui<B> {
     /// [`Draw`]: ratatui::Terminal::draw
     /// [`rendering`]: crate::ui:render
     pub fn draw(&mut self, app: &mut App) -> AppResult<()> {
+        let start = Instant::now();
         self.terminal.draw(|frame| ui::render(app, frame))?;
+        app.frame_time = start.elapsed();
+        Ok(())
+    }
+
+    /// Sets the terminal properties which the interface needs: raw mode, the
+    /// alternate screen, mouse capture and bracketed paste.
+    fn enter() -> AppResult<()> {
+        terminal::enable_raw_mode()?;
+        crossterm::execute!(
+            io::stderr(),
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            EnableBracketedPaste
+        )?;
         Ok(())
     }
 
     /// Resets the terminal interface.
     ///
-    /// This function is also used for the panic hook to revert
-    /// the terminal properties if unexpected errors occur.
+    /// It reverts everything [`enter`] set, in reverse order. This function is
+    /// also used for the panic hook to revert the terminal properties if
+    /// unexpected errors occur.
+    ///
+    /// [`enter`]: Tui::enter
     fn reset() -> AppResult<()> {
+        crossterm::execute!(
+            io::stderr(),
+            DisableBracketedPaste,
+            DisableMouseCapture,
+            LeaveAlternateScreen
+        )?;
         terminal::disable_raw_mode()?;
-        crossterm::execute!(io::stderr(), LeaveAlternateScreen, DisableMouseCapture)?;
+        Ok(())
+    }
+
+    /// Suspends the terminal interface so that an external process can use the terminal.
+    ///
+    /// It reverts the terminal properties like [`exit`] does, shows the cursor, and
+    /// stops reading terminal events so that the process gets all input. [`resume`]
+    /// must be called afterwards to continue rendering.
+    ///
+    /// [`exit`]: Tui::exit
+    /// [`resume`]: Tui::resume
+    pub fn suspend(&mut self) -> AppResult<()> {
+        self.events.set_paused(true);
+        Self::reset()?;
+        self.terminal.show_cursor()?;
+        Ok(())
+    }
+
+    /// Resumes the terminal interface after it was suspended.
+    ///
+    /// The terminal properties are set again even if the external process changed
+    /// them, and the whole screen is redrawn, so that nothing the process left on
+    /// the screen stays around.
+    pub fn resume(&mut self) -> AppResult<()> {
+        Self::enter()?;
+        self.terminal.hide_cursor()?;
+        self.terminal.clear()?;
+        self.events.set_paused(false);
         Ok(())
     }
 
@@ -1,4 +1,35 @@
-use crate::{app::App, app::Column, file_blame::BlameLine, file_blame::Commit};
+use crate::{
+    action::Action,
+    age::{self, AgeWarning, DateFormat},
+    annotations::AnnotationProvider,
+    app::App,
+    app::AppState,
+    app::Column,
+    app::ColumnKind,
+    app::CommitAction,
+    app::Pane,
+    app::Popup,
+    app::PromptKind,
+    codeowners, colors,
+    diff::{self, DiffLineKind, DiffPane},
+    file_blame::BlameLine,
+    file_blame::Commit,
+    file_blame::CopyDetection,
+    file_blame::FileInfo,
+    file_blame::WORKTREE,
+    handler::KeyMap,
+    lfs::LfsPointer,
+    links::IssueLinker,
+    locale::Locale,
+    message::{self, MessageCleaner},
+    metrics,
+    reverts::Reverts,
+    review::Review,
+    search,
+    stats::FileStats,
+    theme::Theme,
+    tree::FileTree,
+};
 use ratatui::{
     layout::*,
     prelude::*,
@@ -6,12 +37,15 @@ use ratatui::{
     widgets::*,
     Frame,
 };
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::str::FromStr;
+use std::mem;
+use std::ops::{Range, RangeInclusive};
+use std::path::Path;
 
 // Divider cell between columns in a row.
-fn divider_cell<'a>() -> Cell<'a> {
-    Cell::from("│")
+fn divider_cell(divider: &str) -> Cell<'_> {
+    Cell::from(divider)
 }
 
 fn empty_cell<'a>() -> Cell<'a> {
@@ -35,51 +69,1227 @@ where
 
 /// Renders the user interface widgets.
 pub fn render(app: &mut App, frame: &mut Frame) {
-    let rects = Layout::default()
-        .constraints([Constraint::Percentage(100)])
-        .split(frame.size());
+    // For screen readers, an open popup is shown on its own, so that its
+    // text isn't mixed up with the lines around it.
+    if app.is_loading() {
+        render_loading(app, frame.size(), frame.buffer_mut());
+    } else if app.accessible {
+        if app.popup.is_none() {
+            render_accessible(app, frame);
+        }
+    } else {
+        render_blame(app, frame);
+    }
+
+    if let Some(Popup::ContextMenu(selected)) = &app.popup {
+        render_context_menu(
+            &app.context_menu_items(),
+            *selected,
+            app.theme.selected,
+            frame,
+        );
+    } else if let Some(Popup::Todos(selected)) = &app.popup {
+        render_todos(app, *selected, frame);
+    } else if let Some(Popup::Prompt(PromptKind::Search, text)) = &app.popup {
+        render_search_bar(text, frame);
+    } else if let Some(Popup::Blocks(selected)) = &app.popup {
+        render_blocks(app, *selected, frame);
+    } else if let Some(Popup::CommitHistory(selected)) = &app.popup {
+        render_commit_history(app, *selected, frame);
+    } else if let Some(Popup::Statistics(stats, selected)) = &app.popup {
+        render_statistics(app, stats, *selected, frame);
+    } else if let Some(Popup::Help(scroll)) = &app.popup {
+        render_help(&app.key_map, *scroll, frame);
+    } else if let Some(Popup::Legend) = &app.popup {
+        render_legend(app, frame);
+    } else if let Some(Popup::ExpandedLine(i, scroll)) = &app.popup {
+        render_expanded_line(app, *i, *scroll, frame);
+    } else if let Some(popup) = &app.popup {
+        render_popup(
+            popup,
+            &app.commit_cache,
+            app.issue_linker.as_ref(),
+            &app.reverts,
+            frame,
+        );
+    }
+
+    if app.show_metrics {
+        render_metrics(app, frame);
+    }
+
+    // Borders of popups would be read out by screen readers.
+    if app.accessible {
+        remove_box_drawing(frame.buffer_mut());
+    }
+
+    // Syntax highlighting and some styles use RGB colors, which are
+    // converted for terminals which don't support them.
+    app.color_support.convert_buffer(frame.buffer_mut());
+}
+
+/// Read-only blame view for embedding in other ratatui applications, e.g.
+/// file managers, as a part of their own layouts. Its state is an [`App`],
+/// usually created with [`App::loading`] with a callback which passes the
+/// progress of loading to [`App::set_progress`] from the host's event loop. Key events are passed to
+/// [`crate::handler::handle_embedded_key_events`], which only performs
+/// actions that move around the blame, and leaves other keys to the host.
+/// Popups, panes and the file tree aren't rendered.
+pub struct BlameWidget;
+
+impl StatefulWidget for BlameWidget {
+    type State = App;
+
+    fn render(self, area: Rect, buf: &mut Buffer, app: &mut App) {
+        if app.is_loading() {
+            render_loading(app, area, buf);
+        } else if app.file_blame.is_some() {
+            render_main_pane(app, area, buf);
+        } else if let Some(err) = &app.load_err {
+            Paragraph::new(err.to_string())
+                .red()
+                .block(Block::default().borders(Borders::ALL))
+                .render(area, buf);
+        }
+    }
+}
+
+// Renders a tab for each file under review, highlighting the blamed file.
+fn render_review_tabs(review: &Review, file_path: &str, frame: &mut Frame, area: Rect) {
+    let tabs = Tabs::new(review.files.iter().map(|f| f.as_str()))
+        .select(review.tab_of(file_path).unwrap_or(usize::MAX))
+        .highlight_style(Style::default().black().on_yellow())
+        .divider("│");
+    frame.render_widget(tabs, area);
+}
+
+// Renders how long loading has taken so far, and a bar with the progress of
+// the current phase below it, in the middle of the screen, while the blame is
+// loaded in the background.
+fn render_loading(app: &App, area: Rect, buf: &mut Buffer) {
+    let AppState::Loading { started } = app.app_state else {
+        return;
+    };
+    let mut text = format!(
+        "Loading blame for {} at {}... {}s, esc to cancel",
+        app.file_path,
+        app.commit_sha,
+        started.elapsed().as_secs()
+    );
+    // In partial clones, old versions of the file may be fetched one by one.
+    if let Some(promisor) = &app.promisor {
+        let fetches = promisor.fetches();
+        if fetches > 0 {
+            text.push_str(&format!(
+                ", missing objects fetched from {}: {}",
+                promisor.remote, fetches
+            ));
+        }
+    }
+    let [_, line, bar, _] = Layout::vertical([
+        Constraint::Fill(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Fill(1),
+    ])
+    .areas(area);
+    Paragraph::new(text)
+        .alignment(Alignment::Center)
+        .render(line, buf);
+
+    let Some(progress) = app.progress else {
+        return;
+    };
+    let label = format!("{} {}%", progress.phase.label(), progress.percent);
+    // Screen readers only get the label, without the characters of the bar.
+    if app.accessible {
+        Paragraph::new(label)
+            .alignment(Alignment::Center)
+            .render(bar, buf);
+        return;
+    }
+    let [_, bar, _] = Layout::horizontal([
+        Constraint::Fill(1),
+        Constraint::Max(60),
+        Constraint::Fill(1),
+    ])
+    .areas(bar);
+    LineGauge::default()
+        .ratio(f64::from(progress.percent) / 100.0)
+        .label(label)
+        .filled_style(Style::default().fg(Color::Cyan))
+        .line_set(symbols::line::THICK)
+        .render(bar, buf);
+}
+
+// Renders the blame tables, and the file tree if it's shown, above the
+// status bar.
+fn render_blame(app: &mut App, frame: &mut Frame) {
+    let [mut area, status_area] =
+        Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.size());
+    render_status_bar(app, frame, status_area);
+
+    // The files under review are shown in tabs above everything else.
+    if let Some(review) = &app.review {
+        let [tabs_area, rest] =
+            Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(area);
+        render_review_tabs(review, &app.file_path, frame, tabs_area);
+        area = rest;
+    }
+
+    // The file tree is shown in a sidebar to the left of the blame.
+    app.screen_layout.panes.clear();
+    if let Some(tree) = app.file_tree.as_mut() {
+        let rects = Layout::horizontal([Constraint::Length(30), Constraint::Fill(1)]).split(area);
+        app.screen_layout.panes.push((Pane::Tree, rects[0]));
+        render_file_tree(
+            tree,
+            app.focus == Pane::Tree,
+            app.theme.selected,
+            frame,
+            rects[0],
+        );
+        area = rects[1];
+    }
+
+    let rects = if app.split.is_some() || app.diff.is_some() {
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).split(area)
+    } else {
+        Layout::default()
+            .constraints([Constraint::Percentage(100)])
+            .split(area)
+    };
+
+    app.screen_layout.panes.push((Pane::Main, rects[0]));
+    if app.split.is_some() {
+        app.screen_layout.panes.push((Pane::Split, rects[1]));
+    } else if app.diff.is_some() {
+        app.screen_layout.panes.push((Pane::Diff, rects[1]));
+    }
+    render_main_pane(app, rects[0], frame.buffer_mut());
+
+    // The blame at another commit shown side by side with the main blame. It's
+    // taken out of the app while rendering, since the rows borrow from the app.
+    if let Some(mut split) = app.split.take() {
+        let columns = shown_columns(app);
+        // The table is inside the borders of the pane.
+        let width = rects[1].width.saturating_sub(2);
+        let context = RowContext {
+            annotations: None,
+            audit_owners: None,
+            wrap_width: app.wrap_contents.then(|| contents_width(&columns, width)),
+            ..row_context(app, &columns)
+        };
+        let lock = if split.locked { "locked" } else { "unlocked" };
+        let title = format!("Blame at parent: {} ({})", split.commit_sha, lock);
+        let focused = app.focus == Pane::Split;
+        let t = blame_table(
+            &split.file_blame.blame_lines,
+            0..split.file_blame.blame_lines.len(),
+            &context,
+            None,
+            pane_block(title, focused),
+        );
+        frame.render_stateful_widget(t, rects[1], &mut split.state);
+        app.split = Some(split);
+    }
+
+    if let Some(diff) = &app.diff {
+        render_diff(diff, app.focus == Pane::Diff, frame, rects[1]);
+    }
+}
+
+// Renders the status bar, with the keys which can be pressed on the right,
+// and either the latest message or the summary of the selected line's commit
+// on the left.
+fn render_status_bar(app: &App, frame: &mut Frame, area: Rect) {
+    let mut hints = vec![];
+    for (key, description) in status_bar_hints(app) {
+        hints.push(format!(" {} ", key).black().on_gray());
+        hints.push(format!(" {} ", description).dark_gray());
+    }
+    let hints = Line::from(hints);
+    let [message_area, hints_area] = Layout::horizontal([
+        Constraint::Fill(1),
+        Constraint::Length(hints.width() as u16),
+    ])
+    .areas(area);
+
+    let message = if let Some(status) = &app.status {
+        if status.is_error {
+            Line::from(status.text.as_str().red())
+        } else {
+            Line::from(status.text.as_str().green())
+        }
+    } else if let Some(commit) = app.selected_commit() {
+        Line::from(vec![
+            format!("{} ", &commit.sha[..8.min(commit.sha.len())]).yellow(),
+            // The type of conventional commits is only left out of the
+            // narrow MESSAGE column.
+            Span::raw(MessageCleaner::default().clean(&commit.commit_message)),
+        ])
+    } else {
+        Line::default()
+    };
+    frame.render_widget(Paragraph::new(message), message_area);
+    frame.render_widget(Paragraph::new(hints), hints_area);
+}
+
+// Actions whose keys are shown in the status bar while the blame table has
+// focus, with short descriptions.
+const STATUS_BAR_ACTIONS: &[(Action, &str)] = &[
+    (Action::ShowCommit, "details"),
+    (Action::NextCommit, "parent"),
+    (Action::PreviousCommit, "back"),
+    (Action::ContextMenu, "actions"),
+    (Action::Search, "search"),
+    (Action::Help, "help"),
+    (Action::Quit, "quit"),
+];
+
+// Descriptions of the keys for traveling between commits in a reverse
+// blame, where Left goes back and Right follows the line to its deletion.
+fn reverse_description(action: Action, description: &'static str) -> &'static str {
+    match action {
+        Action::NextCommit => "back",
+        Action::PreviousCommit => "deletion",
+        _ => description,
+    }
+}
+
+// The most useful keys in the current context, e.g. in the focused pane or
+// the open popup, with what they do. In the blame table, the keys are the
+// first ones bound to the actions, which may have been changed in the config
+// file.
+fn status_bar_hints(app: &App) -> Vec<(String, &'static str)> {
+    if app.popup.is_none() && matches!(app.focus, Pane::Main | Pane::Split) {
+        return STATUS_BAR_ACTIONS
+            .iter()
+            .filter_map(|(action, description)| {
+                let key = app.key_map.keys(*action).into_iter().next()?;
+                match app.parse_options.reverse_until {
+                    Some(_) => Some((key, reverse_description(*action, description))),
+                    None => Some((key, *description)),
+                }
+            })
+            .collect();
+    }
+    let hints: &[(&str, &'static str)] = match &app.popup {
+        Some(Popup::CommitDetail(..)) => &[("r", "revert"), ("p", "cherry-pick"), ("Esc", "close")],
+        Some(Popup::ConfirmCommitAction(..)) => &[("y", "confirm"), ("n", "cancel")],
+        Some(Popup::Prompt(..)) => &[("Enter", "accept"), ("Esc", "cancel")],
+        Some(Popup::ContextMenu(_)) => &[("↑↓", "move"), ("Enter", "run"), ("Esc", "close")],
+        Some(Popup::Todos(_)) => &[("↑↓", "move"), ("Enter", "jump"), ("Esc", "close")],
+        Some(Popup::Blocks(_)) => &[
+            ("↑↓", "move"),
+            ("s", "sort"),
+            ("Enter", "jump"),
+            ("Esc", "close"),
+        ],
+        Some(Popup::CommitHistory(_)) => &[("↑↓", "move"), ("Enter", "go back"), ("Esc", "close")],
+        Some(Popup::Statistics(..)) => &[
+            ("↑↓", "move"),
+            ("s", "sort"),
+            ("Enter", "filter"),
+            ("Esc", "close"),
+        ],
+        Some(Popup::ExpandedLine(..)) | Some(Popup::Help(_)) => {
+            &[("↑↓", "scroll"), ("Esc", "close")]
+        }
+        Some(_) => &[("Esc", "close")],
+        None => match app.focus {
+            Pane::Tree => &[
+                ("↑↓", "move"),
+                ("Enter", "open"),
+                ("Tab", "next pane"),
+                ("f", "hide"),
+            ],
+            _ => &[("↑↓", "scroll"), ("Tab", "next pane"), ("d", "close")],
+        },
+    };
+    hints
+        .iter()
+        .map(|(key, description)| (key.to_string(), *description))
+        .collect()
+}
+
+// The columns which aren't hidden, and only the line numbers and contents of
+// them in the plain view, so that the contents get the full width. Hidden
+// columns leave their width to the others.
+fn shown_columns(app: &App) -> Vec<Column> {
+    app.columns
+        .iter()
+        .filter(|c| c.visible)
+        .filter(|c| !app.plain_view || matches!(c.kind, ColumnKind::Line | ColumnKind::Contents))
+        .cloned()
+        .collect()
+}
+
+// The information shared by the rows of the blame tables, showing the given
+// columns.
+fn row_context<'a>(app: &'a App, columns: &'a [Column]) -> RowContext<'a> {
+    RowContext {
+        commit_cache: &app.commit_cache,
+        theme: &app.theme,
+        columns,
+        annotations: app.annotations.as_deref(),
+        audit_owners: app.file_owners.as_deref().filter(|_| app.owners_audit),
+        issue_linker: app.issue_linker.as_ref(),
+        age_warning: app.age_warning,
+        locale: app.locale,
+        now: age::now(),
+        marked: app.review.as_ref().filter(|r| !r.hide_other_lines),
+        history_start: app.history_start,
+        // While searching, the matches of what was typed so far are highlighted.
+        search_query: match &app.popup {
+            Some(Popup::Prompt(PromptKind::Search, text)) => text,
+            _ => &app.search_query,
+        },
+        commit_accents: app.commit_accents,
+        heat_range: app
+            .file_blame
+            .as_ref()
+            .filter(|_| app.heatmap)
+            .and_then(|file_blame| file_blame.time_range(&app.commit_cache)),
+        show_skipped_commits: app.show_skipped_commits,
+        date_format: app.date_format,
+        author_filter: app.author_filter.as_deref(),
+        contents_offset: app.contents_offset,
+        wrap_width: None,
+        message_cleaner: app.message_cleaner,
+        commit_type_badges: app.commit_type_badges,
+        reverts: &app.reverts,
+    }
+}
+
+// Where the given columns of a table are in its area, which are laid out like
+// the table does, with dividers between them.
+fn column_areas(columns: &[Column], area: Rect) -> Vec<Rect> {
+    let widths = insert_between(
+        columns.iter().map(|c| c.width).collect(),
+        Constraint::Max(1),
+    );
+    let rects = Layout::horizontal(widths)
+        .flex(layout::Flex::Start)
+        .spacing(1)
+        .split(area);
+    rects.iter().step_by(2).copied().collect()
+}
 
-    let selected_style = Style::default().bg(Color::from_str("#3f3f3f").unwrap());
+// Width of the contents column of a table with the given columns and width.
+fn contents_width(columns: &[Column], width: u16) -> usize {
+    columns
+        .iter()
+        .position(|c| c.kind == ColumnKind::Contents)
+        .map_or(0, |i| {
+            column_areas(columns, Rect::new(0, 0, width, 1))[i].width as usize
+        })
+}
+
+// Renders the main blame table with its title and the file's metadata.
+fn render_main_pane(app: &mut App, area: Rect, buf: &mut Buffer) {
+    let kind = match (app.plain_view, &app.parse_options.reverse_until) {
+        (true, _) => "Contents",
+        (false, Some(_)) => "Reverse blame",
+        (false, None) => "Blame",
+    };
+    let mut title = format!(
+        "{} for file: {} at ref: {}",
+        kind, app.file_path, app.commit_sha
+    );
+    if let Some(end) = &app.parse_options.reverse_until {
+        title.push_str(&format!("..{}", end));
+    }
+    // At commits before the file was renamed, the title shows its old name,
+    // followed by the name it has where the travel back in time started.
+    if let Some((_, path)) = app
+        .commit_stack
+        .first()
+        .filter(|(_, p)| *p != app.file_path)
+    {
+        title.push_str(&format!(" now: {}", path));
+    }
+    if !app.commit_stack.is_empty() {
+        title.push_str(&format!(
+            " traveled from: {}",
+            breadcrumb(&app.commit_stack)
+        ));
+    }
+    if let Some((start, end)) = app.parse_options.line_range {
+        title.push_str(&format!(" lines: {}-{}", start, end));
+    }
+    if let Some(promisor) = &app.promisor {
+        if app.parse_options.no_fetch {
+            title.push_str(&format!(
+                " partial clone of {}, not fetching",
+                promisor.remote
+            ));
+        } else {
+            title.push_str(&format!(
+                " partial clone of {}, fetches: {}",
+                promisor.remote,
+                promisor.fetches()
+            ));
+        }
+    }
+    if let Some(file) = &app.parse_options.ignore_revs_file {
+        let name = Path::new(file).file_name().unwrap_or_default();
+        title.push_str(&format!(" ignoring revs in: {}", name.to_string_lossy()));
+    }
+    match app.parse_options.ignore_revs.as_slice() {
+        [] => {}
+        [sha] => title.push_str(&format!(" ignoring: {}", &sha[..8])),
+        revs => title.push_str(&format!(" ignoring: {} commits", revs.len())),
+    }
+    if app.parse_options.copy_detection != CopyDetection::Off {
+        title.push_str(&format!(
+            " detecting: {}",
+            app.parse_options.copy_detection.label()
+        ));
+    }
+    if let Some(owners) = &app.file_owners {
+        title.push_str(&format!(" owned by: {}", owners.join(" ")));
+    }
+    if let Some(author) = &app.author_filter {
+        title.push_str(&format!(" author: {}", author));
+    }
+    if app.wrap_contents {
+        title.push_str(" wrapping lines");
+    } else if app.contents_offset > 0 {
+        title.push_str(&format!(" scrolled: {} columns", app.contents_offset));
+    }
+    if let Some(floor) = &app.floor_commit {
+        title.push_str(&format!(" floor: {}", floor));
+    }
+    if let Some(review) = &app.review {
+        title.push_str(&format!(" reviewing: {}", review.range));
+    }
+    if let (Some(hotspots), Some(i)) = (&app.hotspots, app.hotspot_index) {
+        title.push_str(&format!(
+            " hotspot {}/{}: score {:.1}, {} changes",
+            i + 1,
+            hotspots.len(),
+            hotspots[i].score,
+            hotspots[i].changes
+        ));
+    }
+
+    // The file's metadata is shown in a header under the title, above the table.
+    let focused = (app.split.is_some() || app.diff.is_some() || app.file_tree.is_some())
+        && app.focus == Pane::Main;
+    let mut block = pane_block(title, focused);
+    if app.has_uncommitted_changes() {
+        block = block.title(
+            block::Title::from(
+                " uncommitted changes, press w to include them "
+                    .black()
+                    .on_yellow(),
+            )
+            .alignment(Alignment::Right),
+        );
+    }
+    if let Some(followed) = &app.followed_ref {
+        if let Some(new_tip) = &followed.new_tip {
+            block = block.title(
+                block::Title::from(
+                    format!(
+                        " {} moved to {}, press R to jump to it ",
+                        followed.name,
+                        &new_tip[..8]
+                    )
+                    .black()
+                    .on_cyan(),
+                )
+                .alignment(Alignment::Right),
+            );
+        }
+    }
+    let inner = block.inner(area);
+    block.render(area, buf);
+    let [header_area, table_area] =
+        Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(inner);
+    // The header row of the table and the margin below it take two lines.
+    app.set_viewport_height(table_area.height.saturating_sub(2) as usize);
+
+    let columns = shown_columns(app);
+    let file_blame = app.file_blame.as_ref().unwrap();
+    let selection = app.selection_anchor.and(app.selected_range());
+    let context = RowContext {
+        wrap_width: app
+            .wrap_contents
+            .then(|| contents_width(&columns, table_area.width)),
+        ..row_context(app, &columns)
+    };
+
+    if let Some(info) = &app.file_info {
+        let header = file_header(info, file_blame.blame_lines.len());
+        Paragraph::new(header).dark_gray().render(header_area, buf);
+    }
+    if let Some(pointer) = file_blame
+        .lfs_pointer
+        .as_ref()
+        .filter(|_| app.shows_lfs_notice())
+    {
+        render_lfs_notice(pointer, table_area, buf);
+        return;
+    }
+    // Only the visible lines are turned into rows, so that huge files render
+    // as quickly as small ones. The view is scrolled by the app, so the table
+    // is rendered from its first row.
+    let offset = app.state.offset();
+    let visible = offset..(offset + app.viewport_height).min(file_blame.blame_lines.len());
+    let t = blame_table(
+        &file_blame.blame_lines,
+        visible.clone(),
+        &context,
+        selection,
+        Block::default(),
+    );
+    let mut state = TableState::default().with_selected(app.state.selected().map(|i| i - offset));
+    StatefulWidget::render(t, table_area, buf, &mut state);
+
+    // Where each line ended up, for clicking on it. The table scrolls by
+    // itself if wrapped rows don't fit, in which case its offset moves.
+    let mut rows = vec![];
+    let mut y = table_area.y + 2;
+    for i in (offset + state.offset())..visible.end {
+        if y >= table_area.bottom() {
+            break;
+        }
+        let height = if context.wrap_width.is_some() {
+            contents_text(&file_blame.blame_lines[i], &context)
+                .lines
+                .len()
+                .max(1) as u16
+        } else {
+            1
+        };
+        let height = height.min(table_area.bottom() - y);
+        rows.push((Rect::new(table_area.x, y, table_area.width, height), i));
+        y += height;
+    }
+    let commit_column = columns
+        .iter()
+        .position(|c| c.kind == ColumnKind::Commit)
+        .map(|i| {
+            let rect = column_areas(&columns, table_area)[i];
+            rect.x..rect.right()
+        });
+    app.screen_layout.rows = rows;
+    app.screen_layout.commit_column = commit_column;
+}
+
+// Renders the notice shown instead of the blame of a file stored with Git
+// LFS, with the metadata from its pointer.
+fn render_lfs_notice(pointer: &LfsPointer, area: Rect, buf: &mut Buffer) {
+    let lines = vec![
+        Line::from(
+            "This file is stored with Git LFS, so git only has a pointer to its contents:".bold(),
+        ),
+        Line::from(""),
+        Line::from(format!("  oid  {}", pointer.oid)),
+        Line::from(format!("  size {} bytes", pointer.size)),
+        Line::from(""),
+        Line::from(
+            "Blaming the pointer shows who last replaced the file, not who wrote its contents.",
+        ),
+        Line::from("Press Enter to blame the pointer anyway."),
+    ];
+    Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .render(area.inner(Margin::new(1, 1)), buf);
+}
+
+// Renders the patch of a commit, with added and removed lines colored.
+fn render_diff(diff: &DiffPane, focused: bool, frame: &mut Frame, area: Rect) {
+    let lines: Vec<Line> = diff
+        .lines
+        .iter()
+        .skip(diff.scroll)
+        .take(area.height as usize)
+        .map(|line| {
+            let span = Span::from(line.as_str());
+            Line::from(match diff::line_kind(line) {
+                DiffLineKind::Header => span.bold(),
+                DiffLineKind::Hunk => span.cyan(),
+                DiffLineKind::Added => span.green(),
+                DiffLineKind::Removed => span.red(),
+                DiffLineKind::Context => span,
+            })
+        })
+        .collect();
+    let title = format!(
+        "Diff of {} (line {} of {})",
+        diff.commit_sha,
+        (diff.scroll + 1).min(diff.lines.len()),
+        diff.lines.len()
+    );
+    frame.render_widget(
+        Paragraph::new(lines).block(pane_block(title, focused)),
+        area,
+    );
+}
+
+// Renders the blame for screen readers: one plain line of text per blamed
+// line, without borders or columns, and a line at the top which announces
+// the selected line whenever it changes.
+fn render_accessible(app: &mut App, frame: &mut Frame) {
+    let Some(file_blame) = &app.file_blame else {
+        return;
+    };
+    let record = |i: usize| {
+        let line = &file_blame.blame_lines[i];
+        let commit = app.commit_cache.get(&line.commit_sha);
+        format!(
+            "line {}, {}, {}, {}: {}",
+            line.line_number,
+            commit.map(|c| c.author.as_str()).unwrap_or(""),
+            commit
+                .map(|c| format_date(c, app.date_format, app.locale, age::now()))
+                .unwrap_or_default(),
+            line.commit_sha,
+            line.plain_contents
+        )
+    };
+
+    let selected = app.state.selected().unwrap_or(0);
+    let total = file_blame.blame_lines.len();
+    let mut lines = vec![Line::from(format!(
+        "Blame for {} at {}. Selected {} of {} lines. {}",
+        app.file_path,
+        app.commit_sha,
+        selected + 1,
+        total,
+        if total > 0 {
+            record(selected)
+        } else {
+            String::new()
+        }
+    ))];
+    if let Some(status) = &app.status {
+        lines.push(Line::from(status.text.as_str()));
+    }
+    if let Some(review) = &app.review {
+        lines.push(Line::from(format!(
+            "Reviewing file {} of {} changed in {}.",
+            review.tab_of(&app.file_path).map_or(0, |i| i + 1),
+            review.files.len(),
+            review.range
+        )));
+    }
+
+    // The records around the selected line, with the selected one marked.
+    let height = (frame.size().height as usize).saturating_sub(lines.len());
+    let start = selected
+        .saturating_sub(height / 2)
+        .min(total.saturating_sub(height));
+    for i in start..(start + height).min(total) {
+        let marker = if i == selected { "> " } else { "  " };
+        lines.push(Line::from(format!("{}{}", marker, record(i))));
+    }
+    frame.render_widget(Paragraph::new(lines), frame.size());
+}
+
+// Replace box drawing characters, e.g. of borders, with spaces.
+fn remove_box_drawing(buffer: &mut Buffer) {
+    for cell in buffer.content.iter_mut() {
+        if cell
+            .symbol()
+            .chars()
+            .any(|c| ('\u{2500}'..='\u{259f}').contains(&c))
+        {
+            cell.set_symbol(" ");
+        }
+    }
+}
+
+// Renders the tree of the repository's files at the blamed commit.
+fn render_file_tree(
+    tree: &mut FileTree,
+    focused: bool,
+    highlight_style: Style,
+    frame: &mut Frame,
+    area: Rect,
+) {
+    let items: Vec<ListItem> = tree
+        .visible_items()
+        .into_iter()
+        .map(|item| {
+            let indent = "  ".repeat(item.depth);
+            if item.is_dir {
+                let marker = if item.expanded { "▾ " } else { "▸ " };
+                ListItem::new(format!("{}{}{}/", indent, marker, item.name).blue())
+            } else if item.in_worktree {
+                ListItem::new(format!("{}  {}", indent, item.name))
+            } else {
+                ListItem::new(format!("{}  {} (deleted)", indent, item.name).dark_gray())
+            }
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(focus_style(focused))
+                .title(format!("Files at {}", tree.commit_sha)),
+        )
+        .highlight_style(highlight_style);
+    frame.render_stateful_widget(list, area, &mut tree.state);
+}
+
+// Style of a pane's border, highlighted if the pane has focus.
+fn focus_style(focused: bool) -> Style {
+    if focused {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    }
+}
+
+// Creates the bordered block around a pane with the given title.
+fn pane_block<'a>(title: String, focused: bool) -> Block<'a> {
+    Block::default()
+        .borders(Borders::ALL)
+        .border_style(focus_style(focused))
+        .title(title)
+}
+
+// Creates the header line with the metadata of the blamed file, e.g.
+// "12 lines, 345 bytes, mode 100644, same as worktree".
+fn file_header(info: &FileInfo, lines: usize) -> String {
+    let mode = if info.is_executable() {
+        format!("mode {} (executable)", info.mode)
+    } else {
+        format!("mode {}", info.mode)
+    };
+    let worktree = if info.sparse {
+        "not checked out by the sparse checkout"
+    } else if info.differs_from_worktree {
+        "differs from worktree"
+    } else {
+        "same as worktree"
+    };
+    format!(
+        "{} lines, {} bytes, {}, {}",
+        lines, info.size, mode, worktree
+    )
+}
+
+// Creates the table for a list of blame lines, inside the given block. The
+// given selection is a range of line indices which are highlighted in
+// addition to the selected line.
+fn blame_table<'a>(
+    blame_lines: &'a [BlameLine],
+    visible: Range<usize>,
+    context: &RowContext<'a>,
+    selection: Option<RangeInclusive<usize>>,
+    block: Block<'a>,
+) -> Table<'a> {
+    let theme = context.theme;
 
     // Set up the header row.
-    let mut header_cells = app
+    let mut header_cells = context
         .columns
         .iter()
         .map(|c| c.header_name())
-        .map(|h| Cell::from(h).style(Style::default().fg(Color::Red).bold()))
+        .map(|h| Cell::from(h).style(theme.header))
         .collect();
-    header_cells = insert_between(header_cells, divider_cell());
+    header_cells = insert_between(header_cells, divider_cell(&theme.divider));
     let header = Row::new(header_cells).height(1).bottom_margin(1);
 
-    // Set up blame line rows
-    let mut previous_sha = "".to_string();
-    let file_blame = app.file_blame.as_ref().unwrap();
-    let rows = file_blame.blame_lines.iter().map(|item| {
-        let row = table_row_for_blame_line(
-            &previous_sha,
-            &item.commit_sha,
-            item,
-            &app.commit_cache,
-            &app.columns,
-        );
-        previous_sha = item.commit_sha.clone();
-        row
-    });
+    // Set up rows for the visible blame lines. Like the lines of a block in
+    // view, lines continuing a block from above the view don't repeat its
+    // commit information.
+    let shown_sha = |item: &'a BlameLine| match &item.skipped_commit {
+        Some(sha) if context.show_skipped_commits => sha.as_str(),
+        _ => item.commit_sha.as_str(),
+    };
+    let mut previous_sha = visible
+        .start
+        .checked_sub(1)
+        .map_or("", |i| shown_sha(&blame_lines[i]));
+    let rows: Vec<Row> = blame_lines[visible.clone()]
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let i = visible.start + i;
+            let commit_sha = shown_sha(item);
+            let mut row = table_row_for_blame_line(previous_sha, commit_sha, item, context);
+            if selection.as_ref().is_some_and(|r| r.contains(&i)) {
+                row = row.style(theme.range);
+            }
+            previous_sha = commit_sha;
+            row
+        })
+        .collect();
 
     // Set up the column widths
-    let mut widths: Vec<Constraint> = app.columns.iter().map(|c| c.width).collect();
+    let mut widths: Vec<Constraint> = context.columns.iter().map(|c| c.width).collect();
     widths = insert_between(widths, Constraint::Max(1));
 
     // Create the whole table using the header, rows and column widths.
-    let t = Table::new(rows, widths)
+    Table::new(rows, widths)
         .header(header)
         .column_spacing(1)
-        .block(Block::default().borders(Borders::ALL).title(format!(
-            "Blame for file: {} at ref: {}",
-            app.file_path, app.commit_sha
-        )))
-        .highlight_style(selected_style);
-    frame.render_stateful_widget(t, rects[0], &mut app.state);
+        .block(block)
+        .highlight_style(theme.selected)
+}
+
+// Creates a line from a text, with issue IDs underlined.
+fn linkified_line<'a>(
+    text: impl Into<Cow<'a, str>>,
+    issue_linker: Option<&IssueLinker>,
+) -> Line<'a> {
+    let text = text.into();
+    let Some(linker) = issue_linker else {
+        return Line::from(Span::raw(text));
+    };
+
+    let mut spans = vec![];
+    let mut last = 0;
+    for range in linker.find(&text) {
+        spans.push(Span::raw(text[last..range.start].to_string()));
+        spans.push(Span::raw(text[range.clone()].to_string()).underlined());
+        last = range.end;
+    }
+    spans.push(Span::raw(text[last..].to_string()));
+    Line::from(spans)
+}
+
+// A compact badge for the type of a conventional commit, e.g. "fix" in
+// black on red. Types without a color of their own are gray.
+fn commit_type_badge(kind: &str) -> Span<'static> {
+    let color = match kind {
+        "feat" => Color::Green,
+        "fix" => Color::Red,
+        "refactor" => Color::Blue,
+        "perf" => Color::Magenta,
+        "docs" => Color::Cyan,
+        "test" => Color::Yellow,
+        _ => Color::Gray,
+    };
+    Span::styled(
+        format!("{:.8}", kind),
+        Style::default().fg(Color::Black).bg(color),
+    )
+}
+
+// Creates a rectangle of the given size centered within another rectangle.
+fn centered_rect(width: u16, height: u16, r: Rect) -> Rect {
+    let width = width.min(r.width);
+    let height = height.min(r.height);
+    Rect::new(
+        r.x + (r.width - width) / 2,
+        r.y + (r.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+// Number of changed files listed in the commit detail popup, so that the
+// popup fits on the screen for large commits.
+const MAX_CHANGED_FILES: usize = 10;
+
+// Renders a popup on top of the blame table.
+fn render_popup(
+    popup: &Popup,
+    commit_cache: &HashMap<String, Commit>,
+    issue_linker: Option<&IssueLinker>,
+    reverts: &Reverts,
+    frame: &mut Frame,
+) {
+    let (title, lines) = match popup {
+        Popup::CommitDetail(sha, details) => {
+            let mut lines = vec![];
+            if let Some(commit) = commit_cache.get(sha) {
+                lines.push(Line::from(vec![
+                    "Commit:  ".bold(),
+                    commit.sha.as_str().green(),
+                ]));
+                lines.push(Line::from(vec![
+                    "Author:  ".bold(),
+                    commit.author.as_str().red(),
+                    format!(" <{}>", commit.author_email).into(),
+                ]));
+                lines.push(Line::from(vec![
+                    "Date:    ".bold(),
+                    commit.timestamp.as_str().blue(),
+                ]));
+                lines.push(Line::from(vec![
+                    "Parent:  ".bold(),
+                    commit.parent_commit_sha.as_deref().unwrap_or("-").into(),
+                ]));
+                if let Some(reverted) = reverts.reverted(sha) {
+                    lines.push(Line::from(vec!["Reverts: ".bold(), reverted.magenta()]));
+                }
+                if let Some(revert) = reverts.reverted_by(sha) {
+                    lines.push(Line::from(vec!["Reverted by: ".bold(), revert.magenta()]));
+                }
+                lines.push(Line::from(""));
+                lines.push(linkified_line(&commit.commit_message, issue_linker).bold());
+                if !details.body.is_empty() {
+                    lines.push(Line::from(""));
+                    lines.extend(
+                        details
+                            .body
+                            .lines()
+                            .map(|line| linkified_line(line, issue_linker)),
+                    );
+                }
+                for (key, values) in &commit.trailers {
+                    for value in values {
+                        lines.push(Line::from(vec![
+                            format!("{}: ", key).bold(),
+                            value.as_str().into(),
+                        ]));
+                    }
+                }
+                for url in issue_linker
+                    .map(|l| l.urls(&commit.commit_message))
+                    .unwrap_or_default()
+                {
+                    lines.push(Line::from(url.blue().underlined()));
+                }
+                if !details.changed_files.is_empty() {
+                    lines.push(Line::from(""));
+                    lines.push(Line::from(
+                        format!("Changed files ({}):", details.changed_files.len()).bold(),
+                    ));
+                    for (status, path) in details.changed_files.iter().take(MAX_CHANGED_FILES) {
+                        lines.push(Line::from(vec![
+                            format!("  {:<5}", status).yellow(),
+                            path.as_str().into(),
+                        ]));
+                    }
+                    if details.changed_files.len() > MAX_CHANGED_FILES {
+                        lines.push(Line::from(
+                            format!(
+                                "  and {} more",
+                                details.changed_files.len() - MAX_CHANGED_FILES
+                            )
+                            .dark_gray(),
+                        ));
+                    }
+                }
+            }
+            lines.push(Line::from(""));
+            let mut keys = vec![
+                "r".yellow().bold(),
+                " revert  ".into(),
+                "p".yellow().bold(),
+                " cherry-pick  ".into(),
+            ];
+            if reverts.linked(sha).is_some() {
+                keys.extend(["u".yellow().bold(), " revert link  ".into()]);
+            }
+            keys.extend(["Esc".yellow().bold(), " close".into()]);
+            lines.push(Line::from(keys));
+            ("Commit", lines)
+        }
+        Popup::ConfirmCommitAction(action, sha) => {
+            let command = format!("git {}", action.git_args(sha).join(" "));
+            let description = match action {
+                CommitAction::Revert => "Revert the changes of this commit",
+                CommitAction::CherryPick => "Apply the changes of this commit",
+            };
+            let lines = vec![
+                Line::from(format!("{} in the working tree?", description)),
+                Line::from(""),
+                Line::from(command.green()),
+                Line::from(""),
+                Line::from(vec![
+                    "y".yellow().bold(),
+                    " run  ".into(),
+                    "n".yellow().bold(),
+                    " cancel".into(),
+                ]),
+            ];
+            ("Confirm", lines)
+        }
+        Popup::Prompt(kind, text) => (
+            kind.title(),
+            vec![Line::from(vec![text.as_str().into(), "█".into()])],
+        ),
+        Popup::Statistics(_, _)
+        | Popup::ContextMenu(_)
+        | Popup::Todos(_)
+        | Popup::Blocks(_)
+        | Popup::CommitHistory(_)
+        | Popup::Help(_)
+        | Popup::ExpandedLine(_, _)
+        | Popup::Legend => return,
+        Popup::Message(message) => (
+            "Message",
+            vec![
+                Line::from(message.as_str()),
+                Line::from(""),
+                Line::from("Press any key to close".dark_gray()),
+            ],
+        ),
+    };
+
+    let height = lines.len() as u16 + 2;
+    let area = centered_rect(80, height, frame.size());
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+// Information needed for creating the table rows for blame lines.
+#[derive(Clone, Copy)]
+struct RowContext<'a> {
+    commit_cache: &'a HashMap<String, Commit>,
+    theme: &'a Theme,
+    columns: &'a [Column],
+    annotations: Option<&'a dyn AnnotationProvider>,
+    // Owners of the file, if blocks by authors who aren't owners should be flagged.
+    audit_owners: Option<&'a [String]>,
+    issue_linker: Option<&'a IssueLinker>,
+    age_warning: AgeWarning,
+    now: i64,
+    locale: Locale,
+    // Review whose introduced lines are marked, if they aren't the only ones shown.
+    marked: Option<&'a Review>,
+    // When the history of the repository started, for the age gauge.
+    history_start: Option<i64>,
+    // Search query whose matches in the contents are highlighted.
+    search_query: &'a str,
+    // Whether the commit cells are tinted with colors derived from the hashes.
+    commit_accents: bool,
+    // Times of the oldest and the newest commit of the file, if the time
+    // cells are tinted on a gradient between them.
+    heat_range: Option<(i64, i64)>,
+    // Whether lines blamed past ignored commits show the ignored commits.
+    show_skipped_commits: bool,
+    date_format: DateFormat,
+    // Author picked in the statistics, whose lines aren't dimmed.
+    author_filter: Option<&'a str>,
+    // Number of columns by which the contents are scrolled to the right.
+    contents_offset: usize,
+    // Width of the contents column, if long lines are wrapped across rows
+    // instead of being cut off.
+    wrap_width: Option<usize>,
+    message_cleaner: MessageCleaner,
+    // Whether the types of conventional commits are shown as badges.
+    commit_type_badges: bool,
+    reverts: &'a Reverts,
+}
+
+// The highlighted contents of a line, with the matches of the search query
+// highlighted, and wrapped or scrolled sideways.
+fn contents_text<'a>(item: &'a BlameLine, context: &RowContext) -> Text<'a> {
+    let mut text = ansi_to_tui::IntoText::to_text(&item.contents).unwrap();
+    if !context.search_query.is_empty() {
+        text.lines = text
+            .lines
+            .into_iter()
+            .map(|line| highlight_matches(line, context.search_query))
+            .collect();
+    }
+    if let Some(width) = context.wrap_width.filter(|&w| w > 0) {
+        text.lines = text
+            .lines
+            .into_iter()
+            .flat_map(|line| wrap_line(line, width))
+            .collect();
+    } else if context.contents_offset > 0 {
+        text.lines = text
+            .lines
+            .into_iter()
+            .map(|line| scroll_line(line, context.contents_offset))
+            .collect();
+    }
+    text
+}
+
+// Split a line of highlighted contents into lines of at most the given width,
+// keeping the styles. Characters are kept whole, including combining marks.
+fn wrap_line(line: Line, width: usize) -> Vec<Line> {
+    let mut lines = vec![];
+    let mut spans = vec![];
+    let mut line_width = 0;
+    for span in line.spans {
+        let mut content = String::new();
+        for grapheme in span.styled_graphemes(Style::default()) {
+            let grapheme_width = Span::raw(grapheme.symbol).width();
+            if line_width + grapheme_width > width && line_width > 0 {
+                if !content.is_empty() {
+                    spans.push(Span::styled(mem::take(&mut content), span.style));
+                }
+                lines.push(Line::from(mem::take(&mut spans)));
+                line_width = 0;
+            }
+            content.push_str(grapheme.symbol);
+            line_width += grapheme_width;
+        }
+        if !content.is_empty() {
+            spans.push(Span::styled(content, span.style));
+        }
+    }
+    lines.push(Line::from(spans));
+    lines
+}
+
+// Cut the first columns off a line of highlighted contents, keeping the
+// styles of the rest. Characters are cut whole, including combining marks,
+// and a wide character which is cut in half leaves a space behind.
+fn scroll_line(line: Line, offset: usize) -> Line {
+    let mut skipped = 0;
+    let mut spans = vec![];
+    for span in line.spans {
+        if skipped >= offset {
+            spans.push(span);
+            continue;
+        }
+        let mut content = String::new();
+        for grapheme in span.styled_graphemes(Style::default()) {
+            if skipped >= offset {
+                content.push_str(grapheme.symbol);
+                continue;
+            }
+            skipped += Span::raw(grapheme.symbol).width();
+            if skipped > offset {
+                content.push_str(&" ".repeat(skipped - offset));
+            }
+        }
+        if !content.is_empty() {
+            spans.push(Span::styled(content, span.style));
+        }
+    }
+    Line { spans, ..line }
+}
+
+// Colors at the newest and the oldest end of the heatmap gradient: a warm
+// red for recent changes, and a cold blue for old ones. Both are dark enough
+// for white text.
+const HEAT_NEWEST: (u8, u8, u8) = (0xa8, 0x32, 0x1e);
+const HEAT_OLDEST: (u8, u8, u8) = (0x1e, 0x3c, 0x6e);
+
+// The color of a commit time on the heatmap gradient between the oldest and
+// the newest commit time of a file.
+fn heat_color(time: i64, (oldest, newest): (i64, i64)) -> Color {
+    let age = if newest > oldest {
+        (newest - time) as f64 / (newest - oldest) as f64
+    } else {
+        0.0
+    };
+    let mix = |new: u8, old: u8| (new as f64 + (old as f64 - new as f64) * age).round() as u8;
+    Color::Rgb(
+        mix(HEAT_NEWEST.0, HEAT_OLDEST.0),
+        mix(HEAT_NEWEST.1, HEAT_OLDEST.1),
+        mix(HEAT_NEWEST.2, HEAT_OLDEST.2),
+    )
+}
+
+// Format the date of a commit for the TIME column.
+fn format_date(commit: &Commit, date_format: DateFormat, locale: Locale, now: i64) -> String {
+    match date_format {
+        DateFormat::Absolute => locale.format_date(&commit.timestamp),
+        DateFormat::Relative => age::format_relative(commit.time, now),
+    }
 }
 
 // Creates a table row for a blame line and the previous line's commit sha
@@ -87,30 +1297,741 @@ fn table_row_for_blame_line<'a>(
     previous_ref: &str,
     commit_sha: &'a str,
     item: &'a BlameLine,
-    commit_cache: &'a HashMap<String, Commit>,
-    columns: &[Column],
+    context: &RowContext<'a>,
 ) -> Row<'a> {
     // If the commit sha of the current line matches the commit sha of the
     // previous line, then use empty cells for the timestamp, author, sha and
     // commit message. The effect of this is that only the first line of a block
     // of lines with the same commit will have the info shown which makes
     // for a cleaner UI experience.
-    let mut cells = if item.commit_sha == previous_ref {
-        vec![empty_cell(), empty_cell(), empty_cell(), empty_cell()]
+    let same_block = commit_sha == previous_ref;
+    let commit_context = context.commit_cache.get(commit_sha).unwrap();
+
+    // Wrapped contents make the row as high as their number of lines.
+    let mut height = 1;
+    let mut cells = context
+        .columns
+        .iter()
+        .map(|column| {
+            // The tint of the commit column runs down the whole block.
+            if column.kind == ColumnKind::Commit && context.commit_accents {
+                let accent = colors::commit_accent(commit_sha);
+                if same_block {
+                    return empty_cell().bg(accent);
+                }
+                // Light text for contrast with the dark tints, which include greens.
+                return Cell::from(commit_sha).white().bg(accent);
+            }
+            // Like the tint of the commit column, the heat runs down the whole block.
+            if let (ColumnKind::Time, Some(range)) = (column.kind, context.heat_range) {
+                let heat = heat_color(commit_context.time, range);
+                if same_block {
+                    return empty_cell().bg(heat);
+                }
+                let date = format_date(
+                    commit_context,
+                    context.date_format,
+                    context.locale,
+                    context.now,
+                );
+                return Cell::from(date).white().bg(heat);
+            }
+            if same_block && column.kind.is_commit_info() {
+                return empty_cell();
+            }
+            let style = if commit_context.is_uncommitted() && column.kind.is_commit_info() {
+                context.theme.uncommitted
+            } else {
+                column.style
+            };
+
+            match column.kind {
+                ColumnKind::Time => Cell::from(format_date(
+                    commit_context,
+                    context.date_format,
+                    context.locale,
+                    context.now,
+                )),
+                ColumnKind::Author => {
+                    // Flag authors who aren't among the owners of the file.
+                    if context
+                        .audit_owners
+                        .is_some_and(|owners| !codeowners::is_owner(owners, commit_context))
+                    {
+                        return Cell::from(format!("! {}", commit_context.author))
+                            .style(column.style.on_yellow());
+                    }
+                    Cell::from(commit_context.author.as_str())
+                }
+                ColumnKind::Commit => Cell::from(commit_sha).green(),
+                ColumnKind::Message => {
+                    let message = &commit_context.commit_message;
+                    let badge =
+                        message::commit_type(message).filter(|_| context.commit_type_badges);
+                    let mut line = match badge {
+                        // The badge takes the place of the type in the message.
+                        Some(kind) => {
+                            let cleaner = MessageCleaner {
+                                strip_commit_types: true,
+                            };
+                            let mut line =
+                                linkified_line(cleaner.clean(message), context.issue_linker);
+                            line.spans.insert(0, commit_type_badge(kind));
+                            line.spans.insert(1, Span::raw(" "));
+                            line
+                        }
+                        None => linkified_line(
+                            context.message_cleaner.clean(message),
+                            context.issue_linker,
+                        ),
+                    };
+                    // Reverts are marked, and the changes they undid crossed out.
+                    if context.reverts.reverted(commit_sha).is_some() {
+                        line.spans.insert(0, Span::raw("↺ ").magenta());
+                    }
+                    if context.reverts.reverted_by(commit_sha).is_some() {
+                        line = line.crossed_out();
+                    }
+                    Cell::from(line)
+                }
+                ColumnKind::Annotation => {
+                    let annotation = item
+                        .line_number
+                        .parse()
+                        .ok()
+                        .and_then(|line| context.annotations.and_then(|a| a.annotation(line)));
+                    match annotation {
+                        Some(a) => return Cell::from(a.marker.as_str()).style(a.style()),
+                        None => empty_cell(),
+                    }
+                }
+                // Shown on every line, so that the bars form a gauge down the file.
+                ColumnKind::Age => match context.history_start {
+                    Some(start) => {
+                        Cell::from(age::gauge(commit_context.time, start, context.now).to_string())
+                    }
+                    None => empty_cell(),
+                },
+                // Shown on every line, with the file only for lines which
+                // came from another file.
+                ColumnKind::Origin => match &item.original_path {
+                    Some(path) => Cell::from(format!("{}:{}", path, item.original_line)),
+                    None => Cell::from(item.original_line.to_string()),
+                },
+                ColumnKind::Line => {
+                    // Mark the lines introduced by the commit under review.
+                    if context.marked.is_some_and(|r| r.is_introduced(commit_sha)) {
+                        return Cell::from(format!("+{}", item.line_number))
+                            .black()
+                            .on_green();
+                    }
+                    // Highlight the line numbers of lines matching the age warning rule.
+                    if context
+                        .age_warning
+                        .matches(commit_context.time, context.now)
+                    {
+                        return Cell::from(item.line_number.as_str()).black().on_yellow();
+                    }
+                    // Mark lines blamed past ignored commits, like git
+                    // blame with blame.markIgnoredLines, and truncated lines,
+                    // since the marker at the end of the contents is usually
+                    // out of view.
+                    let ignored = if item.skipped_commit.is_some() {
+                        "*"
+                    } else {
+                        ""
+                    };
+                    let truncated = if item.truncated { "…" } else { "" };
+                    if item.skipped_commit.is_some() || item.truncated {
+                        return Cell::from(format!("{}{}{}", item.line_number, ignored, truncated))
+                            .style(column.style);
+                    }
+                    Cell::from(item.line_number.as_str())
+                }
+                ColumnKind::Contents => {
+                    let text = contents_text(item, context);
+                    height = text.lines.len().max(1) as u16;
+                    Cell::from(text)
+                }
+            }
+            .style(style)
+        })
+        .collect();
+
+    // The dividers run down all lines of wrapped rows.
+    let divider = vec![context.theme.divider.as_str(); height as usize].join("\n");
+    cells = insert_between(cells, Cell::from(divider));
+    let row = Row::new(cells).height(height).bottom_margin(0);
+    if context
+        .author_filter
+        .is_some_and(|author| author != commit_context.author)
+    {
+        return row.style(Style::default().add_modifier(Modifier::DIM));
+    }
+    row
+}
+
+// Highlight the occurrences of a search query in a line, keeping the styles
+// of the syntax highlighting around them. Spans are split where matches
+// start and end.
+fn highlight_matches<'a>(line: Line<'a>, query: &str) -> Line<'a> {
+    let text: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+    let matches = search::find_matches(&text, query);
+    if matches.is_empty() {
+        return line;
+    }
+
+    let mut spans = vec![];
+    let mut offset = 0;
+    for span in line.spans {
+        let content = span.content.as_ref();
+        let end = offset + content.len();
+        // Boundaries of the matches within the span, relative to its start.
+        let mut cuts = vec![0, content.len()];
+        for m in matches.iter().filter(|m| m.start < end && m.end > offset) {
+            cuts.push(m.start.saturating_sub(offset));
+            cuts.push((m.end - offset).min(content.len()));
+        }
+        cuts.sort_unstable();
+        cuts.dedup();
+        for pair in cuts.windows(2) {
+            let (start, stop) = (pair[0], pair[1]);
+            let matched = matches
+                .iter()
+                .any(|m| m.start <= offset + start && offset + stop <= m.end);
+            let style = if matched {
+                span.style.add_modifier(Modifier::REVERSED)
+            } else {
+                span.style
+            };
+            spans.push(Span::styled(content[start..stop].to_string(), style));
+        }
+        offset = end;
+    }
+    Line::from(spans).style(line.style)
+}
+
+// Renders the search prompt in the bottom line of the screen, like in less
+// or vim.
+fn render_search_bar(text: &str, frame: &mut Frame) {
+    let size = frame.size();
+    let area = Rect::new(
+        size.x,
+        size.y + size.height.saturating_sub(1),
+        size.width,
+        1,
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![
+            "/".yellow().bold(),
+            text.into(),
+            "█".into(),
+        ])),
+        area,
+    );
+}
+
+// Renders the ownership statistics of the file on top of the blame table,
+// with a gauge showing the share of lines of each of the top authors.
+fn render_statistics(app: &App, stats: &FileStats, selected: usize, frame: &mut Frame) {
+    let locale = app.locale;
+    let size = frame.size();
+    let area = centered_rect(size.width * 8 / 10, size.height * 8 / 10, size);
+    let title = format!("Statistics, {} (s to sort)", app.author_order.label());
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+
+    let summary = vec![
+        Line::from(vec![
+            "Lines: ".bold(),
+            locale.format_number(stats.total_lines).into(),
+            "  Authors: ".bold(),
+            locale.format_number(stats.authors.len()).into(),
+            "  Bus factor: ".bold(),
+            stats.bus_factor.to_string().yellow(),
+        ]),
+        Line::from(
+            "The bus factor is the number of authors who last changed more than half of the lines."
+                .dark_gray(),
+        ),
+    ];
+    let rects = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(5),
+        Constraint::Fill(1),
+    ])
+    .split(inner);
+    frame.render_widget(Paragraph::new(summary).wrap(Wrap { trim: false }), rects[0]);
+
+    // Sparkline of the commits per month, showing the most recent months if
+    // the history doesn't fit.
+    let history = &stats.monthly_commits;
+    let [label_area, sparkline_area, _] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(3),
+        Constraint::Length(1),
+    ])
+    .areas(rects[1]);
+    let label = format!(
+        "Commits per month ({} to {}):",
+        history.first_month, history.last_month
+    );
+    frame.render_widget(Paragraph::new(label.bold()), label_area);
+    let skip = history
+        .counts
+        .len()
+        .saturating_sub(sparkline_area.width as usize);
+    frame.render_widget(
+        Sparkline::default()
+            .data(&history.counts[skip..])
+            .style(Style::default().fg(Color::Blue)),
+        sparkline_area,
+    );
+
+    // One row per author, for as many authors as fit, scrolled so that the
+    // highlighted author is in view. The filtered author is marked.
+    let rows =
+        Layout::vertical(vec![Constraint::Length(1); rects[2].height as usize]).split(rects[2]);
+    let skip = (selected + 1).saturating_sub(rows.len());
+    for (i, (author, row)) in stats.authors.iter().skip(skip).zip(rows.iter()).enumerate() {
+        let i = i + skip;
+        let columns = Layout::horizontal([Constraint::Length(20), Constraint::Fill(1)]).split(*row);
+        let ratio = stats.ratio(author);
+        let marker = if app.author_filter.as_ref() == Some(&author.author) {
+            "▸ "
+        } else {
+            ""
+        };
+        let mut name = Paragraph::new(format!("{}{}", marker, author.author).red());
+        if i == selected {
+            name = name.style(app.theme.selected);
+        }
+        frame.render_widget(name, columns[0]);
+        frame.render_widget(
+            Gauge::default()
+                .gauge_style(Style::default().fg(Color::Green).bg(Color::Black))
+                .ratio(ratio)
+                .label(format!(
+                    "{} lines ({})",
+                    locale.format_number(author.lines),
+                    locale.format_percent(ratio)
+                )),
+            columns[1],
+        );
+    }
+}
+
+// Renders the list of TODO, FIXME and HACK comments, with the author and age
+// of each comment's line.
+fn render_todos(app: &App, selected: usize, frame: &mut Frame) {
+    let Some(file_blame) = &app.file_blame else {
+        return;
+    };
+    let now = age::now();
+    let items: Vec<ListItem> = app
+        .todo_lines()
+        .into_iter()
+        .map(|i| {
+            let line = &file_blame.blame_lines[i];
+            let commit = app.commit_cache.get(&line.commit_sha);
+            let author = commit.map(|c| c.author.as_str()).unwrap_or("");
+            let age = commit
+                .map(|c| age::format_duration(now - c.time))
+                .unwrap_or_default();
+            ListItem::new(Line::from(vec![
+                format!("{:>5} ", line.line_number).yellow(),
+                format!("{:<15.15} ", author).red(),
+                format!("{:>4} ", age).blue(),
+                line.plain_contents.trim().into(),
+            ]))
+        })
+        .collect();
+
+    let size = frame.size();
+    let area = centered_rect(size.width * 8 / 10, size.height * 8 / 10, size);
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("TODO, FIXME and HACK comments"),
+        )
+        .highlight_style(app.theme.selected);
+    let mut state = ListState::default().with_selected(Some(selected));
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+// Renders the overview of the file's blocks, with the commit, author and age
+// of each block, the lines it spans and a preview of its first line.
+fn render_blocks(app: &App, selected: usize, frame: &mut Frame) {
+    let Some(file_blame) = &app.file_blame else {
+        return;
+    };
+    let now = age::now();
+    let blocks = app.blocks();
+    let items: Vec<ListItem> = blocks
+        .iter()
+        .map(|block| {
+            let first = &file_blame.blame_lines[*block.start()];
+            let last = &file_blame.blame_lines[*block.end()];
+            let commit = app.commit_cache.get(&first.commit_sha);
+            let author = commit.map(|c| c.author.as_str()).unwrap_or("");
+            let age = commit
+                .map(|c| age::format_duration(now - c.time))
+                .unwrap_or_default();
+            let lines = format!("{}-{}", first.line_number, last.line_number);
+            ListItem::new(Line::from(vec![
+                format!("{:.8} ", first.commit_sha).green(),
+                format!("{:<15.15} ", author).red(),
+                format!("{:>4} ", age).blue(),
+                format!("{:>11} ", lines).yellow(),
+                first.plain_contents.trim().into(),
+            ]))
+        })
+        .collect();
+
+    let size = frame.size();
+    let area = centered_rect(size.width * 8 / 10, size.height * 8 / 10, size);
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Blocks: {}, {} (s to sort)",
+            blocks.len(),
+            app.block_order.label()
+        )))
+        .highlight_style(app.theme.selected);
+    let mut state = ListState::default().with_selected(Some(selected));
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+// The commits on the commit stack, oldest first, e.g. "HEAD › 91d2a43e".
+// Long stacks are shortened to the first and the last commits.
+fn breadcrumb(commit_stack: &[(String, String)]) -> String {
+    let labels: Vec<&str> = commit_stack.iter().map(|(sha, _)| ref_label(sha)).collect();
+    if labels.len() > 4 {
+        format!(
+            "{} › … › {}",
+            labels[0],
+            labels[labels.len() - 2..].join(" › ")
+        )
+    } else {
+        labels.join(" › ")
+    }
+}
+
+// A ref as it was given, e.g. "HEAD" or "main", or a commit's sha shortened.
+fn ref_label(sha: &str) -> &str {
+    if sha.len() == 40 && sha.bytes().all(|b| b.is_ascii_hexdigit()) {
+        &sha[..8]
     } else {
-        let commit_context = commit_cache.get(&item.commit_sha).unwrap();
+        sha
+    }
+}
+
+// Renders the list of the commits traveled through, with the current commit
+// last, which is where the next travel back in time starts from.
+fn render_commit_history(app: &App, selected: usize, frame: &mut Frame) {
+    let current = (app.commit_sha.clone(), app.file_path.clone());
+    let items: Vec<ListItem> = app
+        .commit_stack
+        .iter()
+        .chain([&current])
+        .enumerate()
+        .map(|(i, (sha, path))| {
+            let summary = app
+                .commit_cache
+                .get(sha)
+                .map(|c| {
+                    MessageCleaner::default()
+                        .clean(&c.commit_message)
+                        .into_owned()
+                })
+                .unwrap_or_default();
+            let marker = if i == app.commit_stack.len() {
+                "* "
+            } else {
+                "  "
+            };
+            ListItem::new(Line::from(vec![
+                marker.into(),
+                format!("{:<8} ", ref_label(sha)).green(),
+                format!("{} ", path).yellow(),
+                summary.into(),
+            ]))
+        })
+        .collect();
+
+    let size = frame.size();
+    let height = (items.len() as u16 + 2).min(size.height * 8 / 10);
+    let area = centered_rect(size.width * 8 / 10, height, size);
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Commits traveled through: {}",
+            app.commit_stack.len()
+        )))
+        .highlight_style(app.theme.selected);
+    let mut state = ListState::default().with_selected(Some(selected));
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+// Renders the overlay listing all keys of the blame table and what they do,
+// from the same bindings which the keys are handled with.
+fn render_help(key_map: &KeyMap, scroll: u16, frame: &mut Frame) {
+    let entries = key_map.help_entries();
+    let width = entries
+        .iter()
+        .map(|(keys, _)| keys.len())
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<Line> = entries
+        .iter()
+        .map(|(keys, action)| {
+            Line::from(vec![
+                format!("{:<width$}  ", keys, width = width).yellow(),
+                action.description().into(),
+            ])
+        })
+        .collect();
+
+    let size = frame.size();
+    let area = centered_rect(size.width * 8 / 10, size.height * 8 / 10, size);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).scroll((scroll, 0)).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Keys (up/down to scroll, esc to close)"),
+        ),
+        area,
+    );
+}
+
+// Renders the legend: a sample of each color and marker which the blame can
+// currently show, next to what it means. Only the columns which are shown
+// and the highlights which are enabled are listed.
+fn render_legend(app: &App, frame: &mut Frame) {
+    let mut entries: Vec<(Span, String)> = vec![];
+    for column in &shown_columns(app) {
+        let meaning = match column.kind {
+            ColumnKind::Time if app.heatmap => {
+                "date of the last change, tinted from red for the newest to blue for the oldest"
+            }
+            ColumnKind::Time => "date of the last change to the line",
+            ColumnKind::Author => "author of the last change",
+            ColumnKind::Commit if app.commit_accents => {
+                "commit of the last change, tinted with a color derived from its hash"
+            }
+            ColumnKind::Commit => "commit of the last change",
+            ColumnKind::Message => "summary of the commit message",
+            ColumnKind::Annotation => "annotation from --annotations",
+            ColumnKind::Age => "age relative to the repository's history, ▁ newest to █ oldest",
+            ColumnKind::Origin => "file and line in the line's commit, for moved and copied lines",
+            ColumnKind::Line => "line number",
+            ColumnKind::Contents => "contents, syntax highlighted by file type",
+        };
+        entries.push((
+            Span::styled(column.header_name(), column.style),
+            meaning.to_string(),
+        ));
+    }
+    entries.push((
+        Span::raw("(blank)"),
+        "same commit as the line above: commit columns are only filled in for the first line of a block"
+            .to_string(),
+    ));
+    entries.push((
+        Span::styled("line", app.theme.selected),
+        "selected line".to_string(),
+    ));
+    entries.push((
+        Span::styled("line", app.theme.range),
+        "selected range of lines, see v".to_string(),
+    ));
+    entries.push((
+        Span::raw("12…").yellow(),
+        format!(
+            "line longer than {} characters, cut off, see e",
+            app.parse_options.max_line_length
+        ),
+    ));
+    if app.parse_options.ignores_revs() {
+        entries.push((
+            Span::raw("12*").yellow(),
+            "line blamed past an ignored commit, see *".to_string(),
+        ));
+    }
+    if app.commit_sha == WORKTREE {
+        entries.push((
+            Span::styled("00000000", app.theme.uncommitted),
+            "change in the working tree which is not committed yet".to_string(),
+        ));
+    }
+    let mut warnings = vec![];
+    if let Some(older_than) = app.age_warning.older_than {
+        warnings.push(format!("older than {}", age::format_duration(older_than)));
+    }
+    if let Some(newer_than) = app.age_warning.newer_than {
+        warnings.push(format!("newer than {}", age::format_duration(newer_than)));
+    }
+    if !warnings.is_empty() {
+        entries.push((
+            Span::raw("12").black().on_yellow(),
+            format!("line changed {}", warnings.join(" or ")),
+        ));
+    }
+    if let Some(file_blame) = &app.file_blame {
+        let shas = file_blame.blame_lines.iter().map(|l| l.commit_sha.as_str());
+        let (mut reverts, mut reverted) = (false, false);
+        for sha in shas {
+            reverts |= app.reverts.reverted(sha).is_some();
+            reverted |= app.reverts.reverted_by(sha).is_some();
+        }
+        if reverts {
+            entries.push((
+                Span::raw("↺ message").magenta(),
+                "commit reverting another commit, see U".to_string(),
+            ));
+        }
+        if reverted {
+            entries.push((
+                Span::raw("message").crossed_out(),
+                "commit which was reverted later, see U".to_string(),
+            ));
+        }
+    }
+    if app.commit_type_badges {
+        entries.push((
+            commit_type_badge("fix"),
+            "type of a conventional commit, e.g. feat, fix or refactor, see K".to_string(),
+        ));
+    }
+    if let Some(author) = &app.author_filter {
+        entries.push((
+            Span::raw("line").dim(),
+            format!("line by another author than {}, see A", author),
+        ));
+    }
+    if app.owners_audit {
+        entries.push((
+            Span::raw("! name").red().on_yellow(),
+            "author who isn't a code owner of the file".to_string(),
+        ));
+    }
+    if let Some(review) = app.review.as_ref().filter(|r| !r.hide_other_lines) {
+        entries.push((
+            Span::raw("+12").black().on_green(),
+            format!("line introduced by {}", review.range),
+        ));
+    }
+    if app.issue_linker.is_some() {
+        entries.push((
+            Span::raw("PROJ-1").underlined(),
+            "issue reference, see i".to_string(),
+        ));
+    }
+
+    let sample_width = entries
+        .iter()
+        .map(|(sample, _)| sample.width())
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<Line> = entries
+        .into_iter()
+        .map(|(sample, meaning)| {
+            let padding = " ".repeat(sample_width - sample.width() + 2);
+            Line::from(vec![sample, padding.into(), meaning.into()])
+        })
+        .collect();
 
-        vec![
-            Cell::from(commit_context.timestamp.as_str()).style(columns[0].style),
-            Cell::from(commit_context.author.as_str()).style(columns[1].style),
-            Cell::from(commit_sha).green().style(columns[2].style),
-            Cell::from(commit_context.commit_message.as_str()).style(columns[3].style),
-        ]
+    let size = frame.size();
+    let area = centered_rect(size.width * 8 / 10, lines.len() as u16 + 2, size);
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title("Legend"));
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+// Renders the full contents of a line, wrapped and scrolled.
+fn render_expanded_line(app: &App, i: usize, scroll: u16, frame: &mut Frame) {
+    let Some(line) = app.file_blame.as_ref().and_then(|fb| fb.blame_lines.get(i)) else {
+        return;
+    };
+    let size = frame.size();
+    let area = centered_rect(size.width * 8 / 10, size.height * 8 / 10, size);
+    let title = format!(
+        "Line {} ({} chars, up/down to scroll)",
+        line.line_number,
+        line.plain_contents.chars().count()
+    );
+    let paragraph = Paragraph::new(line.plain_contents.as_str())
+        .wrap(Wrap { trim: false })
+        .scroll((scroll, 0))
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+// Renders performance metrics in the top right corner, for diagnosing slow
+// repositories.
+fn render_metrics(app: &App, frame: &mut Frame) {
+    let snapshot = metrics::snapshot();
+    let last_git_command = match &snapshot.last_git_command {
+        Some((name, duration)) => format!("git {} {:.1?}", name, duration),
+        None => "none".to_string(),
     };
+    let lines = vec![
+        Line::from(format!("frame: {:.1?}", app.frame_time)),
+        Line::from(format!(
+            "git commands: {}",
+            app.locale.format_number(snapshot.git_commands as usize)
+        )),
+        Line::from(format!("last: {}", last_git_command)),
+        Line::from(format!(
+            "commit cache: {} commits, {} hits",
+            app.locale.format_number(app.commit_cache.len()),
+            app.locale.format_percent(snapshot.commit_cache_hit_rate())
+        )),
+        Line::from(format!(
+            "memory: {} KiB of {} KiB",
+            app.locale.format_number(app.memory_usage() / 1024),
+            app.locale.format_number(app.memory_budget / 1024)
+        )),
+    ];
+
+    let size = frame.size();
+    let width = lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16 + 2;
+    let width = width.min(size.width);
+    let height = (lines.len() as u16 + 2).min(size.height);
+    let area = Rect::new(size.x + size.width - width, size.y, width, height);
+    let paragraph =
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Metrics"));
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
 
-    let highlighted_text = ansi_to_tui::IntoText::to_text(&(item.contents)).unwrap();
-    cells.push(Cell::from(item.line_number.as_str()).style(columns[4].style));
-    cells.push(Cell::from(highlighted_text).style(columns[5].style));
-    cells = insert_between(cells, divider_cell());
-    Row::new(cells).height(1).bottom_margin(0)
+// Renders the context menu with the actions for the selected line.
+fn render_context_menu(
+    items: &[Action],
+    selected: usize,
+    highlight_style: Style,
+    frame: &mut Frame,
+) {
+    let width = items
+        .iter()
+        .map(|i| i.description().len())
+        .max()
+        .unwrap_or(0) as u16
+        + 4;
+    let area = centered_rect(width, items.len() as u16 + 2, frame.size());
+    let list = List::new(items.iter().map(|i| i.description()))
+        .block(Block::default().borders(Borders::ALL).title("Actions"))
+        .highlight_style(highlight_style);
+    let mut state = ListState::default().with_selected(Some(selected));
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(list, area, &mut state);
 }